@@ -83,8 +83,8 @@ impl SvgRenderer {
             mesh.x_desc(&data.x_label)
                 .y_desc(&data.y_label)
                 .x_label_formatter(&|x| {
-                    // Convert Unix timestamp to UTC date string
-                    if let Ok(dt) = time::OffsetDateTime::from_unix_timestamp(*x) {
+                    // Convert epoch milliseconds to UTC date string
+                    if let Some(dt) = ferrview_common::timestamp::from_millis(*x) {
                         format!(
                             "{:04}-{:02}-{:02}\n{:02}:{:02}",
                             dt.year(),
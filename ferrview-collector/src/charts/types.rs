@@ -5,7 +5,7 @@ use std::fmt;
 /// Represents a single data point in a time-series
 #[derive(Debug, Clone)]
 pub struct MetricPoint {
-    /// Unix timestamp or index
+    /// Unix epoch milliseconds or index
     pub timestamp: i64,
     /// Metric value
     pub value: f64,
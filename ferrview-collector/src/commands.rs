@@ -0,0 +1,313 @@
+// src/commands.rs
+
+use argh::FromArgs;
+use ferrview_common::protocol::RegistrationStatus;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+use crate::store::auth::{Approval, AuthStore, RegistrationInfo};
+use crate::store::errors::StoreError;
+
+/// Administrative commands that run against the data directory and exit
+#[derive(FromArgs, Debug)]
+#[argh(subcommand)]
+pub enum Command {
+    Token(TokenCommand),
+    Secret(SecretCommand),
+    Registration(RegistrationCommand),
+}
+
+/// manage the bearer tokens nodes use to send data
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "token")]
+pub struct TokenCommand {
+    #[argh(subcommand)]
+    action: TokenAction,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand)]
+enum TokenAction {
+    Create(CreateToken),
+    List(ListTokens),
+    Revoke(RevokeToken),
+}
+
+/// create a token and print it; it cannot be shown again
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "create")]
+struct CreateToken {
+    /// node_id the token may write for (repeatable)
+    #[argh(option)]
+    node_id: Vec<String>,
+
+    /// free-form note, e.g. the host the token is for
+    #[argh(option, default = "String::new()")]
+    description: String,
+}
+
+/// list tokens and the node_ids they may write for
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "list")]
+struct ListTokens {}
+
+/// revoke a token; nodes using it are rejected from then on
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "revoke")]
+struct RevokeToken {
+    /// token id, as shown by `token list`
+    #[argh(positional)]
+    id: i64,
+}
+
+/// manage the secrets nodes sign their batches with
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "secret")]
+pub struct SecretCommand {
+    #[argh(subcommand)]
+    action: SecretAction,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand)]
+enum SecretAction {
+    Generate(GenerateSecret),
+    List(ListSecrets),
+    Remove(RemoveSecret),
+}
+
+/// generate a signing secret for a node, replacing its current one
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "generate")]
+struct GenerateSecret {
+    /// node_id the secret is for
+    #[argh(positional)]
+    node_id: String,
+}
+
+/// list nodes that have a signing secret
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "list")]
+struct ListSecrets {}
+
+/// remove a node's signing secret; it may then send unsigned batches again
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "remove")]
+struct RemoveSecret {
+    /// node_id whose secret to remove
+    #[argh(positional)]
+    node_id: String,
+}
+
+/// review nodes that registered for credentials
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "registration")]
+pub struct RegistrationCommand {
+    #[argh(subcommand)]
+    action: RegistrationAction,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand)]
+enum RegistrationAction {
+    List(ListRegistrations),
+    Approve(ApproveRegistration),
+    Reject(RejectRegistration),
+}
+
+/// list registrations, newest first
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "list")]
+struct ListRegistrations {
+    /// only show pending registrations
+    #[argh(switch)]
+    pending: bool,
+}
+
+/// approve a registration; the node picks up a token and signing secret
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "approve")]
+struct ApproveRegistration {
+    /// registration id, as shown by `registration list`
+    #[argh(positional)]
+    id: String,
+    /// approve even though the node already has credentials, replacing its
+    /// signing secret
+    #[argh(switch)]
+    replace: bool,
+}
+
+/// reject a registration; the node's batches stay refused
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "reject")]
+struct RejectRegistration {
+    /// registration id, as shown by `registration list`
+    #[argh(positional)]
+    id: String,
+}
+
+pub async fn run(command: Command, data_dir: &str) -> Result<(), StoreError> {
+    match command {
+        Command::Token(token) => run_token(token.action, data_dir).await,
+        Command::Secret(secret) => run_secret(secret.action, data_dir).await,
+        Command::Registration(registration) => {
+            run_registration(registration.action, data_dir).await
+        }
+    }
+}
+
+async fn run_token(action: TokenAction, data_dir: &str) -> Result<(), StoreError> {
+    let store = AuthStore::open(data_dir).await?;
+
+    match action {
+        TokenAction::Create(create) => {
+            let (id, token) = store
+                .create_token(&create.node_id, &create.description)
+                .await?;
+            println!("Token {} for {}:", id, create.node_id.join(", "));
+            println!("{}", token);
+        }
+        TokenAction::List(_) => {
+            println!(
+                "{:>4}  {:<20}  {:<20}  {:<30}  DESCRIPTION",
+                "ID", "CREATED", "REVOKED", "NODE IDS"
+            );
+            for token in store.list_tokens().await? {
+                println!(
+                    "{:>4}  {:<20}  {:<20}  {:<30}  {}",
+                    token.id,
+                    format_millis(token.created_at),
+                    token.revoked_at.map(format_millis).unwrap_or_default(),
+                    token.node_ids.join(","),
+                    token.description
+                );
+            }
+        }
+        TokenAction::Revoke(revoke) => {
+            if !store.revoke_token(revoke.id).await? {
+                return Err(StoreError::InvalidQuery(format!(
+                    "no active token with id {}",
+                    revoke.id
+                )));
+            }
+            println!("Revoked token {}", revoke.id);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_secret(action: SecretAction, data_dir: &str) -> Result<(), StoreError> {
+    let store = AuthStore::open(data_dir).await?;
+
+    match action {
+        SecretAction::Generate(generate) => {
+            let secret = store.rotate_node_secret(&generate.node_id).await?;
+            println!("Signing secret for {}:", generate.node_id);
+            println!("{}", secret);
+        }
+        SecretAction::List(_) => {
+            println!("{:<20}  NODE ID", "CREATED");
+            for (node_id, created_at) in store.list_node_secrets().await? {
+                println!("{:<20}  {}", format_millis(created_at), node_id);
+            }
+        }
+        SecretAction::Remove(remove) => {
+            if !store.remove_node_secret(&remove.node_id).await? {
+                return Err(StoreError::InvalidQuery(format!(
+                    "node {} has no signing secret",
+                    remove.node_id
+                )));
+            }
+            println!("Removed signing secret for {}", remove.node_id);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_registration(action: RegistrationAction, data_dir: &str) -> Result<(), StoreError> {
+    let store = AuthStore::open(data_dir).await?;
+
+    match action {
+        RegistrationAction::List(list) => {
+            let status = list.pending.then_some(RegistrationStatus::Pending);
+            println!(
+                "{:<16}  {:<8}  {:<20}  {:<21}  {:<36}  HOSTNAME",
+                "ID", "STATUS", "REQUESTED", "FROM", "NODE ID"
+            );
+            let registrations = store.list_registrations(status).await?;
+            // Marked so that a node_id taken over by someone else stands out
+            let marked =
+                |r: &RegistrationInfo| r.has_credentials && r.status == RegistrationStatus::Pending;
+            for registration in &registrations {
+                let marker = if marked(registration) { "*" } else { "" };
+                let status = format!("{}{}", registration.status.as_str(), marker);
+                println!(
+                    "{:<16}  {:<8}  {:<20}  {:<21}  {:<36}  {}",
+                    registration.id,
+                    status,
+                    format_millis(registration.requested_at),
+                    registration.remote_addr,
+                    registration.node_id,
+                    registration.hostname.as_deref().unwrap_or_default()
+                );
+            }
+            if registrations.iter().any(marked) {
+                println!();
+                println!(
+                    "* the node already has credentials; approving needs --replace and \
+                     replaces its signing secret"
+                );
+            }
+        }
+        RegistrationAction::Approve(approve) => {
+            match store
+                .approve_registration(&approve.id, approve.replace)
+                .await?
+            {
+                Approval::Approved => {}
+                Approval::NotPending => {
+                    return Err(StoreError::InvalidQuery(format!(
+                        "no pending registration with id {}",
+                        approve.id
+                    )));
+                }
+                Approval::HasCredentials => {
+                    return Err(StoreError::InvalidQuery(format!(
+                        "the node of registration {} already has credentials; \
+                         approve with --replace to replace its signing secret",
+                        approve.id
+                    )));
+                }
+            }
+            println!(
+                "Approved registration {}; the node collects its credential on its next check",
+                approve.id
+            );
+        }
+        RegistrationAction::Reject(reject) => {
+            if !store.reject_registration(&reject.id).await? {
+                return Err(StoreError::InvalidQuery(format!(
+                    "no pending registration with id {}",
+                    reject.id
+                )));
+            }
+            println!("Rejected registration {}", reject.id);
+        }
+    }
+
+    Ok(())
+}
+
+fn format_millis(millis: i64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000)
+        .ok()
+        .and_then(|t| {
+            t.format(format_description!(
+                "[year]-[month]-[day]T[hour]:[minute]:[second]Z"
+            ))
+            .ok()
+        })
+        .unwrap_or_else(|| millis.to_string())
+}
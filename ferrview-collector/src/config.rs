@@ -0,0 +1,274 @@
+// src/config.rs
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read {0}: {1}")]
+    Io(String, std::io::Error),
+
+    #[error("Invalid config: {0}")]
+    Parse(#[from] basic_toml::Error),
+
+    #[error("Invalid config: {0}")]
+    Invalid(String),
+}
+
+/// Settings from the optional `--config-file`, for everything that does not
+/// fit on the command line
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// Serve HTTPS instead of plain HTTP
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub registration: RegistrationConfig,
+    /// Nodes whose readings are pulled rather than pushed
+    #[serde(default)]
+    pub scrape: Vec<ScrapeTarget>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented to clients
+    pub cert_file: String,
+    /// PEM private key for `cert_file`
+    pub key_file: String,
+    /// PEM bundle of CAs for client certificates. When set, every client
+    /// must present a certificate signed by one of them.
+    pub client_ca_file: Option<String>,
+    /// Client certificate common name -> the node_id it may report as.
+    /// A common name not listed here may only report as itself.
+    #[serde(default)]
+    pub client_node_ids: BTreeMap<String, String>,
+}
+
+/// Credentials for `/api/v1/probe`: bearer tokens managed with
+/// `ferrview-collector token` and signing secrets managed with
+/// `ferrview-collector secret`
+#[derive(Debug, Deserialize)]
+pub struct AuthConfig {
+    /// Reject batches sent without a token. A token that is sent is always
+    /// checked, whether or not this is set.
+    #[serde(default)]
+    pub require_token: bool,
+    /// Reject unsigned batches. Batches for a node that has a secret must
+    /// be signed, whether or not this is set.
+    #[serde(default)]
+    pub require_signature: bool,
+    /// How far a signature timestamp may be from the collector's clock
+    #[serde(default = "default_max_clock_skew_secs")]
+    pub max_clock_skew_secs: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            require_token: false,
+            require_signature: false,
+            max_clock_skew_secs: default_max_clock_skew_secs(),
+        }
+    }
+}
+
+fn default_max_clock_skew_secs() -> u64 {
+    300
+}
+
+/// Registration of unknown nodes, decided with `ferrview-collector registration`
+#[derive(Debug, Default, Deserialize)]
+pub struct RegistrationConfig {
+    /// Accept registrations on `/api/v1/register`, and reject batches sent
+    /// without any credential until their node has been approved
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// A node the collector scrapes, for nodes that cannot reach it. The node
+/// serves its readings with `[exporter]` enabled in its config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScrapeTarget {
+    /// The node_id its readings must carry, and the node `up` is recorded for.
+    /// Samples from a Prometheus endpoint are stored under it.
+    pub node_id: String,
+    /// The node's exporter, e.g. `http://10.0.0.5:9101`, or with
+    /// `format = "prometheus"` the full URL of the metrics, e.g.
+    /// `http://10.0.0.7:9100/metrics`
+    pub url: String,
+    #[serde(default)]
+    pub format: ScrapeFormat,
+    #[serde(default = "default_scrape_interval_secs")]
+    pub interval_secs: u64,
+    /// How long a scrape may take before it counts as failed
+    #[serde(default = "default_scrape_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+/// What a scrape target serves
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScrapeFormat {
+    /// The readings of a ferrview node's exporter
+    #[default]
+    Ferrview,
+    /// The Prometheus text format or OpenMetrics, from any exporter or app
+    Prometheus,
+}
+
+fn default_scrape_interval_secs() -> u64 {
+    60
+}
+
+fn default_scrape_timeout_secs() -> u64 {
+    10
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_string(), e))?;
+        Self::from_str(&content)
+    }
+
+    fn from_str(content: &str) -> Result<Self, ConfigError> {
+        let config: Self = basic_toml::from_str(content)?;
+        config.validate_scrape_targets()?;
+        Ok(config)
+    }
+
+    fn validate_scrape_targets(&self) -> Result<(), ConfigError> {
+        let mut node_ids = std::collections::BTreeSet::new();
+        for target in &self.scrape {
+            let invalid = if target.node_id.is_empty() {
+                Some("needs a node_id".to_string())
+            } else if !target.url.starts_with("http://") {
+                Some("must be an http:// URL".to_string())
+            } else if target.interval_secs == 0 || target.timeout_secs == 0 {
+                Some("interval_secs and timeout_secs must be greater than zero".to_string())
+            } else if target.timeout_secs > target.interval_secs {
+                Some("timeout_secs must not exceed interval_secs".to_string())
+            } else if !node_ids.insert(&target.node_id) {
+                Some(format!("node {} is scraped twice", target.node_id))
+            } else {
+                None
+            };
+            if let Some(reason) = invalid {
+                return Err(ConfigError::Invalid(format!(
+                    "scrape target {}: {}",
+                    target.url, reason
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config() {
+        let config = Config::from_str("").unwrap();
+        assert!(config.tls.is_none());
+        assert!(!config.auth.require_token);
+        assert_eq!(config.auth.max_clock_skew_secs, 300);
+        assert!(!config.registration.enabled);
+        assert!(config.scrape.is_empty());
+    }
+
+    #[test]
+    fn test_scrape_config() {
+        let config = Config::from_str(
+            r#"
+            [[scrape]]
+            node_id = "web-01"
+            url = "http://10.0.0.5:9101"
+
+            [[scrape]]
+            node_id = "web-02"
+            url = "http://10.0.0.6:9101"
+            interval_secs = 15
+            timeout_secs = 5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.scrape.len(), 2);
+        assert_eq!(config.scrape[0].interval_secs, 60);
+        assert_eq!(config.scrape[0].timeout_secs, 10);
+        assert_eq!(config.scrape[0].format, ScrapeFormat::Ferrview);
+        assert_eq!(config.scrape[1].interval_secs, 15);
+
+        let config = Config::from_str(
+            "[[scrape]]\nnode_id = \"db-01\"\nurl = \"http://10.0.0.7:9187/metrics\"\nformat = \"prometheus\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.scrape[0].format, ScrapeFormat::Prometheus);
+        assert!(
+            Config::from_str(
+                "[[scrape]]\nnode_id = \"a\"\nurl = \"http://a\"\nformat = \"json\"\n"
+            )
+            .is_err()
+        );
+
+        let target = |extra: &str| {
+            format!(
+                "[[scrape]]\nnode_id = \"web-01\"\nurl = \"http://10.0.0.5:9101\"\n{}",
+                extra
+            )
+        };
+        assert!(Config::from_str(&target("interval_secs = 5\n")).is_err());
+        assert!(Config::from_str(&target("timeout_secs = 0\n")).is_err());
+        assert!(Config::from_str(&format!("{0}{0}", target(""))).is_err());
+        assert!(
+            Config::from_str("[[scrape]]\nnode_id = \"a\"\nurl = \"https://a:9101\"\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_tls_config() {
+        let config = Config::from_str(
+            r#"
+            [tls]
+            cert_file = "/etc/ferrview/collector.pem"
+            key_file = "/etc/ferrview/collector.key"
+            client_ca_file = "/etc/ferrview/ca.pem"
+
+            [tls.client_node_ids]
+            "web-01.example.com" = "2fd75b6b-cd46-4cae-ba93-30a66ee4fcd4"
+            "#,
+        )
+        .unwrap();
+
+        let tls = config.tls.unwrap();
+        assert_eq!(tls.key_file, "/etc/ferrview/collector.key");
+        assert_eq!(tls.client_ca_file.as_deref(), Some("/etc/ferrview/ca.pem"));
+        assert_eq!(
+            tls.client_node_ids["web-01.example.com"],
+            "2fd75b6b-cd46-4cae-ba93-30a66ee4fcd4"
+        );
+
+        assert!(Config::from_str("[tls]\ncert_file = \"a.pem\"\n").is_err());
+    }
+
+    #[test]
+    fn test_auth_config() {
+        let config = Config::from_str("[auth]\nrequire_token = true\n").unwrap();
+        assert!(config.auth.require_token);
+        assert!(!config.auth.require_signature);
+        assert_eq!(config.auth.max_clock_skew_secs, 300);
+
+        let config =
+            Config::from_str("[auth]\nrequire_signature = true\nmax_clock_skew_secs = 60\n")
+                .unwrap();
+        assert!(config.auth.require_signature);
+        assert_eq!(config.auth.max_clock_skew_secs, 60);
+
+        let config = Config::from_str("[registration]\nenabled = true\n").unwrap();
+        assert!(config.registration.enabled);
+    }
+}
@@ -0,0 +1,112 @@
+// src/http/auth.rs
+
+use hyper::StatusCode;
+use hyper::header::{AUTHORIZATION, HeaderMap};
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use crate::http::response::{self, BoxBody};
+use crate::store::auth::{AuthStore, TokenGrant};
+
+/// Checks the bearer token on ingest requests against the token store
+pub struct Authenticator {
+    tokens: Arc<AuthStore>,
+    require_token: bool,
+}
+
+impl Authenticator {
+    pub fn new(tokens: Arc<AuthStore>, require_token: bool) -> Self {
+        Self {
+            tokens,
+            require_token,
+        }
+    }
+
+    /// The grant for the request's token, `None` if it sent none and none
+    /// is required. A rejection is returned as the response to send.
+    pub async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<Option<TokenGrant>, (StatusCode, BoxBody)> {
+        let token = match bearer_token(headers) {
+            Ok(Some(token)) => token,
+            Ok(None) if self.require_token => {
+                warn!("Rejected request without a bearer token");
+                return Err(response::json_error(
+                    StatusCode::UNAUTHORIZED,
+                    "Bearer token required",
+                ));
+            }
+            Ok(None) => return Ok(None),
+            Err(()) => {
+                warn!("Rejected request with a malformed Authorization header");
+                return Err(response::json_error(
+                    StatusCode::UNAUTHORIZED,
+                    "Malformed Authorization header",
+                ));
+            }
+        };
+
+        match self.tokens.authorize_token(token).await {
+            Ok(Some(grant)) => Ok(Some(grant)),
+            Ok(None) => {
+                warn!("Rejected unknown or revoked bearer token");
+                Err(response::json_error(
+                    StatusCode::UNAUTHORIZED,
+                    "Invalid bearer token",
+                ))
+            }
+            Err(e) => {
+                error!("Failed to look up bearer token: {}", e);
+                Err(response::json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to check credentials",
+                ))
+            }
+        }
+    }
+}
+
+/// The token from an `Authorization: Bearer` header, `Err` if the header is
+/// present but not a bearer token
+fn bearer_token(headers: &HeaderMap) -> Result<Option<&str>, ()> {
+    let Some(value) = headers.get(AUTHORIZATION) else {
+        return Ok(None);
+    };
+
+    let value = value.to_str().map_err(|_| ())?;
+    let (scheme, token) = value.split_once(' ').ok_or(())?;
+    let token = token.trim();
+    if !scheme.eq_ignore_ascii_case("bearer") || token.is_empty() {
+        return Err(());
+    }
+    Ok(Some(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn headers(authorization: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static(authorization));
+        headers
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token(&HeaderMap::new()), Ok(None));
+        assert_eq!(
+            bearer_token(&headers("Bearer fvt_abc")),
+            Ok(Some("fvt_abc"))
+        );
+        assert_eq!(
+            bearer_token(&headers("bearer  fvt_abc")),
+            Ok(Some("fvt_abc"))
+        );
+        assert_eq!(bearer_token(&headers("Basic dXNlcjpwYXNz")), Err(()));
+        assert_eq!(bearer_token(&headers("Bearer ")), Err(()));
+        assert_eq!(bearer_token(&headers("fvt_abc")), Err(()));
+    }
+}
@@ -1,42 +1,257 @@
 // src/http/handlers/api.rs
 
-use http_body_util::BodyExt;
+use bytes::Bytes;
+use ferrview_common::codec::{
+    Compression, SUPPORTED_COMPRESSIONS, SUPPORTED_WIRE_FORMATS, WireFormat,
+};
+use ferrview_common::protocol::{
+    HEADER_REGISTRATION_CLAIM, RegistrationRequest, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
 use hyper::body::Incoming;
+use hyper::header::{CONTENT_ENCODING, CONTENT_TYPE};
 use hyper::{Request, StatusCode};
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
+use crate::http::registration::Registrations;
 use crate::http::response::{self, BoxBody};
-use crate::http::types::{HealthResponse, ProbeDataBatch, SuccessResponse};
+use crate::http::server::ServerState;
+use crate::http::signature::SignatureVerifier;
+use crate::http::tls::ClientIdentity;
+use crate::http::types::{
+    HealthResponse, IncomingBatch, MetricsCatalogResponse, NodeResponse, SuccessResponse,
+};
 use crate::http::validate::{max_request_size, validate_request_size};
-use crate::store::writer::WriterHandle;
+use crate::store::auth::TokenGrant;
+use crate::store::errors::StoreError;
+use crate::store::nodes::{NodeStore, Tags, matches_tags, validate_tags};
 
-pub async fn handle_probe(req: Request<Incoming>, writer: &WriterHandle) -> (StatusCode, BoxBody) {
-    let whole_body = match req.collect().await {
+/// Largest registration request body accepted
+const MAX_REGISTRATION_SIZE: usize = 4096;
+
+/// Accept a batch of probe data. With a client certificate, a bearer token
+/// or a signature, every point must belong to a node the credential may
+/// write for. Without any, the batch is refused while registration is on.
+pub async fn handle_probe(
+    req: Request<Incoming>,
+    state: &ServerState,
+    identity: Option<&ClientIdentity>,
+    grant: Option<&TokenGrant>,
+) -> (StatusCode, BoxBody) {
+    let signatures = &state.signatures;
+
+    let signature = match SignatureVerifier::signature(req.headers()) {
+        Ok(signature) => signature,
+        Err(rejection) => return rejection,
+    };
+
+    // Nodes predating content negotiation always send JSON
+    let format = match req.headers().get(CONTENT_TYPE) {
+        None => WireFormat::Json,
+        Some(value) => match value.to_str().ok().and_then(WireFormat::from_content_type) {
+            Some(format) => format,
+            None => {
+                error!("Unsupported content type: {:?}", value);
+                return response::json_error(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Unsupported content type",
+                );
+            }
+        },
+    };
+
+    let compression = match req.headers().get(CONTENT_ENCODING) {
+        None => Compression::None,
+        Some(value) => match value
+            .to_str()
+            .ok()
+            .and_then(Compression::from_content_encoding)
+        {
+            Some(compression) => compression,
+            None => {
+                error!("Unsupported content encoding: {:?}", value);
+                return response::json_error(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Unsupported content encoding",
+                );
+            }
+        },
+    };
+
+    // Reading stops at the limit, before anyone is authenticated
+    let whole_body = match Limited::new(req.into_body(), max_request_size())
+        .collect()
+        .await
+    {
         Ok(collected) => collected.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            error!("Request body over {} bytes", max_request_size());
+            return response::json_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &format!("Request too large (max: {})", max_request_size()),
+            );
+        }
         Err(e) => {
             error!("Failed to read request body: {}", e);
             return response::json_error(StatusCode::BAD_REQUEST, "Failed to read body");
         }
     };
 
-    if let Err(e) = validate_request_size(whole_body.len()) {
-        error!("Request size validation failed: {}", e);
-        return response::json_error(StatusCode::PAYLOAD_TOO_LARGE, &e.to_string());
+    // The signature covers the body as sent, so it is checked before
+    // anything is decompressed or decoded
+    if let Some(signature) = &signature
+        && let Err(rejection) = signatures.verify(signature, &whole_body).await
+    {
+        return rejection;
     }
 
-    let batch: ProbeDataBatch = match serde_json::from_slice(&whole_body) {
+    let whole_body = if compression == Compression::None {
+        whole_body
+    } else {
+        // Decompression stops just past the limit, so a bomb is caught by
+        // the second size check without being inflated in full
+        let decompressed = match compression.decompress(&whole_body, max_request_size()) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("Failed to decompress request body: {}", e);
+                return response::json_error(StatusCode::BAD_REQUEST, "Invalid compressed body");
+            }
+        };
+
+        if let Err(e) = validate_request_size(decompressed.len()) {
+            error!("Decompressed request size validation failed: {}", e);
+            return response::json_error(StatusCode::PAYLOAD_TOO_LARGE, &e.to_string());
+        }
+
+        debug!(
+            "Decompressed {} body: {} -> {} bytes",
+            compression.content_encoding().unwrap_or("identity"),
+            whole_body.len(),
+            decompressed.len()
+        );
+        Bytes::from(decompressed)
+    };
+
+    let batch: IncomingBatch = match format.decode(&whole_body) {
         Ok(b) => b,
         Err(e) => {
-            error!("Failed to parse JSON: {}", e);
-            return response::json_error(StatusCode::BAD_REQUEST, "Invalid JSON");
+            error!("Failed to parse {} body: {}", format.content_type(), e);
+            return response::json_error(StatusCode::BAD_REQUEST, "Invalid request body");
         }
     };
 
-    debug!("Received batch of {} probe data points", batch.data.len());
+    let mut metadata = None;
+    if let IncomingBatch::Envelope(envelope) = &batch {
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&envelope.schema_version) {
+            error!("Unsupported schema version: {}", envelope.schema_version);
+            return response::json_error(StatusCode::BAD_REQUEST, "Unsupported schema version");
+        }
+
+        if let Some(reason) = validate_tags(&envelope.tags) {
+            error!("Invalid tags from node {}: {}", envelope.node_id, reason);
+            return response::json_error(StatusCode::BAD_REQUEST, "Invalid tags");
+        }
+        // Tags are only taken along with data, which the checks below
+        // tie to a node the sender may write for
+        if !envelope.points.is_empty() {
+            metadata = Some((
+                envelope.node_id.clone(),
+                envelope.agent_version.clone(),
+                envelope.tags.clone(),
+            ));
+        }
+
+        debug!(
+            "Envelope from node {} (agent {}), sequence {}",
+            envelope.node_id, envelope.agent_version, envelope.sequence
+        );
+    }
+
+    let data = batch.into_points();
+
+    debug!("Received batch of {} probe data points", data.len());
 
-    if let Err(e) = writer.insert_batch(batch.data).await {
-        error!("Failed to queue write: {}", e);
-        return response::json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to queue write");
+    if let Some(identity) = identity
+        && let Some(point) = data.iter().find(|p| p.node_id != identity.node_id)
+    {
+        warn!(
+            "Client certificate {} may not report as node {}",
+            identity.subject, point.node_id
+        );
+        return response::json_error(
+            StatusCode::FORBIDDEN,
+            "Client certificate does not permit this node_id",
+        );
+    }
+
+    if let Some(grant) = grant
+        && let Some(point) = data.iter().find(|p| !grant.permits(&p.node_id))
+    {
+        warn!(
+            "Token {} may not write for node {}",
+            grant.token_id, point.node_id
+        );
+        return response::json_error(StatusCode::FORBIDDEN, "Token does not permit this node_id");
+    }
+
+    match &signature {
+        Some(signature) => {
+            if let Some(point) = data.iter().find(|p| p.node_id != signature.node_id) {
+                warn!(
+                    "Batch signed by node {} contains data for node {}",
+                    signature.node_id, point.node_id
+                );
+                return response::json_error(
+                    StatusCode::FORBIDDEN,
+                    "Signature does not permit this node_id",
+                );
+            }
+        }
+        None => {
+            if let Err(rejection) = signatures
+                .check_unsigned(data.iter().map(|p| p.node_id.as_str()))
+                .await
+            {
+                return rejection;
+            }
+        }
+    }
+
+    if identity.is_none()
+        && grant.is_none()
+        && signature.is_none()
+        && let Err(rejection) = state
+            .registrations
+            .check_unauthenticated(data.iter().map(|p| p.node_id.as_str()))
+            .await
+    {
+        return rejection;
+    }
+
+    match state.writer.try_insert_batch(data) {
+        Ok(()) => {}
+        Err(StoreError::WriterBusy) => {
+            // The node keeps the batch and comes back after Retry-After
+            warn!("Writer queue is full, turning a batch away");
+            return response::json_error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Collector is busy, retry later",
+            );
+        }
+        Err(e) => {
+            error!("Failed to queue write: {}", e);
+            return response::json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to queue write",
+            );
+        }
+    }
+
+    if let Some((node_id, agent_version, tags)) = metadata
+        && let Err(e) = state.nodes.update(&node_id, &agent_version, &tags).await
+    {
+        // The data itself is stored; stale tags are not worth a retry
+        error!("Failed to update metadata of node {}: {}", node_id, e);
     }
 
     response::json(
@@ -47,6 +262,93 @@ pub async fn handle_probe(req: Request<Incoming>, writer: &WriterHandle) -> (Sta
     )
 }
 
+/// Record a registration from a node without credentials
+pub async fn handle_register(
+    req: Request<Incoming>,
+    remote_addr: &str,
+    registrations: &Registrations,
+) -> (StatusCode, BoxBody) {
+    if !registrations.enabled() {
+        return handle_not_found().await;
+    }
+
+    // Anyone may register, so reading stops at the limit
+    let body = match Limited::new(req.into_body(), MAX_REGISTRATION_SIZE)
+        .collect()
+        .await
+    {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            return response::json_error(StatusCode::PAYLOAD_TOO_LARGE, "Registration too large");
+        }
+        Err(e) => {
+            error!("Failed to read request body: {}", e);
+            return response::json_error(StatusCode::BAD_REQUEST, "Failed to read body");
+        }
+    };
+
+    let request: RegistrationRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            error!("Failed to parse registration: {}", e);
+            return response::json_error(StatusCode::BAD_REQUEST, "Invalid request body");
+        }
+    };
+
+    match registrations.register(&request, remote_addr).await {
+        Ok(pending) => response::json(StatusCode::ACCEPTED, &pending),
+        Err(rejection) => rejection,
+    }
+}
+
+/// Report a registration's status to the node holding its claim
+pub async fn handle_registration_status(
+    req: &Request<Incoming>,
+    id: &str,
+    registrations: &Registrations,
+) -> (StatusCode, BoxBody) {
+    if !registrations.enabled() {
+        return handle_not_found().await;
+    }
+
+    let Some(claim) = req
+        .headers()
+        .get(HEADER_REGISTRATION_CLAIM)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return response::json_error(StatusCode::NOT_FOUND, "Unknown registration");
+    };
+
+    match registrations.status(id, claim).await {
+        Ok(status) => response::json(StatusCode::OK, &status),
+        Err(rejection) => rejection,
+    }
+}
+
+/// Nodes known from their envelopes, with their tags; only those having
+/// every tag in `filter`
+pub async fn handle_nodes(nodes: &NodeStore, filter: &Tags) -> (StatusCode, BoxBody) {
+    match nodes.list().await {
+        Ok(list) => {
+            let nodes: Vec<NodeResponse> = list
+                .into_iter()
+                .filter(|node| matches_tags(&node.tags, filter))
+                .map(|node| NodeResponse {
+                    node_id: node.node_id,
+                    agent_version: node.agent_version,
+                    tags: node.tags,
+                    updated_at: node.updated_at,
+                })
+                .collect();
+            response::json(StatusCode::OK, &nodes)
+        }
+        Err(e) => {
+            error!("Failed to list nodes: {}", e);
+            response::json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list nodes")
+        }
+    }
+}
+
 pub async fn handle_health() -> (StatusCode, BoxBody) {
     response::json(
         StatusCode::OK,
@@ -54,6 +356,25 @@ pub async fn handle_health() -> (StatusCode, BoxBody) {
             status: "healthy".to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
             max_request_size_bytes: max_request_size(),
+            protocol_versions: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+            content_types: SUPPORTED_WIRE_FORMATS
+                .iter()
+                .map(|f| f.content_type().to_string())
+                .collect(),
+            content_encodings: SUPPORTED_COMPRESSIONS
+                .iter()
+                .filter_map(|c| c.content_encoding())
+                .map(str::to_string)
+                .collect(),
+        },
+    )
+}
+
+pub async fn handle_metrics_catalog() -> (StatusCode, BoxBody) {
+    response::json(
+        StatusCode::OK,
+        &MetricsCatalogResponse {
+            metrics: ferrview_common::metrics::ALL,
         },
     )
 }
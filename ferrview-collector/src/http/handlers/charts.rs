@@ -1,16 +1,20 @@
 // src/http/handlers/charts.rs
 
+use ferrview_common::metrics::{self, MetricDef};
 use hyper::StatusCode;
+use std::collections::BTreeMap;
 use tracing::{debug, error};
 
 use crate::charts::{ChartData, SvgRenderer, TimeSeries, TimeSeriesChart};
 use crate::http::response::{self, BoxBody};
 use crate::http::ui::helpers;
+use crate::scrape::prometheus;
 use crate::store::date_range::DateRange;
 use crate::store::date_range_reader::DateRangeReader;
 
 pub async fn handle_cpu_chart(
     node_id: &str,
+    labels: &BTreeMap<String, String>,
     range: &DateRange,
     reader: &DateRangeReader,
 ) -> (StatusCode, BoxBody) {
@@ -20,7 +24,7 @@ pub async fn handle_cpu_chart(
     );
 
     let metrics = match reader
-        .query_node_metrics(node_id, "cpu_core_%_usage_percent", range)
+        .query_node_metrics(node_id, metrics::CPU_CORE_USAGE_PERCENT.name, labels, range)
         .await
     {
         Ok(m) => m,
@@ -34,7 +38,7 @@ pub async fn handle_cpu_chart(
         return response::svg_error("No CPU data available");
     }
 
-    let series_map = helpers::group_metrics_by_index(&metrics, "cpu_core_");
+    let series_map = helpers::group_metrics_by_label(&metrics, "core");
 
     if series_map.is_empty() {
         return response::svg_error("No CPU data found");
@@ -44,10 +48,11 @@ pub async fn handle_cpu_chart(
         "CPU Usage - Node {}",
         helpers::shorten_uuid(node_id)
     ))
-    .with_labels("Time", "Usage (%)");
+    .with_labels("Time", axis_label(&metrics::CPU_CORE_USAGE_PERCENT, None));
 
-    for (name, points) in series_map {
-        let mut series = TimeSeries::new(name).with_unit("%");
+    for (core, points) in series_map {
+        let mut series = TimeSeries::new(format!("Core {}", core))
+            .with_unit(metrics::CPU_CORE_USAGE_PERCENT.unit);
         for (timestamp, value) in points {
             series.add_point(timestamp, value);
         }
@@ -59,6 +64,7 @@ pub async fn handle_cpu_chart(
 
 pub async fn handle_memory_chart(
     node_id: &str,
+    labels: &BTreeMap<String, String>,
     range: &DateRange,
     reader: &DateRangeReader,
 ) -> (StatusCode, BoxBody) {
@@ -68,7 +74,7 @@ pub async fn handle_memory_chart(
     );
 
     let used_metrics = match reader
-        .query_node_metrics(node_id, "memory_used_bytes", range)
+        .query_node_metrics(node_id, metrics::MEMORY_USED_BYTES.name, labels, range)
         .await
     {
         Ok(m) => m,
@@ -79,7 +85,7 @@ pub async fn handle_memory_chart(
     };
 
     let total_metrics = match reader
-        .query_node_metrics(node_id, "memory_total_bytes", range)
+        .query_node_metrics(node_id, metrics::MEMORY_TOTAL_BYTES.name, labels, range)
         .await
     {
         Ok(m) => m,
@@ -97,16 +103,13 @@ pub async fn handle_memory_chart(
         "Memory Usage - Node {}",
         helpers::shorten_uuid(node_id)
     ))
-    .with_labels("Time", "Memory (GB)");
+    .with_labels("Time", axis_label(&metrics::MEMORY_USED_BYTES, Some("GB")));
 
     // Used memory series
     let mut used_series = TimeSeries::new("Used Memory").with_unit("GB");
     for metric in &used_metrics {
-        if let (Ok(timestamp), Ok(value)) = (
-            helpers::parse_timestamp(&metric.timestamp),
-            metric.probe_value.parse::<f64>(),
-        ) {
-            used_series.add_point(timestamp, value / 1_073_741_824.0);
+        if let Some(value) = metric.probe_value.as_f64() {
+            used_series.add_point(metric.timestamp, value / 1_073_741_824.0);
         }
     }
     chart_data.add_series(used_series);
@@ -115,11 +118,8 @@ pub async fn handle_memory_chart(
     if !total_metrics.is_empty() {
         let mut total_series = TimeSeries::new("Total Memory").with_unit("GB");
         for metric in &total_metrics {
-            if let (Ok(timestamp), Ok(value)) = (
-                helpers::parse_timestamp(&metric.timestamp),
-                metric.probe_value.parse::<f64>(),
-            ) {
-                total_series.add_point(timestamp, value / 1_073_741_824.0);
+            if let Some(value) = metric.probe_value.as_f64() {
+                total_series.add_point(metric.timestamp, value / 1_073_741_824.0);
             }
         }
         chart_data.add_series(total_series);
@@ -130,6 +130,7 @@ pub async fn handle_memory_chart(
 
 pub async fn handle_temperature_chart(
     node_id: &str,
+    labels: &BTreeMap<String, String>,
     range: &DateRange,
     reader: &DateRangeReader,
 ) -> (StatusCode, BoxBody) {
@@ -139,7 +140,7 @@ pub async fn handle_temperature_chart(
     );
 
     let metrics = match reader
-        .query_node_metrics(node_id, "temperature_sensor_%_celsius", range)
+        .query_node_metrics(node_id, metrics::TEMPERATURE_CELSIUS.name, labels, range)
         .await
     {
         Ok(m) => m,
@@ -149,19 +150,11 @@ pub async fn handle_temperature_chart(
         }
     };
 
-    // Filter to only include actual temperature readings, exclude max and critical thresholds
-    let filtered_metrics: Vec<_> = metrics
-        .into_iter()
-        .filter(|m| {
-            !m.probe_name.contains("_max_celsius") && !m.probe_name.contains("_critical_celsius")
-        })
-        .collect();
-
-    if filtered_metrics.is_empty() {
+    if metrics.is_empty() {
         return response::svg_error("No temperature data available");
     }
 
-    let series_map = helpers::group_metrics_by_index(&filtered_metrics, "temperature_sensor_");
+    let series_map = helpers::group_metrics_by_label(&metrics, "sensor");
 
     if series_map.is_empty() {
         return response::svg_error("No temperature data found");
@@ -171,10 +164,10 @@ pub async fn handle_temperature_chart(
         "Temperature - Node {}",
         helpers::shorten_uuid(node_id)
     ))
-    .with_labels("Time", "Temperature (°C)");
+    .with_labels("Time", axis_label(&metrics::TEMPERATURE_CELSIUS, None));
 
     for (name, points) in series_map {
-        let mut series = TimeSeries::new(name).with_unit("°C");
+        let mut series = TimeSeries::new(name).with_unit(metrics::TEMPERATURE_CELSIUS.unit);
         for (timestamp, value) in points {
             series.add_point(timestamp, value);
         }
@@ -186,6 +179,7 @@ pub async fn handle_temperature_chart(
 
 pub async fn handle_network_chart(
     node_id: &str,
+    labels: &BTreeMap<String, String>,
     range: &DateRange,
     reader: &DateRangeReader,
 ) -> (StatusCode, BoxBody) {
@@ -196,7 +190,12 @@ pub async fn handle_network_chart(
 
     // Query received bytes
     let rx_metrics = match reader
-        .query_node_metrics(node_id, "network_interface_%_total_received_bytes", range)
+        .query_node_metrics(
+            node_id,
+            metrics::NETWORK_TOTAL_RECEIVED_BYTES.name,
+            labels,
+            range,
+        )
         .await
     {
         Ok(m) => m,
@@ -208,7 +207,12 @@ pub async fn handle_network_chart(
 
     // Query transmitted bytes
     let tx_metrics = match reader
-        .query_node_metrics(node_id, "network_interface_%_total_transmitted_bytes", range)
+        .query_node_metrics(
+            node_id,
+            metrics::NETWORK_TOTAL_TRANSMITTED_BYTES.name,
+            labels,
+            range,
+        )
         .await
     {
         Ok(m) => m,
@@ -226,15 +230,18 @@ pub async fn handle_network_chart(
         "Network Traffic - Node {}",
         helpers::shorten_uuid(node_id)
     ))
-    .with_labels("Time", "Traffic (GB)");
+    .with_labels(
+        "Time",
+        axis_label(&metrics::NETWORK_TOTAL_RECEIVED_BYTES, Some("GB")),
+    );
 
-    // Group by interface index and create series
-    let rx_map = helpers::group_metrics_by_index(&rx_metrics, "network_interface_");
-    let tx_map = helpers::group_metrics_by_index(&tx_metrics, "network_interface_");
+    // Group by interface name and create series
+    let rx_map = helpers::group_metrics_by_label(&rx_metrics, "iface");
+    let tx_map = helpers::group_metrics_by_label(&tx_metrics, "iface");
 
     // Add RX series (convert bytes to GB)
-    for (name, points) in rx_map {
-        let series_name = format!("{} RX", name.replace("Core", "eth"));
+    for (iface, points) in rx_map {
+        let series_name = format!("{} RX", iface);
         let mut series = TimeSeries::new(series_name).with_unit("GB");
         for (timestamp, value) in points {
             series.add_point(timestamp, value / 1_073_741_824.0);
@@ -243,8 +250,8 @@ pub async fn handle_network_chart(
     }
 
     // Add TX series (convert bytes to GB)
-    for (name, points) in tx_map {
-        let series_name = format!("{} TX", name.replace("Core", "eth"));
+    for (iface, points) in tx_map {
+        let series_name = format!("{} TX", iface);
         let mut series = TimeSeries::new(series_name).with_unit("GB");
         for (timestamp, value) in points {
             series.add_point(timestamp, value / 1_073_741_824.0);
@@ -257,6 +264,7 @@ pub async fn handle_network_chart(
 
 pub async fn handle_disk_chart(
     node_id: &str,
+    labels: &BTreeMap<String, String>,
     range: &DateRange,
     reader: &DateRangeReader,
 ) -> (StatusCode, BoxBody) {
@@ -266,7 +274,7 @@ pub async fn handle_disk_chart(
     );
 
     let metrics = match reader
-        .query_node_metrics(node_id, "disk_%_usage_percent", range)
+        .query_node_metrics(node_id, metrics::DISK_USAGE_PERCENT.name, labels, range)
         .await
     {
         Ok(m) => m,
@@ -280,7 +288,7 @@ pub async fn handle_disk_chart(
         return response::svg_error("No disk data available");
     }
 
-    let series_map = helpers::group_metrics_by_index(&metrics, "disk_");
+    let series_map = helpers::group_metrics_by_label(&metrics, "mount");
 
     if series_map.is_empty() {
         return response::svg_error("No disk data found");
@@ -290,11 +298,10 @@ pub async fn handle_disk_chart(
         "Disk Usage - Node {}",
         helpers::shorten_uuid(node_id)
     ))
-    .with_labels("Time", "Usage (%)");
+    .with_labels("Time", axis_label(&metrics::DISK_USAGE_PERCENT, None));
 
-    for (name, points) in series_map {
-        let series_name = name.replace("Core", "Disk");
-        let mut series = TimeSeries::new(series_name).with_unit("%");
+    for (mount, points) in series_map {
+        let mut series = TimeSeries::new(mount).with_unit(metrics::DISK_USAGE_PERCENT.unit);
         for (timestamp, value) in points {
             series.add_point(timestamp, value);
         }
@@ -306,6 +313,7 @@ pub async fn handle_disk_chart(
 
 pub async fn handle_forks_chart(
     node_id: &str,
+    labels: &BTreeMap<String, String>,
     range: &DateRange,
     reader: &DateRangeReader,
 ) -> (StatusCode, BoxBody) {
@@ -315,7 +323,7 @@ pub async fn handle_forks_chart(
     );
 
     let metrics = match reader
-        .query_node_metrics(node_id, "forks_total", range)
+        .query_node_metrics(node_id, metrics::FORKS_TOTAL.name, labels, range)
         .await
     {
         Ok(m) => m,
@@ -333,15 +341,12 @@ pub async fn handle_forks_chart(
         "Process Forks - Node {}",
         helpers::shorten_uuid(node_id)
     ))
-    .with_labels("Time", "Total Forks");
+    .with_labels("Time", axis_label(&metrics::FORKS_TOTAL, None));
 
-    let mut series = TimeSeries::new("Forks (cumulative)").with_unit("");
+    let mut series = TimeSeries::new("Forks (cumulative)").with_unit(metrics::FORKS_TOTAL.unit);
     for metric in &metrics {
-        if let (Ok(timestamp), Ok(value)) = (
-            helpers::parse_timestamp(&metric.timestamp),
-            metric.probe_value.parse::<f64>(),
-        ) {
-            series.add_point(timestamp, value);
+        if let Some(value) = metric.probe_value.as_f64() {
+            series.add_point(metric.timestamp, value);
         }
     }
     chart_data.add_series(series);
@@ -349,6 +354,113 @@ pub async fn handle_forks_chart(
     render_chart(&chart_data)
 }
 
+/// Series drawn on a Prometheus chart; a metric with more label sets than
+/// this, such as a histogram, is narrowed down with `label.<key>=<value>`
+const MAX_PROMETHEUS_SERIES: usize = 20;
+
+pub async fn handle_prometheus_chart(
+    node_id: &str,
+    metric: &str,
+    labels: &BTreeMap<String, String>,
+    range: &DateRange,
+    reader: &DateRangeReader,
+) -> (StatusCode, BoxBody) {
+    debug!(
+        "Generating Prometheus chart of {} for node {} ({} to {})",
+        metric, node_id, range.start_date, range.end_date
+    );
+
+    if metric.is_empty() {
+        return response::svg_error("No metric given");
+    }
+
+    // LIKE takes the underscores in metric names for any character, so
+    // what else matches is dropped below
+    let metrics = match reader
+        .query_node_metrics(node_id, metric, labels, range)
+        .await
+    {
+        Ok(m) => m,
+        Err(e) => {
+            error!("Failed to query Prometheus metric {}: {}", metric, e);
+            return response::svg_error("Query failed");
+        }
+    };
+
+    // One series per label set
+    let mut series_map: BTreeMap<String, Vec<(i64, f64)>> = BTreeMap::new();
+    for point in &metrics {
+        if point.probe_type != prometheus::PROBE_TYPE || point.probe_name != metric {
+            continue;
+        }
+        if let Some(value) = point.probe_value.as_f64() {
+            series_map
+                .entry(series_name(metric, &point.labels))
+                .or_default()
+                .push((point.timestamp, value));
+        }
+    }
+
+    if series_map.is_empty() {
+        return response::svg_error("No data available for this metric");
+    }
+    if series_map.len() > MAX_PROMETHEUS_SERIES {
+        debug!(
+            "Drawing {} of the {} series of {}",
+            MAX_PROMETHEUS_SERIES,
+            series_map.len(),
+            metric
+        );
+    }
+
+    let mut chart_data = ChartData::new(prometheus_title(metric, node_id, series_map.len()))
+        .with_labels("Time", metric);
+
+    for (name, points) in series_map.into_iter().take(MAX_PROMETHEUS_SERIES) {
+        let mut series = TimeSeries::new(name);
+        for (timestamp, value) in points {
+            series.add_point(timestamp, value);
+        }
+        chart_data.add_series(series);
+    }
+
+    render_chart(&chart_data)
+}
+
+/// Title of a Prometheus chart, saying so when only some of its `series`
+/// are drawn
+fn prometheus_title(metric: &str, node_id: &str, series: usize) -> String {
+    let title = format!("{} - Node {}", metric, helpers::shorten_uuid(node_id));
+    if series > MAX_PROMETHEUS_SERIES {
+        format!(
+            "{} ({} of {} series, narrow down with label.<key>=)",
+            title, MAX_PROMETHEUS_SERIES, series
+        )
+    } else {
+        title
+    }
+}
+
+/// Legend entry of a series, e.g. `code=200, method=post`
+fn series_name(metric: &str, labels: &BTreeMap<String, String>) -> String {
+    if labels.is_empty() {
+        return metric.to_string();
+    }
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Y axis label for a metric, e.g. "Usage (%)", optionally in a scaled unit
+fn axis_label(metric: &MetricDef, scaled_unit: Option<&str>) -> String {
+    match scaled_unit.unwrap_or(metric.unit) {
+        "" => metric.title.to_string(),
+        unit => format!("{} ({})", metric.title, unit),
+    }
+}
+
 fn render_chart(chart_data: &ChartData) -> (StatusCode, BoxBody) {
     let config = TimeSeriesChart::new(1200, 500);
     let renderer = SvgRenderer::new(config);
@@ -361,3 +473,45 @@ fn render_chart(chart_data: &ChartData) -> (StatusCode, BoxBody) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axis_label() {
+        assert_eq!(
+            axis_label(&metrics::CPU_CORE_USAGE_PERCENT, None),
+            "Usage (%)"
+        );
+        assert_eq!(
+            axis_label(&metrics::MEMORY_USED_BYTES, Some("GB")),
+            "Memory (GB)"
+        );
+        assert_eq!(axis_label(&metrics::FORKS_TOTAL, None), "Total Forks");
+    }
+
+    #[test]
+    fn test_series_name() {
+        assert_eq!(series_name("up", &BTreeMap::new()), "up");
+        let labels = BTreeMap::from([
+            ("method".to_string(), "post".to_string()),
+            ("code".to_string(), "200".to_string()),
+        ]);
+        assert_eq!(
+            series_name("http_requests_total", &labels),
+            "code=200, method=post"
+        );
+    }
+
+    #[test]
+    fn test_prometheus_title() {
+        let node_id = "123e4567-e89b-12d3-a456-426614174000";
+        let title = prometheus_title("up", node_id, MAX_PROMETHEUS_SERIES);
+        assert!(!title.contains("series"), "{}", title);
+        assert!(
+            prometheus_title("http_request_duration_seconds_bucket", node_id, 33)
+                .ends_with("(20 of 33 series, narrow down with label.<key>=)")
+        );
+    }
+}
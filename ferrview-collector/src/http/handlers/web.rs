@@ -1,18 +1,28 @@
 // src/http/handlers/web.rs
 
+use ferrview_common::metrics::names;
 use hyper::StatusCode;
 use tracing::{debug, error};
 
+use crate::http::registration::Registrations;
 use crate::http::response::{self, BoxBody};
 use crate::http::ui::{
     helpers,
-    models::{NodeDetails, NodeSummary},
+    models::{NodeDetails, NodeSummary, RegistrationRow},
     views,
 };
+use crate::scrape::prometheus;
 use crate::store::db::Database;
-use crate::store::queries::{MetricDataPoint, query_all_node_ids, query_latest_node_metrics};
+use crate::store::nodes::{NodeStore, Tags, matches_tags};
+use crate::store::queries::{
+    MetricDataPoint, query_all_node_ids, query_latest_node_metrics, query_node_metric_names,
+};
 
-pub async fn handle_home(data_dir: &str) -> (StatusCode, BoxBody) {
+pub async fn handle_home(
+    data_dir: &str,
+    nodes: &NodeStore,
+    filter: &Tags,
+) -> (StatusCode, BoxBody) {
     debug!("Handling home page request");
 
     let date = helpers::current_date();
@@ -32,15 +42,22 @@ pub async fn handle_home(data_dir: &str) -> (StatusCode, BoxBody) {
         }
     };
 
-    let mut nodes = Vec::with_capacity(node_ids.len());
+    let mut all_tags = nodes.all_tags();
+    let mut summaries = Vec::with_capacity(node_ids.len());
     for node_id in node_ids {
+        let tags = all_tags.remove(&node_id).unwrap_or_default();
+        if !matches_tags(&tags, filter) {
+            continue;
+        }
         let metrics = query_latest_node_metrics(db.conn(), &node_id)
             .await
             .unwrap_or_default();
-        nodes.push(build_node_summary(&node_id, &metrics));
+        let mut summary = build_node_summary(&node_id, &metrics);
+        summary.tags = tags;
+        summaries.push(summary);
     }
 
-    let html = views::home::render(&nodes);
+    let html = views::home::render(&summaries, filter);
     response::html(&html)
 }
 
@@ -68,11 +85,47 @@ pub async fn handle_node_dashboard(node_id: &str, data_dir: &str) -> (StatusCode
         return render_error("Not Found", &format!("No data found for node {}", node_id));
     }
 
-    let node_details = build_node_details(node_id, &metrics);
+    let mut node_details = build_node_details(node_id, &metrics);
+    node_details.prometheus_metrics =
+        match query_node_metric_names(db.conn(), node_id, prometheus::PROBE_TYPE).await {
+            Ok(names) => names,
+            Err(e) => {
+                error!("Failed to query Prometheus metrics of {}: {}", node_id, e);
+                Vec::new()
+            }
+        };
     let html = views::node::render(&node_details);
     response::html(&html)
 }
 
+pub async fn handle_registrations(registrations: &Registrations) -> (StatusCode, BoxBody) {
+    debug!("Handling registrations page request");
+
+    let rows: Vec<RegistrationRow> = match registrations.list().await {
+        Ok(list) => list
+            .into_iter()
+            .map(|r| RegistrationRow {
+                id: r.id,
+                node_id: r.node_id,
+                hostname: r.hostname,
+                agent_version: r.agent_version,
+                remote_addr: r.remote_addr,
+                status: r.status.as_str(),
+                requested_at: Some(r.requested_at),
+                decided_at: r.decided_at,
+                has_credentials: r.has_credentials,
+            })
+            .collect(),
+        Err(e) => {
+            error!("Failed to list registrations: {}", e);
+            return render_error("Query Error", "Failed to load registrations");
+        }
+    };
+
+    let html = views::registrations::render(&rows, registrations.enabled());
+    response::html(&html)
+}
+
 fn render_error(title: &str, message: &str) -> (StatusCode, BoxBody) {
     let html = views::error::render(title, message);
     response::html_error(StatusCode::INTERNAL_SERVER_ERROR, &html)
@@ -83,28 +136,28 @@ fn build_node_summary(node_id: &str, metrics: &[MetricDataPoint]) -> NodeSummary
     let mut max_temp: Option<f64> = None;
 
     for metric in metrics {
-        if summary.last_seen.is_none() && !metric.timestamp.is_empty() {
-            summary.last_seen = Some(metric.timestamp.clone());
+        if summary.last_seen.is_none() {
+            summary.last_seen = Some(metric.timestamp);
         }
         match metric.probe_name.as_str() {
-            "system_hostname" => summary.hostname = Some(metric.probe_value.clone()),
-            "system_cpu_arch" => summary.cpu_arch = Some(metric.probe_value.clone()),
-            "cpu_core_count" => summary.cpu_cores = Some(metric.probe_value.clone()),
-            "memory_total_bytes" => {
-                if let Ok(bytes) = metric.probe_value.parse::<f64>() {
+            names::SYSTEM_HOSTNAME => summary.hostname = Some(metric.probe_value.to_string()),
+            names::SYSTEM_CPU_ARCH => summary.cpu_arch = Some(metric.probe_value.to_string()),
+            names::CPU_CORE_COUNT => summary.cpu_cores = Some(metric.probe_value.to_string()),
+            names::MEMORY_TOTAL_BYTES => {
+                if let Some(bytes) = metric.probe_value.as_f64() {
                     summary.memory_total_gb = Some(bytes / 1_073_741_824.0);
                 }
             }
-            "temperature_sensor_count" => summary.temp_sensors = Some(metric.probe_value.clone()),
-            _ => {
-                // Extract temperature sensor readings to find max
-                if metric.probe_name.starts_with("temperature_sensor_")
-                    && metric.probe_name.ends_with("_celsius")
-                    && let Ok(temp) = metric.probe_value.parse::<f64>()
-                {
+            names::TEMPERATURE_SENSOR_COUNT => {
+                summary.temp_sensors = Some(metric.probe_value.to_string())
+            }
+            names::TEMPERATURE_CELSIUS => {
+                // Track the hottest sensor reading
+                if let Some(temp) = metric.probe_value.as_f64() {
                     max_temp = Some(max_temp.map_or(temp, |current| current.max(temp)));
                 }
             }
+            _ => {}
         }
     }
 
@@ -117,17 +170,19 @@ fn build_node_details(node_id: &str, metrics: &[MetricDataPoint]) -> NodeDetails
     let mut details = NodeDetails::new(node_id.to_string(), current_date);
 
     for metric in metrics {
-        if details.last_seen.is_none() && !metric.timestamp.is_empty() {
-            details.last_seen = Some(metric.timestamp.clone());
+        if details.last_seen.is_none() {
+            details.last_seen = Some(metric.timestamp);
         }
         match metric.probe_name.as_str() {
-            "system_hostname" => details.hostname = Some(metric.probe_value.clone()),
-            "system_os_name" => details.os_name = Some(metric.probe_value.clone()),
-            "system_kernel_version" => details.kernel_version = Some(metric.probe_value.clone()),
-            "system_cpu_arch" => details.cpu_arch = Some(metric.probe_value.clone()),
-            "cpu_core_count" => details.cpu_cores = Some(metric.probe_value.clone()),
-            "memory_total_bytes" => {
-                if let Ok(bytes) = metric.probe_value.parse::<f64>() {
+            names::SYSTEM_HOSTNAME => details.hostname = Some(metric.probe_value.to_string()),
+            names::SYSTEM_OS_NAME => details.os_name = Some(metric.probe_value.to_string()),
+            names::SYSTEM_KERNEL_VERSION => {
+                details.kernel_version = Some(metric.probe_value.to_string())
+            }
+            names::SYSTEM_CPU_ARCH => details.cpu_arch = Some(metric.probe_value.to_string()),
+            names::CPU_CORE_COUNT => details.cpu_cores = Some(metric.probe_value.to_string()),
+            names::MEMORY_TOTAL_BYTES => {
+                if let Some(bytes) = metric.probe_value.as_f64() {
                     details.memory_total_gb = Some(bytes / 1_073_741_824.0);
                 }
             }
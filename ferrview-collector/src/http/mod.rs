@@ -1,8 +1,12 @@
 // src/http/mod.rs
 
+pub mod auth;
 pub mod handlers;
+pub mod registration;
 pub mod response;
 pub mod server;
+pub mod signature;
+pub mod tls;
 pub mod types;
 pub mod ui;
 pub mod validate;
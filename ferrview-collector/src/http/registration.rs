@@ -0,0 +1,213 @@
+// src/http/registration.rs
+
+use ferrview_common::protocol::{RegistrationRequest, RegistrationResponse, RegistrationStatus};
+use hyper::StatusCode;
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use crate::http::response::{self, BoxBody};
+use crate::store::auth::{AuthStore, RegistrationInfo};
+use crate::store::errors::StoreError;
+
+/// Longest node_id or hostname a registration may carry
+const MAX_FIELD_LEN: usize = 256;
+
+type Rejection = (StatusCode, BoxBody);
+
+/// Lets unknown nodes ask for credentials, and keeps their batches out
+/// until an admin has approved them
+pub struct Registrations {
+    store: Arc<AuthStore>,
+    enabled: bool,
+}
+
+impl Registrations {
+    pub fn new(store: Arc<AuthStore>, enabled: bool) -> Self {
+        Self { store, enabled }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Record a registration, returning the pending response with its claim
+    pub async fn register(
+        &self,
+        request: &RegistrationRequest,
+        remote_addr: &str,
+    ) -> Result<RegistrationResponse, Rejection> {
+        if !valid_field(&request.node_id)
+            || !request.hostname.as_deref().is_none_or(valid_field)
+            || !valid_field(&request.agent_version)
+        {
+            return Err(response::json_error(
+                StatusCode::BAD_REQUEST,
+                "Invalid registration request",
+            ));
+        }
+
+        match self.store.create_registration(request, remote_addr).await {
+            Ok(Some((registration_id, claim))) => Ok(RegistrationResponse {
+                registration_id,
+                status: RegistrationStatus::Pending,
+                claim: Some(claim),
+                token: None,
+                secret: None,
+            }),
+            Ok(None) => {
+                warn!(
+                    "Refused registration of node {}, too many are pending",
+                    request.node_id
+                );
+                Err(response::json_error(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Too many pending registrations",
+                ))
+            }
+            Err(e) => Err(store_failure(e)),
+        }
+    }
+
+    /// Current state of a registration for the node holding its claim
+    pub async fn status(&self, id: &str, claim: &str) -> Result<RegistrationResponse, Rejection> {
+        match self.store.poll_registration(id, claim).await {
+            Ok(Some(status)) => Ok(status),
+            Ok(None) => Err(response::json_error(
+                StatusCode::NOT_FOUND,
+                "Unknown registration",
+            )),
+            Err(e) => Err(store_failure(e)),
+        }
+    }
+
+    /// All registrations, newest first
+    pub async fn list(&self) -> Result<Vec<RegistrationInfo>, StoreError> {
+        self.store.list_registrations(None).await
+    }
+
+    /// Check a batch that came with no credential at all. Once registration
+    /// is enabled such batches are refused, saying how far the first node
+    /// in it got.
+    pub async fn check_unauthenticated<'a>(
+        &self,
+        node_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), Rejection> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let Some(node_id) = node_ids.into_iter().next() else {
+            return Ok(());
+        };
+
+        let message = match self.store.registration_status(node_id).await {
+            Ok(Some(RegistrationStatus::Pending)) => "Registration pending approval",
+            Ok(Some(RegistrationStatus::Rejected)) => "Registration rejected",
+            Ok(Some(RegistrationStatus::Approved)) => "Registered nodes must authenticate",
+            Ok(None) => "Unknown node, register first",
+            Err(e) => return Err(store_failure(e)),
+        };
+        warn!(
+            "Rejected unauthenticated batch for node {}: {}",
+            node_id, message
+        );
+        Err(response::json_error(StatusCode::FORBIDDEN, message))
+    }
+}
+
+fn valid_field(value: &str) -> bool {
+    !value.is_empty() && value.len() <= MAX_FIELD_LEN && !value.chars().any(char::is_control)
+}
+
+fn store_failure(e: StoreError) -> Rejection {
+    error!("Failed to access registrations: {}", e);
+    response::json_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to access registrations",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(node_id: &str) -> RegistrationRequest {
+        RegistrationRequest {
+            node_id: node_id.to_string(),
+            hostname: None,
+            agent_version: "0.6.0".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_batches_wait_for_approval() {
+        let store = Arc::new(AuthStore::in_memory().await);
+        let registrations = Registrations::new(Arc::clone(&store), true);
+
+        let (status, _) = registrations
+            .check_unauthenticated(["node-1"])
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let pending = registrations
+            .register(&request("node-1"), "127.0.0.1:4711")
+            .await
+            .unwrap();
+        assert_eq!(pending.status, RegistrationStatus::Pending);
+        let claim = pending.claim.unwrap();
+
+        assert!(
+            registrations
+                .status(&pending.registration_id, "wrong")
+                .await
+                .is_err()
+        );
+        assert!(
+            registrations
+                .check_unauthenticated(["node-1"])
+                .await
+                .is_err()
+        );
+
+        store
+            .approve_registration(&pending.registration_id, false)
+            .await
+            .unwrap();
+        let approved = registrations
+            .status(&pending.registration_id, &claim)
+            .await
+            .unwrap();
+        assert!(approved.token.is_some());
+
+        // Approval hands out credentials; it does not open the door to
+        // batches that leave them off
+        assert!(
+            registrations
+                .check_unauthenticated(["node-1"])
+                .await
+                .is_err()
+        );
+
+        let open = Registrations::new(store, false);
+        assert!(open.check_unauthenticated(["node-2"]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_registration() {
+        let registrations = Registrations::new(Arc::new(AuthStore::in_memory().await), true);
+        assert!(registrations.register(&request(""), "a").await.is_err());
+        assert!(
+            registrations
+                .register(&request("node\n1"), "a")
+                .await
+                .is_err()
+        );
+        assert!(
+            registrations
+                .register(&request(&"x".repeat(MAX_FIELD_LEN + 1)), "a")
+                .await
+                .is_err()
+        );
+    }
+}
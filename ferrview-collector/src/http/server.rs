@@ -1,59 +1,72 @@
 // src/http/server.rs
 
 use hyper::body::Incoming;
+use hyper::header::{RETRY_AFTER, WWW_AUTHENTICATE};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper::{Method, Request, Response};
+use hyper::{Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
+use crate::http::auth::Authenticator;
 use crate::http::handlers::{api, charts, web};
+use crate::http::registration::Registrations;
 use crate::http::response::BoxBody;
+use crate::http::signature::SignatureVerifier;
+use crate::http::tls::{ClientIdentity, TlsReloader};
 use crate::store::date_range::DateRange;
 use crate::store::date_range_reader::DateRangeReader;
 use crate::store::errors::StoreError;
+use crate::store::nodes::NodeStore;
 use crate::store::writer::WriterHandle;
 
+/// Seconds an overloaded collector asks clients to wait before trying again
+const RETRY_AFTER_SECS: u64 = 5;
+
 pub struct ServerState {
     pub writer: WriterHandle,
     pub date_range_reader: DateRangeReader,
     pub data_dir: String,
+    pub auth: Authenticator,
+    pub signatures: SignatureVerifier,
+    pub registrations: Registrations,
+    pub nodes: Arc<NodeStore>,
 }
 
 pub struct HttpServer {
     addr: SocketAddr,
     state: Arc<ServerState>,
+    tls: Option<Arc<TlsReloader>>,
 }
 
 impl HttpServer {
     pub fn new(
         host: &str,
         port: &str,
-        writer: WriterHandle,
-        date_range_reader: DateRangeReader,
-        data_dir: String,
+        state: ServerState,
+        tls: Option<Arc<TlsReloader>>,
     ) -> Result<Self, StoreError> {
         let addr: SocketAddr = format!("{}:{}", host, port)
             .parse()
             .map_err(|e| StoreError::InvalidQuery(format!("Invalid address: {}", e)))?;
+        let state = Arc::new(state);
 
-        let state = Arc::new(ServerState {
-            writer,
-            date_range_reader,
-            data_dir,
-        });
-
-        Ok(Self { addr, state })
+        Ok(Self { addr, state, tls })
     }
 
     pub async fn run(self) -> Result<(), StoreError> {
         let listener = TcpListener::bind(self.addr).await.map_err(StoreError::Io)?;
 
-        info!("HTTP server listening on {}", self.addr);
+        if self.tls.is_some() {
+            info!("HTTPS server listening on {}", self.addr);
+        } else {
+            info!("HTTP server listening on {}", self.addr);
+        }
 
         loop {
             let (stream, remote_addr) = match listener.accept().await {
@@ -64,27 +77,66 @@ impl HttpServer {
                 }
             };
 
-            let io = TokioIo::new(stream);
             let state = Arc::clone(&self.state);
 
+            let Some(tls) = &self.tls else {
+                tokio::spawn(serve_connection(stream, state, remote_addr, None));
+                continue;
+            };
+
+            // Settings are picked per connection, so a reload applies to
+            // new connections only
+            let tls = tls.current();
             tokio::spawn(async move {
-                let service = service_fn(move |req| {
-                    let state = Arc::clone(&state);
-                    route(req, state, remote_addr)
-                });
+                let stream = match tls.acceptor().accept(stream).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("TLS handshake with {} failed: {}", remote_addr, e);
+                        return;
+                    }
+                };
 
-                if let Err(e) = http1::Builder::new().serve_connection(io, service).await {
-                    error!("Error serving connection from {}: {}", remote_addr, e);
+                let identity = tls.client_identity(stream.get_ref().1.peer_certificates());
+                if let Some(identity) = &identity {
+                    debug!(
+                        "Client {} presented {}, reporting as node {}",
+                        remote_addr, identity.subject, identity.node_id
+                    );
                 }
+
+                serve_connection(stream, state, remote_addr, identity).await;
             });
         }
     }
 }
 
+async fn serve_connection<S>(
+    stream: S,
+    state: Arc<ServerState>,
+    remote_addr: SocketAddr,
+    identity: Option<ClientIdentity>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let identity = identity.map(Arc::new);
+    let service = service_fn(move |req| {
+        let state = Arc::clone(&state);
+        route(req, state, remote_addr, identity.clone())
+    });
+
+    if let Err(e) = http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .await
+    {
+        error!("Error serving connection from {}: {}", remote_addr, e);
+    }
+}
+
 async fn route(
     req: Request<Incoming>,
     state: Arc<ServerState>,
     remote_addr: SocketAddr,
+    identity: Option<Arc<ClientIdentity>>,
 ) -> Result<Response<BoxBody>, hyper::Error> {
     debug!(
         "Request from {}: {} {}",
@@ -98,7 +150,31 @@ async fn route(
     let (status, body, content_type) = match (req.method(), path) {
         // API routes
         (&Method::POST, "/api/v1/probe") => {
-            let (s, b) = api::handle_probe(req, &state.writer).await;
+            let (s, b) = match state.auth.authenticate(req.headers()).await {
+                Ok(grant) => {
+                    api::handle_probe(req, &state, identity.as_deref(), grant.as_ref()).await
+                }
+                Err(rejection) => rejection,
+            };
+            (s, b, "application/json")
+        }
+        (&Method::POST, "/api/v1/register") => {
+            let (s, b) =
+                api::handle_register(req, &remote_addr.to_string(), &state.registrations).await;
+            (s, b, "application/json")
+        }
+        (&Method::GET, _) if path.starts_with("/api/v1/register/") => {
+            let id = &path["/api/v1/register/".len()..];
+            let (s, b) = api::handle_registration_status(&req, id, &state.registrations).await;
+            (s, b, "application/json")
+        }
+        (&Method::GET, "/api/v1/nodes") => {
+            let tags = parse_tag_filter_from_query(req.uri().query());
+            let (s, b) = api::handle_nodes(&state.nodes, &tags).await;
+            (s, b, "application/json")
+        }
+        (&Method::GET, "/api/v1/metrics") => {
+            let (s, b) = api::handle_metrics_catalog().await;
             (s, b, "application/json")
         }
         (&Method::GET, "/health") => {
@@ -108,7 +184,12 @@ async fn route(
 
         // Web UI routes
         (&Method::GET, "/ui") => {
-            let (s, b) = web::handle_home(&state.data_dir).await;
+            let tags = parse_tag_filter_from_query(req.uri().query());
+            let (s, b) = web::handle_home(&state.data_dir, &state.nodes, &tags).await;
+            (s, b, "text/html; charset=utf-8")
+        }
+        (&Method::GET, "/ui/registrations") => {
+            let (s, b) = web::handle_registrations(&state.registrations).await;
             (s, b, "text/html; charset=utf-8")
         }
         (&Method::GET, _) if path.starts_with("/ui/node/") => {
@@ -122,11 +203,17 @@ async fn route(
         }
     };
 
-    Ok(Response::builder()
+    let mut builder = Response::builder()
         .status(status)
-        .header("Content-Type", content_type)
-        .body(body)
-        .unwrap())
+        .header("Content-Type", content_type);
+    if status == StatusCode::UNAUTHORIZED {
+        builder = builder.header(WWW_AUTHENTICATE, "Bearer");
+    }
+    if status == StatusCode::SERVICE_UNAVAILABLE || status == StatusCode::TOO_MANY_REQUESTS {
+        builder = builder.header(RETRY_AFTER, RETRY_AFTER_SECS);
+    }
+
+    Ok(builder.body(body).unwrap())
 }
 
 async fn route_node(
@@ -149,27 +236,43 @@ async fn route_node(
     if parts.len() == 5 {
         let chart_file = parts[4];
 
-        // Parse date range from query parameters
+        // Parse date range and label filter from query parameters
         let range = parse_date_range_from_query(query);
+        let labels = parse_label_filter_from_query(query);
 
         let (status, body) = match chart_file {
             "cpu.svg" => {
-                charts::handle_cpu_chart(node_id, &range, &state.date_range_reader).await
+                charts::handle_cpu_chart(node_id, &labels, &range, &state.date_range_reader).await
             }
             "memory.svg" => {
-                charts::handle_memory_chart(node_id, &range, &state.date_range_reader).await
+                charts::handle_memory_chart(node_id, &labels, &range, &state.date_range_reader)
+                    .await
             }
             "temperature.svg" => {
-                charts::handle_temperature_chart(node_id, &range, &state.date_range_reader).await
+                charts::handle_temperature_chart(node_id, &labels, &range, &state.date_range_reader)
+                    .await
             }
             "network.svg" => {
-                charts::handle_network_chart(node_id, &range, &state.date_range_reader).await
+                charts::handle_network_chart(node_id, &labels, &range, &state.date_range_reader)
+                    .await
             }
             "disk.svg" => {
-                charts::handle_disk_chart(node_id, &range, &state.date_range_reader).await
+                charts::handle_disk_chart(node_id, &labels, &range, &state.date_range_reader).await
             }
             "forks.svg" => {
-                charts::handle_forks_chart(node_id, &range, &state.date_range_reader).await
+                charts::handle_forks_chart(node_id, &labels, &range, &state.date_range_reader).await
+            }
+            "prometheus.svg" => {
+                let params = parse_query_string(query);
+                let metric = params.get("metric").map(String::as_str).unwrap_or("");
+                charts::handle_prometheus_chart(
+                    node_id,
+                    metric,
+                    &labels,
+                    &range,
+                    &state.date_range_reader,
+                )
+                .await
             }
             _ => api::handle_not_found().await,
         };
@@ -193,7 +296,7 @@ fn parse_query_string(query: Option<&str>) -> HashMap<String, String> {
     if let Some(q) = query {
         for pair in q.split('&') {
             if let Some((key, value)) = pair.split_once('=') {
-                params.insert(key.to_string(), value.to_string());
+                params.insert(percent_decode(key), percent_decode(value));
             }
         }
     }
@@ -201,6 +304,49 @@ fn parse_query_string(query: Option<&str>) -> HashMap<String, String> {
     params
 }
 
+/// Decode `%XX` escapes and `+` in a query string component
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit() =>
+            {
+                // Two ASCII hex digits always parse
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                out.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parse a label filter from `label.<key>=<value>` query parameters
+fn parse_label_filter_from_query(query: Option<&str>) -> BTreeMap<String, String> {
+    parse_prefixed_filter(query, "label.")
+}
+
+/// Parse a node tag filter from `tag.<key>=<value>` query parameters
+fn parse_tag_filter_from_query(query: Option<&str>) -> BTreeMap<String, String> {
+    parse_prefixed_filter(query, "tag.")
+}
+
+fn parse_prefixed_filter(query: Option<&str>, prefix: &str) -> BTreeMap<String, String> {
+    parse_query_string(query)
+        .into_iter()
+        .filter_map(|(key, value)| key.strip_prefix(prefix).map(|k| (k.to_string(), value)))
+        .collect()
+}
+
 /// Parse date range from query parameters
 fn parse_date_range_from_query(query: Option<&str>) -> DateRange {
     let params = parse_query_string(query);
@@ -217,3 +363,36 @@ fn parse_date_range_from_query(query: Option<&str>) -> DateRange {
         _ => DateRange::today(), // Default to today
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("%2Fvar%2Flog"), "/var/log");
+        assert_eq!(percent_decode("coretemp+Package"), "coretemp Package");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+        // Only two hex digits make an escape
+        assert_eq!(percent_decode("%+1"), "% 1");
+        assert_eq!(percent_decode("%-1x"), "%-1x");
+    }
+
+    #[test]
+    fn test_parse_label_filter_from_query() {
+        let labels = parse_label_filter_from_query(Some("range=7d&label.mount=%2Fvar"));
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels["mount"], "/var");
+
+        assert!(parse_label_filter_from_query(None).is_empty());
+    }
+
+    #[test]
+    fn test_parse_tag_filter_from_query() {
+        let tags = parse_tag_filter_from_query(Some("tag.env=prod&label.mount=%2F&tag.dc=fra1"));
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags["env"], "prod");
+        assert_eq!(tags["dc"], "fra1");
+    }
+}
@@ -0,0 +1,371 @@
+// src/http/signature.rs
+
+use ferrview_common::signing::{
+    HEADER_NODE_ID, HEADER_NONCE, HEADER_SIGNATURE, HEADER_TIMESTAMP, Signature,
+};
+use hyper::StatusCode;
+use hyper::header::HeaderMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+use tracing::{debug, error, warn};
+
+use crate::http::response::{self, BoxBody};
+use crate::store::auth::AuthStore;
+
+/// Nonces remembered per node. Only nonces younger than the allowed clock
+/// skew need remembering, since older requests are rejected as stale anyway;
+/// a node signing more than this within the window is turned away until some
+/// expire, as forgetting live ones would let them be replayed.
+const MAX_NONCES_PER_NODE: usize = 1024;
+
+type Rejection = (StatusCode, BoxBody);
+
+/// Checks batch signatures made with each node's shared secret, and
+/// rejects batches that are stale or replayed
+pub struct SignatureVerifier {
+    store: Arc<AuthStore>,
+    require_signature: bool,
+    max_skew_secs: i64,
+    nonces: Mutex<HashMap<String, NonceCache>>,
+}
+
+impl SignatureVerifier {
+    pub fn new(store: Arc<AuthStore>, require_signature: bool, max_skew_secs: u64) -> Self {
+        Self {
+            store,
+            require_signature,
+            max_skew_secs: i64::try_from(max_skew_secs).unwrap_or(i64::MAX),
+            nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The signature headers of a request, `None` if it carries none
+    pub fn signature(headers: &HeaderMap) -> Result<Option<Signature>, Rejection> {
+        let names = [
+            HEADER_NODE_ID,
+            HEADER_TIMESTAMP,
+            HEADER_NONCE,
+            HEADER_SIGNATURE,
+        ];
+        if !names.iter().any(|name| headers.contains_key(*name)) {
+            return Ok(None);
+        }
+
+        let get = |name| headers.get(name).and_then(|v| v.to_str().ok());
+        let (Some(node_id), Some(timestamp), Some(nonce), Some(signature)) = (
+            get(HEADER_NODE_ID),
+            get(HEADER_TIMESTAMP),
+            get(HEADER_NONCE),
+            get(HEADER_SIGNATURE),
+        ) else {
+            return Err(reject("Incomplete signature headers"));
+        };
+
+        let timestamp = timestamp
+            .parse()
+            .map_err(|_| reject("Invalid signature timestamp"))?;
+        if nonce.is_empty() {
+            return Err(reject("Empty signature nonce"));
+        }
+
+        Ok(Some(Signature {
+            node_id: node_id.to_string(),
+            timestamp,
+            nonce: nonce.to_string(),
+            signature: signature.to_string(),
+        }))
+    }
+
+    /// Check a signed request body. The nonce is only recorded once the
+    /// signature holds, so forged requests cannot fill the cache.
+    pub async fn verify(&self, signature: &Signature, body: &[u8]) -> Result<(), Rejection> {
+        self.verify_at(signature, body, OffsetDateTime::now_utc().unix_timestamp())
+            .await
+    }
+
+    async fn verify_at(
+        &self,
+        signature: &Signature,
+        body: &[u8],
+        now: i64,
+    ) -> Result<(), Rejection> {
+        // The timestamp is not authenticated yet, so it may be anything
+        let skew = now.checked_sub(signature.timestamp).map(i64::unsigned_abs);
+        if skew.is_none_or(|skew| skew > self.max_skew_secs.unsigned_abs()) {
+            warn!(
+                "Rejected batch from node {} signed at {}, {}s from now",
+                signature.node_id,
+                signature.timestamp,
+                now.saturating_sub(signature.timestamp)
+            );
+            return Err(reject("Stale signature timestamp"));
+        }
+
+        let secret = match self.store.node_secret(&signature.node_id).await {
+            Ok(Some(secret)) => secret,
+            Ok(None) => {
+                warn!(
+                    "Rejected signed batch from node {} without a secret",
+                    signature.node_id
+                );
+                return Err(reject("Unknown signing node"));
+            }
+            Err(e) => {
+                error!("Failed to look up signing secret: {}", e);
+                return Err(response::json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to check credentials",
+                ));
+            }
+        };
+
+        if !signature.verify(&secret, body) {
+            warn!(
+                "Rejected batch with a bad signature for node {}",
+                signature.node_id
+            );
+            return Err(reject("Invalid signature"));
+        }
+
+        let nonce = self
+            .nonces
+            .lock()
+            .unwrap()
+            .entry(signature.node_id.clone())
+            .or_default()
+            .insert(
+                &signature.nonce,
+                signature.timestamp,
+                now - self.max_skew_secs,
+            );
+        match nonce {
+            Nonce::Fresh => {}
+            Nonce::Replayed => {
+                warn!(
+                    "Rejected replayed batch from node {} (nonce {})",
+                    signature.node_id, signature.nonce
+                );
+                return Err(reject("Replayed request"));
+            }
+            Nonce::Full => {
+                // The node keeps the batch and comes back after Retry-After
+                warn!(
+                    "Node {} sent more than {} signed batches in {}s, turning one away",
+                    signature.node_id, MAX_NONCES_PER_NODE, self.max_skew_secs
+                );
+                return Err(response::json_error(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "Too many signed requests, retry later",
+                ));
+            }
+        }
+
+        debug!("Verified signature from node {}", signature.node_id);
+        Ok(())
+    }
+
+    /// Check an unsigned batch for `node_ids`. It is refused if signatures
+    /// are required, or if any of the nodes has a secret and so should have
+    /// signed it.
+    pub async fn check_unsigned<'a>(
+        &self,
+        node_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), Rejection> {
+        if self.require_signature {
+            warn!("Rejected unsigned batch");
+            return Err(reject("Signature required"));
+        }
+
+        let node_ids: HashSet<&str> = node_ids.into_iter().collect();
+        for node_id in node_ids {
+            match self.store.node_secret(node_id).await {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    warn!(
+                        "Rejected unsigned batch for node {}, which has a secret",
+                        node_id
+                    );
+                    return Err(reject("Signature required for this node_id"));
+                }
+                Err(e) => {
+                    error!("Failed to look up signing secret: {}", e);
+                    return Err(response::json_error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to check credentials",
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn reject(message: &str) -> Rejection {
+    response::json_error(StatusCode::UNAUTHORIZED, message)
+}
+
+/// What recording a nonce found
+#[derive(Debug, PartialEq)]
+enum Nonce {
+    Fresh,
+    Replayed,
+    /// The cache holds `MAX_NONCES_PER_NODE` nonces that have not expired
+    Full,
+}
+
+/// Recent nonces of one node, oldest first
+#[derive(Default)]
+struct NonceCache {
+    order: VecDeque<(i64, String)>,
+    seen: HashSet<String>,
+}
+
+impl NonceCache {
+    /// Record `nonce`, signed at `timestamp`, once entries signed before
+    /// `expired_before` are forgotten
+    fn insert(&mut self, nonce: &str, timestamp: i64, expired_before: i64) -> Nonce {
+        while let Some((signed_at, _)) = self.order.front() {
+            if *signed_at >= expired_before {
+                break;
+            }
+            if let Some((_, old)) = self.order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+
+        if self.seen.contains(nonce) {
+            return Nonce::Replayed;
+        }
+        if self.order.len() >= MAX_NONCES_PER_NODE {
+            return Nonce::Full;
+        }
+        self.seen.insert(nonce.to_string());
+        self.order.push_back((timestamp, nonce.to_string()));
+        Nonce::Fresh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    const NOW: i64 = 1_704_110_400;
+
+    async fn verifier() -> (SignatureVerifier, String) {
+        let store = AuthStore::in_memory().await;
+        let secret = store.rotate_node_secret("node-1").await.unwrap();
+        (SignatureVerifier::new(Arc::new(store), false, 300), secret)
+    }
+
+    #[tokio::test]
+    async fn test_valid_signature_is_single_use() {
+        let (verifier, secret) = verifier().await;
+        let signature = Signature::sign(&secret, "node-1", NOW, b"batch");
+
+        assert!(verifier.verify_at(&signature, b"batch", NOW).await.is_ok());
+
+        let (status, _) = verifier
+            .verify_at(&signature, b"batch", NOW + 1)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // A new nonce for the same body is fine
+        let again = Signature::sign(&secret, "node-1", NOW, b"batch");
+        assert!(verifier.verify_at(&again, b"batch", NOW + 1).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_and_stale_signatures() {
+        let (verifier, secret) = verifier().await;
+
+        let signature = Signature::sign(&secret, "node-1", NOW, b"batch");
+        assert!(verifier.verify_at(&signature, b"other", NOW).await.is_err());
+        // The forged attempt did not burn the nonce
+        assert!(verifier.verify_at(&signature, b"batch", NOW).await.is_ok());
+
+        let stale = Signature::sign(&secret, "node-1", NOW - 301, b"batch");
+        assert!(verifier.verify_at(&stale, b"batch", NOW).await.is_err());
+        let future = Signature::sign(&secret, "node-1", NOW + 301, b"batch");
+        assert!(verifier.verify_at(&future, b"batch", NOW).await.is_err());
+
+        // Timestamps too far off to subtract are stale too
+        for timestamp in [i64::MIN, i64::MAX] {
+            let absurd = Signature::sign(&secret, "node-1", timestamp, b"batch");
+            let (status, _) = verifier
+                .verify_at(&absurd, b"batch", NOW)
+                .await
+                .unwrap_err();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        let unknown = Signature::sign(&secret, "node-2", NOW, b"batch");
+        assert!(verifier.verify_at(&unknown, b"batch", NOW).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_batches() {
+        let (verifier, _) = verifier().await;
+        assert!(verifier.check_unsigned(["node-2"]).await.is_ok());
+        assert!(verifier.check_unsigned(["node-2", "node-1"]).await.is_err());
+
+        let strict = SignatureVerifier::new(Arc::new(AuthStore::in_memory().await), true, 300);
+        assert!(strict.check_unsigned(["node-2"]).await.is_err());
+    }
+
+    #[test]
+    fn test_signature_headers() {
+        assert!(matches!(
+            SignatureVerifier::signature(&HeaderMap::new()),
+            Ok(None)
+        ));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER_NODE_ID, HeaderValue::from_static("node-1"));
+        headers.insert(HEADER_TIMESTAMP, HeaderValue::from_static("1704110400"));
+        headers.insert(HEADER_NONCE, HeaderValue::from_static("abc"));
+        assert!(SignatureVerifier::signature(&headers).is_err());
+
+        headers.insert(HEADER_SIGNATURE, HeaderValue::from_static("00"));
+        let signature = SignatureVerifier::signature(&headers).unwrap().unwrap();
+        assert_eq!(signature.timestamp, NOW);
+
+        headers.insert(HEADER_TIMESTAMP, HeaderValue::from_static("yesterday"));
+        assert!(SignatureVerifier::signature(&headers).is_err());
+    }
+
+    #[test]
+    fn test_nonce_cache_forgets_expired_entries() {
+        let mut cache = NonceCache::default();
+        assert_eq!(cache.insert("a", NOW, NOW - 300), Nonce::Fresh);
+        assert_eq!(cache.insert("a", NOW, NOW - 300), Nonce::Replayed);
+        assert_eq!(cache.insert("b", NOW + 10, NOW - 290), Nonce::Fresh);
+
+        // Once "a" is past the window it is dropped; a replay of it would
+        // fail the timestamp check instead
+        assert_eq!(cache.insert("c", NOW + 301, NOW + 1), Nonce::Fresh);
+        assert!(!cache.seen.contains("a"));
+        assert!(cache.seen.contains("b"));
+    }
+
+    #[test]
+    fn test_full_nonce_cache_keeps_live_nonces() {
+        let mut cache = NonceCache::default();
+        for i in 0..MAX_NONCES_PER_NODE {
+            assert_eq!(cache.insert(&i.to_string(), NOW, NOW - 300), Nonce::Fresh);
+        }
+
+        // Nothing has expired: new nonces are turned away and the oldest
+        // one still counts as a replay
+        assert_eq!(cache.insert("new", NOW, NOW - 300), Nonce::Full);
+        assert_eq!(cache.insert("0", NOW, NOW - 300), Nonce::Replayed);
+        assert_eq!(cache.order.len(), MAX_NONCES_PER_NODE);
+
+        // Once they expire there is room again
+        assert_eq!(cache.insert("new", NOW + 301, NOW + 1), Nonce::Fresh);
+        assert_eq!(cache.order.len(), 1);
+    }
+}
@@ -0,0 +1,329 @@
+// src/http/tls.rs
+
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::config::{Config, ConfigError, TlsConfig};
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("{0}: {1}")]
+    File(String, String),
+
+    #[error("TLS error: {0}")]
+    Rustls(#[from] rustls::Error),
+
+    #[error("Invalid client CA: {0}")]
+    ClientCa(String),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error("{0} has no [tls] section")]
+    Missing(String),
+}
+
+/// The node a client certificate is allowed to report for
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientIdentity {
+    pub subject: String,
+    pub node_id: String,
+}
+
+/// TLS settings in effect for new connections
+pub struct TlsState {
+    acceptor: TlsAcceptor,
+    config: TlsConfig,
+}
+
+impl TlsState {
+    pub fn new(config: TlsConfig) -> Result<Self, TlsError> {
+        let server_config = server_config(&config)?;
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            config,
+        })
+    }
+
+    pub fn acceptor(&self) -> &TlsAcceptor {
+        &self.acceptor
+    }
+
+    /// Identity of a client from the certificate it presented, `None` if it
+    /// presented none or one without a common name
+    pub fn client_identity(&self, certs: Option<&[CertificateDer<'_>]>) -> Option<ClientIdentity> {
+        let (_, cert) = X509Certificate::from_der(certs?.first()?).ok()?;
+        let common_name = cert.subject().iter_common_name().next()?.as_str().ok()?;
+
+        let node_id = self
+            .config
+            .client_node_ids
+            .get(common_name)
+            .map_or(common_name, String::as_str);
+
+        Some(ClientIdentity {
+            subject: cert.subject().to_string(),
+            node_id: node_id.to_string(),
+        })
+    }
+}
+
+/// Current TLS settings, replaced wholesale on reload so that connections
+/// already established keep the settings they were accepted with
+pub struct TlsReloader {
+    config_path: String,
+    current: RwLock<Arc<TlsState>>,
+}
+
+impl TlsReloader {
+    pub fn new(config_path: &str, config: TlsConfig) -> Result<Self, TlsError> {
+        Ok(Self {
+            config_path: config_path.to_string(),
+            current: RwLock::new(Arc::new(TlsState::new(config)?)),
+        })
+    }
+
+    pub fn current(&self) -> Arc<TlsState> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Re-read the config file and the certificates it names. On error the
+    /// previous settings stay in effect.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let config = Config::load(&self.config_path)?
+            .tls
+            .ok_or_else(|| TlsError::Missing(self.config_path.clone()))?;
+        let state = TlsState::new(config)?;
+
+        *self.current.write().unwrap() = Arc::new(state);
+        info!("Reloaded TLS certificates from {}", self.config_path);
+        Ok(())
+    }
+}
+
+fn server_config(tls: &TlsConfig) -> Result<ServerConfig, TlsError> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+
+    let builder = match &tls.client_ca_file {
+        Some(ca_file) => {
+            let mut roots = RootCertStore::empty();
+            let (added, ignored) = roots.add_parsable_certificates(load_certs(ca_file)?);
+            if ignored > 0 {
+                warn!("Ignored {} unusable certificate(s) in {}", ignored, ca_file);
+            }
+            if added == 0 {
+                return Err(TlsError::ClientCa(format!(
+                    "{}: no usable certificates",
+                    ca_file
+                )));
+            }
+            debug!("Requiring client certificates signed by {}", ca_file);
+
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| TlsError::ClientCa(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let key = PrivateKeyDer::from_pem_file(&tls.key_file)
+        .map_err(|e| TlsError::File(tls.key_file.clone(), e.to_string()))?;
+    let mut config = builder.with_single_cert(load_certs(&tls.cert_file)?, key)?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| TlsError::File(path.to_string(), e.to_string()))?;
+
+    if certs.is_empty() {
+        return Err(TlsError::File(
+            path.to_string(),
+            "no certificates found".to_string(),
+        ));
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair};
+    use rustls::ClientConfig;
+    use rustls::pki_types::ServerName;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    struct Pki {
+        dir: PathBuf,
+        ca: Certificate,
+        ca_key: KeyPair,
+    }
+
+    impl Pki {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "ferrview-collector-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+
+            let ca_key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = params.self_signed(&ca_key).unwrap();
+            fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+
+            Self { dir, ca, ca_key }
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.dir.join(name).to_string_lossy().into_owned()
+        }
+
+        /// Issue a certificate for `common_name`, returning (cert, key) PEM
+        fn issue(&self, common_name: &str) -> (String, String) {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec![common_name.to_string()]).unwrap();
+            params
+                .distinguished_name
+                .push(DnType::CommonName, common_name);
+            let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+            (cert.pem(), key.serialize_pem())
+        }
+
+        fn tls_config(&self, client_node_ids: BTreeMap<String, String>) -> TlsConfig {
+            let (cert, key) = self.issue("localhost");
+            fs::write(self.path("server.pem"), cert).unwrap();
+            fs::write(self.path("server.key"), key).unwrap();
+
+            TlsConfig {
+                cert_file: self.path("server.pem"),
+                key_file: self.path("server.key"),
+                client_ca_file: Some(self.path("ca.pem")),
+                client_node_ids,
+            }
+        }
+
+        fn client_config(&self, common_name: &str) -> ClientConfig {
+            let mut roots = RootCertStore::empty();
+            roots.add(self.ca.der().clone()).unwrap();
+
+            let (cert, key) = self.issue(common_name);
+            ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_client_auth_cert(
+                    vec![CertificateDer::from_pem_slice(cert.as_bytes()).unwrap()],
+                    PrivateKeyDer::from_pem_slice(key.as_bytes()).unwrap(),
+                )
+                .unwrap()
+        }
+    }
+
+    impl Drop for Pki {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Handshake over an in-memory pipe, returning the server's view of the client
+    async fn handshake(state: &TlsState, client: ClientConfig) -> Option<ClientIdentity> {
+        let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+        let connector = TlsConnector::from(Arc::new(client));
+        let name = ServerName::try_from("localhost").unwrap();
+
+        let (client, server) = tokio::join!(
+            connector.connect(name, client_io),
+            state.acceptor().accept(server_io)
+        );
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+
+        // Exchange a byte so both sides complete the handshake
+        client.write_all(b"x").await.unwrap();
+        client.flush().await.unwrap();
+        let mut buf = [0u8; 1];
+        server.read_exact(&mut buf).await.unwrap();
+
+        state.client_identity(server.get_ref().1.peer_certificates())
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_identity() {
+        let pki = Pki::new("tls-identity");
+        let state = TlsState::new(pki.tls_config(BTreeMap::from([(
+            "web-01.example.com".to_string(),
+            "2fd75b6b".to_string(),
+        )])))
+        .unwrap();
+
+        let identity = handshake(&state, pki.client_config("node-1"))
+            .await
+            .unwrap();
+        assert_eq!(identity.node_id, "node-1");
+        assert!(identity.subject.contains("CN=node-1"));
+
+        let identity = handshake(&state, pki.client_config("web-01.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(identity.node_id, "2fd75b6b");
+    }
+
+    #[test]
+    fn test_reload_keeps_previous_state_on_error() {
+        let pki = Pki::new("tls-reload");
+        let config_path = pki.path("collector.toml");
+        let tls = pki.tls_config(BTreeMap::new());
+        fs::write(
+            &config_path,
+            format!(
+                "[tls]\ncert_file = \"{}\"\nkey_file = \"{}\"\n",
+                tls.cert_file, tls.key_file
+            ),
+        )
+        .unwrap();
+
+        let reloader = TlsReloader::new(&config_path, tls.clone()).unwrap();
+        let before = reloader.current();
+
+        reloader.reload().unwrap();
+        assert!(!Arc::ptr_eq(&before, &reloader.current()));
+
+        // A broken key leaves the last good settings in place
+        let current = reloader.current();
+        fs::write(&tls.key_file, "garbage").unwrap();
+        assert!(reloader.reload().is_err());
+        assert!(Arc::ptr_eq(&current, &reloader.current()));
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let result = TlsState::new(TlsConfig {
+            cert_file: "/nonexistent/server.pem".to_string(),
+            key_file: "/nonexistent/server.key".to_string(),
+            client_ca_file: None,
+            client_node_ids: BTreeMap::new(),
+        });
+        assert!(matches!(result, Err(TlsError::File(..))));
+    }
+}
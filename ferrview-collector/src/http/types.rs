@@ -1,12 +1,10 @@
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
+use std::collections::BTreeMap;
 
-use ferrview_common::ProbeDataPoint;
+use ferrview_common::metrics::MetricDef;
 
-/// Request payload for probe data submission
-#[derive(Debug, Deserialize)]
-pub struct ProbeDataBatch {
-    pub data: Vec<ProbeDataPoint>,
-}
+/// Request payload and health check response are shared with ferrview-node
+pub use ferrview_common::protocol::{HealthResponse, IncomingBatch};
 
 /// Standard success response
 #[derive(Debug, Serialize)]
@@ -14,12 +12,20 @@ pub struct SuccessResponse {
     pub status: String,
 }
 
-/// Health check response
+/// A node in the `/api/v1/nodes` response
 #[derive(Debug, Serialize)]
-pub struct HealthResponse {
-    pub status: String,
-    pub version: String,
-    pub max_request_size_bytes: usize,
+pub struct NodeResponse {
+    pub node_id: String,
+    pub agent_version: String,
+    pub tags: BTreeMap<String, String>,
+    /// Epoch milliseconds of the last change to the node's metadata
+    pub updated_at: i64,
+}
+
+/// Metric catalog response
+#[derive(Debug, Serialize)]
+pub struct MetricsCatalogResponse {
+    pub metrics: &'static [MetricDef],
 }
 
 /// Standard error response
@@ -39,12 +45,16 @@ mod tests {
             status: "healthy".to_string(),
             version: "0.2.0".to_string(),
             max_request_size_bytes: 10485760,
+            protocol_versions: vec![1, 2],
+            content_types: vec!["application/json".to_string()],
+            content_encodings: vec![],
         };
 
         let json = serde_json::to_string(&response).unwrap();
         assert!(json.contains("\"status\":\"healthy\""));
         assert!(json.contains("\"version\":\"0.2.0\""));
         assert!(json.contains("\"max_request_size_bytes\":10485760"));
+        assert!(json.contains("\"protocol_versions\":[1,2]"));
     }
 
     #[test]
@@ -53,10 +63,25 @@ mod tests {
             status: "healthy".to_string(),
             version: "1.0.0".to_string(),
             max_request_size_bytes: 5242880,
+            protocol_versions: vec![1],
+            content_types: vec!["application/json".to_string()],
+            content_encodings: vec![],
         };
 
         assert_eq!(response.status, "healthy");
         assert_eq!(response.version, "1.0.0");
         assert_eq!(response.max_request_size_bytes, 5242880);
     }
+
+    #[test]
+    fn test_metrics_catalog_serialization() {
+        let response = MetricsCatalogResponse {
+            metrics: ferrview_common::metrics::ALL,
+        };
+
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.starts_with("{\"metrics\":[{"));
+        assert!(json.contains("\"name\":\"memory_used_bytes\""));
+        assert!(json.contains("\"unit\":\"B\""));
+    }
 }
@@ -1,7 +1,7 @@
 // src/http/ui/helpers.rs
 
 use crate::store::queries::MetricDataPoint;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /// Shorten UUID for display (first 8 characters)
 pub fn shorten_uuid(uuid: &str) -> String {
@@ -12,16 +12,6 @@ pub fn shorten_uuid(uuid: &str) -> String {
     }
 }
 
-/// Parse ISO timestamp to Unix timestamp
-pub fn parse_timestamp(timestamp_str: &str) -> Result<i64, ()> {
-    time::OffsetDateTime::parse(
-        timestamp_str,
-        &time::format_description::well_known::Rfc3339,
-    )
-    .map(|dt| dt.unix_timestamp())
-    .map_err(|_| ())
-}
-
 /// Get current date in YYYY-MM-DD format
 pub fn current_date() -> String {
     let now = time::OffsetDateTime::now_utc();
@@ -33,40 +23,29 @@ pub fn current_date() -> String {
     )
 }
 
-/// Group metrics by extracting index from pattern
-/// e.g., "cpu_core_0_usage_percent" → "Core 0"
-pub fn group_metrics_by_index(
+/// Group metrics into series by the value of one label
+/// e.g., cpu_core_usage_percent {core="0"} → "0"
+///
+/// Points without the label, or without a numeric value, are skipped.
+pub fn group_metrics_by_label(
     metrics: &[MetricDataPoint],
-    prefix: &str,
-) -> HashMap<String, Vec<(i64, f64)>> {
-    let mut map: HashMap<String, Vec<(i64, f64)>> = HashMap::new();
+    label: &str,
+) -> BTreeMap<String, Vec<(i64, f64)>> {
+    let mut map: BTreeMap<String, Vec<(i64, f64)>> = BTreeMap::new();
 
     for metric in metrics {
-        if let Some(index) = extract_index(&metric.probe_name, prefix) {
-            let label = if prefix.contains("cpu") {
-                format!("Core {}", index)
-            } else if prefix.contains("sensor") {
-                format!("Sensor {}", index)
-            } else {
-                format!("#{}", index)
-            };
-
-            if let Ok(timestamp) = parse_timestamp(&metric.timestamp)
-                && let Ok(value) = metric.probe_value.parse::<f64>()
-            {
-                map.entry(label).or_default().push((timestamp, value));
-            }
+        if let Some(key) = metric.labels.get(label)
+            && let Some(value) = metric.probe_value.as_f64()
+        {
+            map.entry(key.clone())
+                .or_default()
+                .push((metric.timestamp, value));
         }
     }
 
     map
 }
 
-fn extract_index(name: &str, prefix: &str) -> Option<u32> {
-    let without_prefix = name.strip_prefix(prefix)?;
-    without_prefix.split('_').next()?.parse().ok()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,8 +57,28 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_timestamp() {
-        assert!(parse_timestamp("2024-12-08T09:41:30Z").is_ok());
-        assert!(parse_timestamp("invalid").is_err());
+    fn test_group_metrics_by_label() {
+        let point = |mount: &str, timestamp: i64, value: f64| MetricDataPoint {
+            node_id: "n".to_string(),
+            timestamp,
+            probe_type: "sysinfo".to_string(),
+            probe_name: "disk_usage_percent".to_string(),
+            probe_value: ferrview_common::ProbeValue::Float(value),
+            labels: BTreeMap::from([("mount".to_string(), mount.to_string())]),
+        };
+
+        let metrics = vec![
+            point("/", 1_733_648_400_000, 10.0),
+            point("/var", 1_733_648_400_000, 50.0),
+            point("/", 1_733_648_460_000, 11.0),
+        ];
+
+        let grouped = group_metrics_by_label(&metrics, "mount");
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped["/"].len(), 2);
+        assert_eq!(grouped["/"][1], (1_733_648_460_000, 11.0));
+        assert_eq!(grouped["/var"][0].1, 50.0);
+
+        assert!(group_metrics_by_label(&metrics, "iface").is_empty());
     }
 }
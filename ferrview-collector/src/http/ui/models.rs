@@ -1,5 +1,7 @@
 // src/http/ui/models.rs
 
+use crate::store::nodes::Tags;
+
 /// Summary info for a node (used in home page list)
 #[derive(Debug, Clone)]
 pub struct NodeSummary {
@@ -10,7 +12,10 @@ pub struct NodeSummary {
     pub memory_total_gb: Option<f64>,
     pub temp_sensors: Option<String>,
     pub max_temp_celsius: Option<f64>,
-    pub last_seen: Option<String>,
+    /// Epoch milliseconds of the latest data point
+    pub last_seen: Option<i64>,
+    /// Tags from the node's config
+    pub tags: Tags,
 }
 
 /// Detailed info for a single node (used in dashboard)
@@ -23,8 +28,27 @@ pub struct NodeDetails {
     pub cpu_arch: Option<String>,
     pub cpu_cores: Option<String>,
     pub memory_total_gb: Option<f64>,
-    pub last_seen: Option<String>,
+    /// Epoch milliseconds of the latest data point
+    pub last_seen: Option<i64>,
     pub current_date: String,
+    /// Metrics scraped from the node's Prometheus endpoints today
+    pub prometheus_metrics: Vec<String>,
+}
+
+/// A node registration (used in the registrations page)
+#[derive(Debug, Clone)]
+pub struct RegistrationRow {
+    pub id: String,
+    pub node_id: String,
+    pub hostname: Option<String>,
+    pub agent_version: String,
+    pub remote_addr: String,
+    pub status: &'static str,
+    /// Epoch milliseconds
+    pub requested_at: Option<i64>,
+    pub decided_at: Option<i64>,
+    /// The node_id already has a token or signing secret
+    pub has_credentials: bool,
 }
 
 impl NodeSummary {
@@ -38,6 +62,7 @@ impl NodeSummary {
             temp_sensors: None,
             max_temp_celsius: None,
             last_seen: None,
+            tags: Tags::new(),
         }
     }
 }
@@ -54,6 +79,7 @@ impl NodeDetails {
             memory_total_gb: None,
             last_seen: None,
             current_date,
+            prometheus_metrics: Vec::new(),
         }
     }
 }
@@ -4,7 +4,8 @@
 
 use askama::Template;
 
-use super::models::{NodeDetails, NodeSummary};
+use super::models::{NodeDetails, NodeSummary, RegistrationRow};
+use crate::store::nodes::Tags;
 
 // Custom filters module - must be named `filters` for Askama to find them
 mod filters {
@@ -33,11 +34,21 @@ mod filters {
         })
     }
 
-    /// Format Option<String> timestamp for display
-    pub fn format_timestamp(timestamp: &Option<String>) -> ::askama::Result<String> {
+    /// Format Option<i64> epoch milliseconds for display
+    pub fn format_timestamp(timestamp: &Option<i64>) -> ::askama::Result<String> {
         Ok(match timestamp {
-            Some(ts) if ts.len() >= 19 => format!("{} {}", &ts[..10], &ts[11..19]),
-            Some(ts) => ts.clone(),
+            Some(ms) => match ferrview_common::timestamp::from_millis(*ms) {
+                Some(dt) => format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    dt.year(),
+                    dt.month() as u8,
+                    dt.day(),
+                    dt.hour(),
+                    dt.minute(),
+                    dt.second()
+                ),
+                None => ms.to_string(),
+            },
             None => "N/A".to_string(),
         })
     }
@@ -48,6 +59,8 @@ mod filters {
 #[template(path = "home.html")]
 pub struct HomeTemplate<'a> {
     pub nodes: &'a [NodeSummary],
+    /// Tags the node list is filtered by
+    pub filter: &'a Tags,
     pub version: &'a str,
 }
 
@@ -61,6 +74,16 @@ pub struct NodeTemplate<'a> {
     pub current_date: &'a str,
 }
 
+/// Node registrations page template
+#[derive(Template)]
+#[template(path = "registrations.html")]
+pub struct RegistrationsTemplate<'a> {
+    pub registrations: &'a [RegistrationRow],
+    pub pending: usize,
+    pub enabled: bool,
+    pub version: &'a str,
+}
+
 /// Error page template
 #[derive(Template)]
 #[template(path = "error.html")]
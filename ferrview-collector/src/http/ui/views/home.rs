@@ -3,10 +3,12 @@
 use askama::Template;
 
 use crate::http::ui::{models::NodeSummary, templates::HomeTemplate};
+use crate::store::nodes::Tags;
 
-pub fn render(nodes: &[NodeSummary]) -> String {
+pub fn render(nodes: &[NodeSummary], filter: &Tags) -> String {
     let template = HomeTemplate {
         nodes,
+        filter,
         version: env!("CARGO_PKG_VERSION"),
     };
 
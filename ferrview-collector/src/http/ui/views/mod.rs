@@ -3,3 +3,4 @@
 pub mod error;
 pub mod home;
 pub mod node;
+pub mod registrations;
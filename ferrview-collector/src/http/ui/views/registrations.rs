@@ -0,0 +1,22 @@
+// src/http/ui/views/registrations.rs
+
+use askama::Template;
+
+use crate::http::ui::{models::RegistrationRow, templates::RegistrationsTemplate};
+
+pub fn render(registrations: &[RegistrationRow], enabled: bool) -> String {
+    let template = RegistrationsTemplate {
+        registrations,
+        pending: registrations
+            .iter()
+            .filter(|r| r.status == "pending")
+            .count(),
+        enabled,
+        version: env!("CARGO_PKG_VERSION"),
+    };
+
+    template.render().unwrap_or_else(|e| {
+        tracing::error!("Failed to render registrations template: {}", e);
+        format!("Template error: {}", e)
+    })
+}
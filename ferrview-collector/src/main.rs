@@ -1,14 +1,30 @@
 // src/main.rs
 
 use argh::FromArgs;
+use ferrview_common::logging::{
+    self, DEFAULT_LOG_MAX_SIZE_MB, LogFormat, LogOptions, parse_log_max_size_mb,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
 use time::macros::format_description;
 use tracing::{debug, error, info};
 use tracing_subscriber::fmt::time::UtcTime;
 
 mod charts;
+mod commands;
+mod config;
 mod http;
+mod scrape;
 mod store;
 
+use config::Config;
+use http::auth::Authenticator;
+use http::registration::Registrations;
+use http::signature::SignatureVerifier;
+use http::tls::TlsReloader;
+use scrape::Scraper;
+use store::auth::AuthStore;
+use store::nodes::NodeStore;
 use store::writer::WriterService;
 
 fn default_host() -> String {
@@ -38,26 +54,87 @@ pub struct Argz {
     /// data directory
     #[argh(option, short = 'd', default = "default_data_dir()")]
     data_dir: String,
+
+    /// config file (TLS and other optional settings)
+    #[argh(option, short = 'c')]
+    config_file: Option<String>,
+
+    /// log format: text (default) or json
+    #[argh(option, default = "LogFormat::Text")]
+    log_format: LogFormat,
+
+    /// write logs to this file instead of stdout
+    #[argh(option)]
+    log_file: Option<PathBuf>,
+
+    /// size in MB at which the log file is rotated (default 10)
+    #[argh(
+        option,
+        default = "DEFAULT_LOG_MAX_SIZE_MB",
+        from_str_fn(parse_log_max_size_mb)
+    )]
+    log_max_size_mb: u64,
+
+    #[argh(subcommand)]
+    command: Option<commands::Command>,
 }
 
 #[tokio::main]
 async fn main() {
+    let argz: Argz = argh::from_env();
+
     let timer = UtcTime::new(format_description!(
         "[year]-[month padding:zero]-[day padding:zero]T[hour padding:zero]:[minute padding:zero]:[second padding:zero]Z"
     ));
+    let options = LogOptions {
+        format: argz.log_format,
+        file: argz.log_file.clone(),
+        max_size_bytes: argz.log_max_size_mb.saturating_mul(1024 * 1024),
+    };
+    if let Err(e) = logging::init(&options, timer, false) {
+        let path = options.file.unwrap_or_default();
+        eprintln!("Failed to open log file {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+    debug!("Args: {:?}", argz);
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .with_timer(timer)
-        .init();
+    if let Some(command) = argz.command {
+        if let Err(e) = commands::run(command, &argz.data_dir).await {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     info!("Starting ferrview-collector");
 
-    let argz: Argz = argh::from_env();
-    debug!("Args: {:?}", argz);
+    let config = match &argz.config_file {
+        Some(path) => match Config::load(path) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Failed to load configuration: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+    debug!("Config: {:?}", config);
+
+    let tls = match (&argz.config_file, config.tls) {
+        (Some(path), Some(tls_config)) => match TlsReloader::new(path, tls_config) {
+            Ok(tls) => Some(Arc::new(tls)),
+            Err(e) => {
+                error!("Failed to set up TLS: {}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    #[cfg(unix)]
+    if let Some(tls) = &tls {
+        spawn_tls_reload_on_sighup(Arc::clone(tls));
+    }
 
     // Initialize database writer service
     let (writer_service, writer_handle) = match WriterService::new(&argz.data_dir).await {
@@ -74,18 +151,68 @@ async fn main() {
         writer_service.run().await;
     });
 
+    let auth_store = match AuthStore::open(&argz.data_dir).await {
+        Ok(store) => Arc::new(store),
+        Err(e) => {
+            error!("Failed to open credential store: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let auth = Authenticator::new(Arc::clone(&auth_store), config.auth.require_token);
+    let signatures = SignatureVerifier::new(
+        Arc::clone(&auth_store),
+        config.auth.require_signature,
+        config.auth.max_clock_skew_secs,
+    );
+    let registrations = Registrations::new(auth_store, config.registration.enabled);
+
+    let nodes = match NodeStore::open(&argz.data_dir).await {
+        Ok(store) => Arc::new(store),
+        Err(e) => {
+            error!("Failed to open node store: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if config.auth.require_token {
+        info!("Bearer tokens required on /api/v1/probe");
+    }
+    if config.auth.require_signature {
+        info!("Signed batches required on /api/v1/probe");
+    }
+    if config.registration.enabled {
+        info!("Node registration enabled, unauthenticated batches are rejected");
+    }
+
+    // Nodes that cannot push are scraped, each on its own task
+    let scrapers: Vec<_> = config
+        .scrape
+        .into_iter()
+        .map(|target| {
+            info!(
+                "Scraping node {} at {} every {}s",
+                target.node_id, target.url, target.interval_secs
+            );
+            let scraper = Scraper::new(target, writer_handle.clone(), Arc::clone(&nodes));
+            tokio::spawn(scraper.run())
+        })
+        .collect();
+
     // Initialize date range reader
     let date_range_reader = store::date_range_reader::DateRangeReader::new(&argz.data_dir);
     info!("Date range reader initialized");
 
     // Create and run HTTP server
-    let server = match http::server::HttpServer::new(
-        &argz.host,
-        &argz.port,
-        writer_handle,
+    let state = http::server::ServerState {
+        writer: writer_handle,
         date_range_reader,
-        argz.data_dir.clone(),
-    ) {
+        data_dir: argz.data_dir.clone(),
+        auth,
+        signatures,
+        registrations,
+        nodes,
+    };
+
+    let server = match http::server::HttpServer::new(&argz.host, &argz.port, state, tls) {
         Ok(s) => s,
         Err(e) => {
             error!("Failed to create HTTP server: {}", e);
@@ -113,6 +240,9 @@ async fn main() {
 
     // Graceful shutdown
     info!("Shutting down...");
+    for scraper in scrapers {
+        scraper.abort();
+    }
     if let Err(e) = writer_handle_clone.shutdown().await {
         error!("Error shutting down writer: {}", e);
     }
@@ -123,3 +253,26 @@ async fn main() {
 
     info!("Shutdown complete");
 }
+
+/// Reload TLS certificates on SIGHUP, e.g. after a certificate renewal
+#[cfg(unix)]
+fn spawn_tls_reload_on_sighup(tls: Arc<TlsReloader>) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Failed to listen for SIGHUP, TLS reload disabled: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading TLS certificates");
+            if let Err(e) = tls.reload() {
+                error!("TLS reload failed, keeping current certificates: {}", e);
+            }
+        }
+    });
+}
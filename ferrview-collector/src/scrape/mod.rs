@@ -0,0 +1,303 @@
+// src/scrape/mod.rs
+
+//! Pull mode: readings fetched on a schedule from nodes that cannot reach
+//! the collector, from the `/api/v1/readings` endpoint of their exporter,
+//! and stored like pushed batches. Targets with `format = "prometheus"` are
+//! any endpoint serving the Prometheus text format, whose samples are
+//! stored with probe_type `prometheus`.
+
+pub mod prometheus;
+
+use bytes::Bytes;
+use ferrview_common::metrics::{MetricDef, SCRAPE_DURATION_SECONDS, SCRAPE_UP};
+use ferrview_common::protocol::{BatchEnvelope, SUPPORTED_PROTOCOL_VERSIONS};
+use ferrview_common::{ProbeDataPoint, ProbeValue, error_chain, timestamp};
+use http_body_util::{BodyExt, Empty, Limited};
+use hyper::header::{ACCEPT, CONTENT_TYPE};
+use hyper::{Request, StatusCode};
+use hyper_util::client::legacy::{Client, connect::HttpConnector};
+use hyper_util::rt::TokioExecutor;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{ScrapeFormat, ScrapeTarget};
+use crate::http::validate::max_request_size;
+use crate::store::nodes::{NodeStore, validate_tags};
+use crate::store::writer::WriterHandle;
+
+#[derive(Debug, Error)]
+enum ScrapeError {
+    #[error("HTTP error: {0}")]
+    Http(String),
+
+    #[error("Unexpected response status: {0}")]
+    Status(StatusCode),
+
+    #[error("Invalid readings: {0}")]
+    Invalid(String),
+
+    #[error("Timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+/// A series: probe type, metric name and labels
+type SeriesKey = (String, String, BTreeMap<String, String>);
+
+/// Scrapes one node on its schedule
+pub struct Scraper {
+    target: ScrapeTarget,
+    client: Client<HttpConnector, Empty<Bytes>>,
+    writer: WriterHandle,
+    nodes: Arc<NodeStore>,
+    /// Timestamp of the latest reading stored per series. A node serves a
+    /// reading until the next collection replaces it, so without this
+    /// scraping faster than it collects would store it again.
+    stored: HashMap<SeriesKey, i64>,
+    /// Whether the last scrape succeeded, so only changes are logged
+    up: Option<bool>,
+}
+
+impl Scraper {
+    pub fn new(target: ScrapeTarget, writer: WriterHandle, nodes: Arc<NodeStore>) -> Self {
+        Self {
+            target,
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            writer,
+            nodes,
+            stored: HashMap::new(),
+            up: None,
+        }
+    }
+
+    /// Scrape every `interval_secs` until the collector stops
+    pub async fn run(mut self) {
+        let mut ticks = tokio::time::interval(Duration::from_secs(self.target.interval_secs));
+        // A slow scrape pushes the next one back instead of causing a burst
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticks.tick().await;
+            let points = self.scrape().await;
+            if let Err(e) = self.writer.insert_batch(points).await {
+                error!(
+                    "Failed to queue readings of node {}: {}",
+                    self.target.node_id, e
+                );
+            }
+        }
+    }
+
+    /// Scrape once, returning the readings not stored before followed by
+    /// `up` and `scrape_duration_seconds`
+    async fn scrape(&mut self) -> Vec<ProbeDataPoint> {
+        let started = Instant::now();
+        let timeout = Duration::from_secs(self.target.timeout_secs);
+        let result = tokio::time::timeout(timeout, self.fetch())
+            .await
+            .unwrap_or(Err(ScrapeError::Timeout(timeout)));
+        let elapsed = started.elapsed();
+
+        match (&result, self.up) {
+            (Err(e), Some(false)) => debug!("Scrape of node {} failed: {}", self.target.node_id, e),
+            (Err(e), _) => warn!(
+                "Scrape of node {} at {} failed: {}",
+                self.target.node_id, self.target.url, e
+            ),
+            (Ok(_), Some(false)) => info!("Node {} can be scraped again", self.target.node_id),
+            (Ok(_), _) => {}
+        }
+        self.up = Some(result.is_ok());
+
+        let up = result.is_ok();
+        let mut points = match result {
+            Ok(points) => fresh_points(&mut self.stored, points),
+            Err(_) => Vec::new(),
+        };
+
+        let now = timestamp::now_millis();
+        points.push(self.point(&SCRAPE_UP, ProbeValue::Integer(i64::from(up)), now));
+        points.push(self.point(
+            &SCRAPE_DURATION_SECONDS,
+            ProbeValue::Float(elapsed.as_secs_f64()),
+            now,
+        ));
+        points
+    }
+
+    async fn fetch(&self) -> Result<Vec<ProbeDataPoint>, ScrapeError> {
+        match self.target.format {
+            ScrapeFormat::Ferrview => self.fetch_readings().await,
+            ScrapeFormat::Prometheus => self.fetch_samples().await,
+        }
+    }
+
+    /// The node's readings, checked to be for the node it should be
+    async fn fetch_readings(&self) -> Result<Vec<ProbeDataPoint>, ScrapeError> {
+        let url = format!("{}/api/v1/readings", self.target.url.trim_end_matches('/'));
+        let (_, body) = self.get(&url, "application/json").await?;
+        let envelope: BatchEnvelope =
+            serde_json::from_slice(&body).map_err(|e| ScrapeError::Invalid(e.to_string()))?;
+
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&envelope.schema_version) {
+            return Err(ScrapeError::Invalid(format!(
+                "unsupported schema version {}",
+                envelope.schema_version
+            )));
+        }
+        if envelope.node_id != self.target.node_id {
+            return Err(ScrapeError::Invalid(format!(
+                "readings are for node {}",
+                envelope.node_id
+            )));
+        }
+        if let Some(reason) = validate_tags(&envelope.tags) {
+            return Err(ScrapeError::Invalid(reason));
+        }
+
+        if let Err(e) = self
+            .nodes
+            .update(&envelope.node_id, &envelope.agent_version, &envelope.tags)
+            .await
+        {
+            error!(
+                "Failed to update metadata of node {}: {}",
+                envelope.node_id, e
+            );
+        }
+        Ok(envelope.into_points())
+    }
+
+    /// The samples of a Prometheus endpoint, stamped with the scrape time
+    /// unless they carry their own
+    async fn fetch_samples(&self) -> Result<Vec<ProbeDataPoint>, ScrapeError> {
+        let (content_type, body) = self.get(&self.target.url, prometheus::ACCEPT).await?;
+        let text = std::str::from_utf8(&body)
+            .map_err(|_| ScrapeError::Invalid("metrics are not UTF-8".to_string()))?;
+        let samples = prometheus::parse(text, prometheus::is_openmetrics(&content_type))
+            .map_err(ScrapeError::Invalid)?;
+
+        let now = timestamp::now_millis();
+        Ok(samples
+            .into_iter()
+            .map(|sample| ProbeDataPoint {
+                node_id: self.target.node_id.clone(),
+                timestamp: sample.timestamp.unwrap_or(now),
+                probe_type: prometheus::PROBE_TYPE.to_string(),
+                probe_name: sample.name,
+                probe_value: match sample.value {
+                    prometheus::Value::Integer(v) => ProbeValue::Integer(v),
+                    prometheus::Value::Float(v) => ProbeValue::Float(v),
+                },
+                labels: sample.labels,
+            })
+            .collect())
+    }
+
+    /// GET `url`, returning the response's content type and body
+    async fn get(&self, url: &str, accept: &str) -> Result<(String, Bytes), ScrapeError> {
+        let request = Request::get(url)
+            .header(ACCEPT, accept)
+            .body(Empty::new())
+            .map_err(|e| ScrapeError::Http(e.to_string()))?;
+
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| ScrapeError::Http(error_chain(&e)))?;
+        if response.status() != StatusCode::OK {
+            return Err(ScrapeError::Status(response.status()));
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let body = Limited::new(response.into_body(), max_request_size())
+            .collect()
+            .await
+            .map_err(|e| ScrapeError::Http(e.to_string()))?
+            .to_bytes();
+        Ok((content_type, body))
+    }
+
+    fn point(&self, def: &MetricDef, value: ProbeValue, timestamp: i64) -> ProbeDataPoint {
+        ProbeDataPoint {
+            node_id: self.target.node_id.clone(),
+            timestamp,
+            probe_type: def.probe_type.to_string(),
+            probe_name: def.name.to_string(),
+            probe_value: value,
+            labels: BTreeMap::new(),
+        }
+    }
+}
+
+/// The points newer than what `stored` has for their series, updating it.
+/// Series missing from `points` are forgotten.
+fn fresh_points(
+    stored: &mut HashMap<SeriesKey, i64>,
+    points: Vec<ProbeDataPoint>,
+) -> Vec<ProbeDataPoint> {
+    let mut latest = HashMap::with_capacity(points.len());
+    let fresh = points
+        .into_iter()
+        .filter(|point| {
+            let key = (
+                point.probe_type.clone(),
+                point.probe_name.clone(),
+                point.labels.clone(),
+            );
+            let is_new = stored.get(&key).is_none_or(|&ts| point.timestamp > ts);
+            latest.insert(key, point.timestamp);
+            is_new
+        })
+        .collect();
+    *stored = latest;
+    fresh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(name: &str, timestamp: i64, mount: &str) -> ProbeDataPoint {
+        ProbeDataPoint {
+            node_id: "web-01".to_string(),
+            timestamp,
+            probe_type: "sysinfo".to_string(),
+            probe_name: name.to_string(),
+            probe_value: ProbeValue::Integer(1),
+            labels: BTreeMap::from([("mount".to_string(), mount.to_string())]),
+        }
+    }
+
+    #[test]
+    fn test_fresh_points() {
+        let mut stored = HashMap::new();
+        let first = vec![point("disk", 1000, "/"), point("disk", 1000, "/var")];
+        assert_eq!(fresh_points(&mut stored, first.clone()), first);
+
+        // Scraped again before the node collected: nothing new
+        assert!(fresh_points(&mut stored, first).is_empty());
+
+        let second = vec![point("disk", 2000, "/"), point("disk", 1000, "/var")];
+        assert_eq!(
+            fresh_points(&mut stored, second),
+            vec![point("disk", 2000, "/")]
+        );
+
+        // A series that went away and came back is new again
+        fresh_points(&mut stored, vec![point("disk", 2000, "/")]);
+        assert_eq!(
+            fresh_points(&mut stored, vec![point("disk", 1000, "/var")]),
+            vec![point("disk", 1000, "/var")]
+        );
+    }
+}
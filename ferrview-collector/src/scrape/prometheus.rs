@@ -0,0 +1,318 @@
+// src/scrape/prometheus.rs
+
+//! Parser for the Prometheus text exposition format and OpenMetrics.
+//!
+//! Every sample line becomes one series, so the buckets, `_sum` and
+//! `_count` of a histogram and the quantiles of a summary are stored as
+//! they are exposed, with `le` and `quantile` kept as labels.
+
+use std::collections::BTreeMap;
+
+/// probe_type of the readings scraped from Prometheus endpoints
+pub const PROBE_TYPE: &str = "prometheus";
+
+/// What the collector asks for: OpenMetrics, or the classic text format
+pub const ACCEPT: &str =
+    "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5";
+
+/// A sample line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: Value,
+    /// Unix epoch milliseconds, if the endpoint gave one
+    pub timestamp: Option<i64>,
+}
+
+/// Counts that are written as integers stay integers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+}
+
+/// Whether a response in `content_type` is OpenMetrics, whose timestamps
+/// are in seconds rather than milliseconds
+pub fn is_openmetrics(content_type: &str) -> bool {
+    content_type
+        .trim_start()
+        .starts_with("application/openmetrics-text")
+}
+
+/// Parse an exposition, failing on the first malformed line. Samples that
+/// are NaN or infinite are left out, as they cannot be charted.
+pub fn parse(text: &str, openmetrics: bool) -> Result<Vec<Sample>, String> {
+    let mut samples = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line == "# EOF" {
+            break;
+        }
+        // HELP, TYPE and UNIT add nothing the samples do not say
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let sample =
+            parse_sample(line, openmetrics).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        let finite = match sample.value {
+            Value::Integer(_) => true,
+            Value::Float(v) => v.is_finite(),
+        };
+        if finite {
+            samples.push(sample);
+        }
+    }
+
+    Ok(samples)
+}
+
+/// `name{label="value",...} value [timestamp] [# exemplar]`
+fn parse_sample(line: &str, openmetrics: bool) -> Result<Sample, String> {
+    let name_len = line
+        .char_indices()
+        .find(|&(i, c)| !is_name_char(c, i == 0))
+        .map_or(line.len(), |(i, _)| i);
+    if name_len == 0 {
+        return Err("expected a metric name".to_string());
+    }
+    let name = line[..name_len].to_string();
+
+    let mut rest = &line[name_len..];
+    let labels = match rest.strip_prefix('{') {
+        Some(after) => {
+            let (labels, after) = parse_labels(after)?;
+            rest = after;
+            labels
+        }
+        None => BTreeMap::new(),
+    };
+
+    // An OpenMetrics exemplar follows the sample after " # "
+    let rest = rest.split(" # ").next().unwrap_or_default();
+    let mut fields = rest.split_whitespace();
+    let value = fields
+        .next()
+        .ok_or_else(|| format!("{} has no value", name))?;
+    let value = parse_value(value).ok_or_else(|| format!("invalid value {}", value))?;
+    let timestamp = fields
+        .next()
+        .map(|ts| {
+            parse_timestamp(ts, openmetrics).ok_or_else(|| format!("invalid timestamp {}", ts))
+        })
+        .transpose()?;
+    if fields.next().is_some() {
+        return Err("unexpected text after the timestamp".to_string());
+    }
+
+    Ok(Sample {
+        name,
+        labels,
+        value,
+        timestamp,
+    })
+}
+
+/// The labels up to the closing brace, and what follows it
+fn parse_labels(mut rest: &str) -> Result<(BTreeMap<String, String>, &str), String> {
+    let mut labels = BTreeMap::new();
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix('}') {
+            return Ok((labels, after));
+        }
+
+        let name_len = rest
+            .char_indices()
+            .find(|&(i, c)| !is_label_char(c, i == 0))
+            .map_or(rest.len(), |(i, _)| i);
+        if name_len == 0 {
+            return Err("expected a label name".to_string());
+        }
+        let name = &rest[..name_len];
+
+        rest = rest[name_len..].trim_start();
+        rest = rest
+            .strip_prefix('=')
+            .ok_or_else(|| format!("expected = after label {}", name))?
+            .trim_start();
+        rest = rest
+            .strip_prefix('"')
+            .ok_or_else(|| format!("expected a quoted value for label {}", name))?;
+
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '"')) => break i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, c @ ('\\' | '"'))) => value.push(c),
+                    Some((_, c)) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => return Err(format!("unterminated value for label {}", name)),
+                },
+                Some((_, c)) => value.push(c),
+                None => return Err(format!("unterminated value for label {}", name)),
+            }
+        };
+        labels.insert(name.to_string(), value);
+
+        rest = rest[end + 1..].trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after;
+        } else if !rest.starts_with('}') {
+            return Err(format!("expected , or }} after label {}", name));
+        }
+    }
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    if let Ok(v) = value.parse::<i64>() {
+        return Some(Value::Integer(v));
+    }
+    match value {
+        "NaN" => Some(Value::Float(f64::NAN)),
+        "+Inf" | "Inf" => Some(Value::Float(f64::INFINITY)),
+        "-Inf" => Some(Value::Float(f64::NEG_INFINITY)),
+        // Rust accepts spellings such as "infinity" the formats do not
+        _ if value
+            .bytes()
+            .any(|b| b.is_ascii_alphabetic() && b != b'e' && b != b'E') =>
+        {
+            None
+        }
+        _ => value.parse().ok().map(Value::Float),
+    }
+}
+
+/// Milliseconds in the Prometheus text format, seconds in OpenMetrics
+fn parse_timestamp(timestamp: &str, openmetrics: bool) -> Option<i64> {
+    if openmetrics {
+        let seconds: f64 = timestamp.parse().ok()?;
+        seconds
+            .is_finite()
+            .then(|| (seconds * 1000.0).round() as i64)
+    } else {
+        timestamp.parse().ok()
+    }
+}
+
+fn is_name_char(c: char, first: bool) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == ':' || (!first && c.is_ascii_digit())
+}
+
+fn is_label_char(c: char, first: bool) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || (!first && c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_text_format() {
+        let text = r#"
+# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{method="post",code="400"}    3 1395066363000
+
+# Escapes in label values
+msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\""} 1.458255915e9
+
+metric_without_timestamp_and_labels 12.47
+something_weird{problem="division by zero"} +Inf -3982045
+
+# TYPE http_request_duration_seconds histogram
+http_request_duration_seconds_bucket{le="0.05"} 24054
+http_request_duration_seconds_bucket{le="+Inf"} 144320
+http_request_duration_seconds_sum 53423
+http_request_duration_seconds_count 144320
+
+# TYPE rpc_duration_seconds summary
+rpc_duration_seconds{quantile="0.99"} 76656
+rpc_duration_seconds{quantile="0.5",} NaN
+rpc_duration_seconds_sum 1.7560473e+07
+"#;
+        let samples = parse(text, false).unwrap();
+        assert_eq!(samples.len(), 10);
+
+        assert_eq!(samples[0].name, "http_requests_total");
+        assert_eq!(
+            samples[0].labels,
+            labels(&[("method", "post"), ("code", "200")])
+        );
+        assert_eq!(samples[0].value, Value::Integer(1027));
+        assert_eq!(samples[0].timestamp, Some(1_395_066_363_000));
+
+        assert_eq!(
+            samples[2].labels,
+            labels(&[
+                ("path", r"C:\DIR\FILE.TXT"),
+                ("error", "Cannot find file:\n\"FILE.TXT\"")
+            ])
+        );
+        assert_eq!(samples[2].value, Value::Float(1.458255915e9));
+        assert_eq!(samples[3].value, Value::Float(12.47));
+        assert_eq!(samples[3].timestamp, None);
+
+        // The infinite sample is dropped, the +Inf bucket is a label
+        assert_eq!(samples[4].name, "http_request_duration_seconds_bucket");
+        assert_eq!(samples[5].labels, labels(&[("le", "+Inf")]));
+        assert_eq!(samples[5].value, Value::Integer(144_320));
+        assert_eq!(samples[7].name, "http_request_duration_seconds_count");
+
+        // So is the NaN quantile
+        assert_eq!(samples[8].labels, labels(&[("quantile", "0.99")]));
+        assert_eq!(samples[9].value, Value::Float(1.7560473e7));
+    }
+
+    #[test]
+    fn test_parse_openmetrics() {
+        assert!(is_openmetrics(
+            "application/openmetrics-text; version=1.0.0; charset=utf-8"
+        ));
+        assert!(!is_openmetrics("text/plain; version=0.0.4"));
+
+        let text = r#"# TYPE foo counter
+# UNIT foo seconds
+foo_total{a="b"} 17.0 1520879607.789 # {trace_id="oHg5SJYRHA0"} 9.8 1520879607.789
+foo_created{a="b"} 1520430000.123
+# EOF
+ignored 1
+"#;
+        let samples = parse(text, true).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].value, Value::Float(17.0));
+        assert_eq!(samples[0].timestamp, Some(1_520_879_607_789));
+        assert_eq!(samples[1].name, "foo_created");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("1abc 1\n", false).is_err());
+        assert!(parse("foo\n", false).is_err());
+        assert!(parse("foo bar\n", false).is_err());
+        assert!(parse("foo infinity\n", false).is_err());
+        assert!(parse("foo{a=\"b} 1\n", false).is_err());
+        assert!(parse("foo{a=b} 1\n", false).is_err());
+        assert!(parse("foo{a=\"b\" c=\"d\"} 1\n", false).is_err());
+        assert!(parse("foo 1 1.5\n", false).is_err());
+        assert!(parse("foo 1 2 3\n", false).is_err());
+
+        let error = parse("ok 1\nbad{\n", false).unwrap_err();
+        assert!(error.starts_with("line 2:"), "{}", error);
+    }
+}
@@ -0,0 +1,860 @@
+// ferrview-collector/src/store/auth.rs
+use ferrview_common::protocol::{RegistrationRequest, RegistrationResponse, RegistrationStatus};
+use ferrview_common::signing::{self, to_hex};
+use ferrview_common::timestamp::now_millis;
+use ring::digest::{SHA256, digest};
+use ring::rand::{SecureRandom, SystemRandom};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{Connection, Row};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::str::FromStr;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+use crate::store::errors::StoreError;
+
+/// File holding credentials, next to the daily data files
+pub const AUTH_DB_FILENAME: &str = "ferrview_auth.db";
+
+/// Prefix of every generated token, so a leaked one is easy to recognise
+const TOKEN_PREFIX: &str = "fvt_";
+const TOKEN_BYTES: usize = 24;
+
+/// Registrations awaiting a decision; further ones are refused so that
+/// anyone able to reach the collector cannot grow the table without bound
+const MAX_PENDING_REGISTRATIONS: i64 = 1000;
+
+/// How long after approval a node that has not used its credential yet can
+/// fetch it again, in case the response handing it out was lost
+const CREDENTIAL_PICKUP_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Only a SHA-256 hash of each token is kept; the token itself is shown
+/// once, when it is created. Signing secrets have to be kept as they are,
+/// since verifying an HMAC takes the key itself.
+///
+/// A registration keeps a hash of its claim. When it is approved, the new
+/// token and secret wait in `token` and `secret` until the node first
+/// authenticates with them, or for [`CREDENTIAL_PICKUP_MS`], and are
+/// cleared then.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token_hash TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL DEFAULT '',
+    created_at INTEGER NOT NULL,
+    revoked_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS api_token_nodes (
+    token_id INTEGER NOT NULL REFERENCES api_tokens(id),
+    node_id TEXT NOT NULL,
+    PRIMARY KEY (token_id, node_id)
+);
+
+CREATE TABLE IF NOT EXISTS node_secrets (
+    node_id TEXT PRIMARY KEY,
+    secret TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS node_registrations (
+    id TEXT PRIMARY KEY,
+    node_id TEXT NOT NULL,
+    hostname TEXT,
+    agent_version TEXT NOT NULL,
+    remote_addr TEXT NOT NULL,
+    claim_hash TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    requested_at INTEGER NOT NULL,
+    decided_at INTEGER,
+    token TEXT,
+    secret TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_registrations_node_id
+    ON node_registrations(node_id, requested_at);
+"#;
+
+/// Whether the node_id of a `node_registrations` row already has a signing
+/// secret or an active token
+const HAS_CREDENTIALS: &str = "EXISTS (SELECT 1 FROM node_secrets s
+        WHERE s.node_id = node_registrations.node_id)
+    OR EXISTS (SELECT 1 FROM api_token_nodes n JOIN api_tokens t ON t.id = n.token_id
+        WHERE n.node_id = node_registrations.node_id AND t.revoked_at IS NULL)";
+
+/// A token as listed by the CLI
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenInfo {
+    pub id: i64,
+    pub description: String,
+    pub node_ids: Vec<String>,
+    /// Unix epoch milliseconds
+    pub created_at: i64,
+    pub revoked_at: Option<i64>,
+}
+
+/// What a valid token allows its bearer to do
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenGrant {
+    pub token_id: i64,
+    pub node_ids: BTreeSet<String>,
+}
+
+impl TokenGrant {
+    pub fn permits(&self, node_id: &str) -> bool {
+        self.node_ids.contains(node_id)
+    }
+}
+
+/// A registration as listed by the CLI and the UI
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistrationInfo {
+    pub id: String,
+    pub node_id: String,
+    pub hostname: Option<String>,
+    pub agent_version: String,
+    pub remote_addr: String,
+    pub status: RegistrationStatus,
+    /// Unix epoch milliseconds
+    pub requested_at: i64,
+    pub decided_at: Option<i64>,
+    /// The node_id already has a token or signing secret, so the request
+    /// may come from someone else claiming to be that node
+    pub has_credentials: bool,
+}
+
+/// Outcome of [`AuthStore::approve_registration`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Approval {
+    Approved,
+    /// There is no pending registration with that id
+    NotPending,
+    /// The node_id already has credentials and replacing them was not asked for
+    HasCredentials,
+}
+
+/// Node credentials in `ferrview_auth.db`: bearer tokens with the node_ids
+/// each one may write, the secrets nodes sign their batches with, and
+/// registrations of nodes asking for both
+pub struct AuthStore {
+    conn: Mutex<SqliteConnection>,
+}
+
+impl AuthStore {
+    /// Open (or create) the token store in `data_dir`
+    pub async fn open(data_dir: &str) -> Result<Self, StoreError> {
+        std::fs::create_dir_all(data_dir)?;
+        Self::open_path(&Path::new(data_dir).join(AUTH_DB_FILENAME)).await
+    }
+
+    async fn open_path(db_path: &Path) -> Result<Self, StoreError> {
+        let db_url = format!("sqlite://{}", db_path.display());
+        debug!("Opening token store at: {}", db_url);
+
+        let options = SqliteConnectOptions::from_str(&db_url)?
+            .create_if_missing(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+
+        let mut conn = SqliteConnection::connect_with(&options).await?;
+        sqlx::query(SCHEMA).execute(&mut conn).await?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// A store that lives only as long as the value, for tests
+    #[cfg(test)]
+    pub async fn in_memory() -> Self {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        sqlx::query(SCHEMA).execute(&mut conn).await.unwrap();
+        Self {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    /// Create a token for `node_ids`, returning its id and the token itself
+    pub async fn create_token(
+        &self,
+        node_ids: &[String],
+        description: &str,
+    ) -> Result<(i64, String), StoreError> {
+        if node_ids.is_empty() {
+            return Err(StoreError::InvalidQuery(
+                "a token needs at least one node_id".to_string(),
+            ));
+        }
+
+        let mut conn = self.conn.lock().await;
+        let mut tx = conn.begin().await?;
+        let created = insert_token(&mut tx, node_ids, description).await?;
+        tx.commit().await?;
+
+        info!(
+            "Created token {} for node(s) {}",
+            created.0,
+            node_ids.join(", ")
+        );
+        Ok(created)
+    }
+
+    /// Every token, revoked ones included, oldest first
+    pub async fn list_tokens(&self) -> Result<Vec<TokenInfo>, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let rows = sqlx::query(
+            "SELECT t.id, t.description, t.created_at, t.revoked_at, n.node_id
+             FROM api_tokens t LEFT JOIN api_token_nodes n ON n.token_id = t.id
+             ORDER BY t.id, n.node_id",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut tokens: Vec<TokenInfo> = Vec::new();
+        for row in rows {
+            let id: i64 = row.try_get("id")?;
+            if tokens.last().is_none_or(|token| token.id != id) {
+                tokens.push(TokenInfo {
+                    id,
+                    description: row.try_get("description")?,
+                    node_ids: Vec::new(),
+                    created_at: row.try_get("created_at")?,
+                    revoked_at: row.try_get("revoked_at")?,
+                });
+            }
+            if let Some(node_id) = row.try_get::<Option<String>, _>("node_id")?
+                && let Some(token) = tokens.last_mut()
+            {
+                token.node_ids.push(node_id);
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Revoke a token; `false` if there is no such token or it was already revoked
+    pub async fn revoke_token(&self, id: i64) -> Result<bool, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let result =
+            sqlx::query("UPDATE api_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+                .bind(now_millis())
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Look up a presented token, `None` if it is unknown or revoked
+    pub async fn authorize_token(&self, token: &str) -> Result<Option<TokenGrant>, StoreError> {
+        let mut conn = self.conn.lock().await;
+
+        let Some(row) =
+            sqlx::query("SELECT id FROM api_tokens WHERE token_hash = ? AND revoked_at IS NULL")
+                .bind(hash_token(token))
+                .fetch_optional(&mut *conn)
+                .await?
+        else {
+            return Ok(None);
+        };
+        let token_id: i64 = row.try_get("id")?;
+
+        let node_ids = sqlx::query_scalar("SELECT node_id FROM api_token_nodes WHERE token_id = ?")
+            .bind(token_id)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+
+        // A node using the token of its registration has collected it
+        let collected = sqlx::query(
+            "UPDATE node_registrations SET token = NULL, secret = NULL WHERE token = ?",
+        )
+        .bind(token)
+        .execute(&mut *conn)
+        .await?;
+        if collected.rows_affected() > 0 {
+            info!(
+                "Node with token {} collected its registration credential",
+                token_id
+            );
+        }
+
+        Ok(Some(TokenGrant { token_id, node_ids }))
+    }
+
+    /// Generate a new signing secret for `node_id`, replacing any previous one
+    pub async fn rotate_node_secret(&self, node_id: &str) -> Result<String, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let secret = replace_secret(&mut conn, node_id).await?;
+
+        info!("Set signing secret for node {}", node_id);
+        Ok(secret)
+    }
+
+    /// The signing secret of `node_id`, `None` if it has none
+    pub async fn node_secret(&self, node_id: &str) -> Result<Option<String>, StoreError> {
+        let mut conn = self.conn.lock().await;
+        Ok(
+            sqlx::query_scalar("SELECT secret FROM node_secrets WHERE node_id = ?")
+                .bind(node_id)
+                .fetch_optional(&mut *conn)
+                .await?,
+        )
+    }
+
+    /// Nodes with a signing secret and when it was set (epoch milliseconds)
+    pub async fn list_node_secrets(&self) -> Result<Vec<(String, i64)>, StoreError> {
+        let mut conn = self.conn.lock().await;
+        Ok(
+            sqlx::query_as("SELECT node_id, created_at FROM node_secrets ORDER BY node_id")
+                .fetch_all(&mut *conn)
+                .await?,
+        )
+    }
+
+    /// Remove the signing secret of `node_id`; `false` if it had none
+    pub async fn remove_node_secret(&self, node_id: &str) -> Result<bool, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let result = sqlx::query("DELETE FROM node_secrets WHERE node_id = ?")
+            .bind(node_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record a registration request, returning its id and claim. `None`
+    /// if too many registrations are already waiting for a decision.
+    pub async fn create_registration(
+        &self,
+        request: &RegistrationRequest,
+        remote_addr: &str,
+    ) -> Result<Option<(String, String)>, StoreError> {
+        let mut conn = self.conn.lock().await;
+
+        let pending: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM node_registrations WHERE status = ?")
+                .bind(RegistrationStatus::Pending.as_str())
+                .fetch_one(&mut *conn)
+                .await?;
+        if pending >= MAX_PENDING_REGISTRATIONS {
+            return Ok(None);
+        }
+
+        let id = random_hex(8)?;
+        let claim = random_hex(TOKEN_BYTES)?;
+        sqlx::query(
+            "INSERT INTO node_registrations
+                (id, node_id, hostname, agent_version, remote_addr, claim_hash, requested_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(&request.node_id)
+        .bind(&request.hostname)
+        .bind(&request.agent_version)
+        .bind(remote_addr)
+        .bind(hash_token(&claim))
+        .bind(now_millis())
+        .execute(&mut *conn)
+        .await?;
+
+        info!(
+            "Node {} ({}) registered from {} as {}, awaiting approval",
+            request.node_id,
+            request.hostname.as_deref().unwrap_or("unknown host"),
+            remote_addr,
+            id
+        );
+        Ok(Some((id, claim)))
+    }
+
+    /// Status of a registration for the node holding its claim, `None` if
+    /// there is no such registration or the claim does not match. The
+    /// credential of an approved registration is handed out until the node
+    /// uses it or [`CREDENTIAL_PICKUP_MS`] have passed.
+    pub async fn poll_registration(
+        &self,
+        id: &str,
+        claim: &str,
+    ) -> Result<Option<RegistrationResponse>, StoreError> {
+        let mut conn = self.conn.lock().await;
+
+        let Some(row) = sqlx::query(
+            "SELECT status, decided_at, token, secret FROM node_registrations
+             WHERE id = ? AND claim_hash = ?",
+        )
+        .bind(id)
+        .bind(hash_token(claim))
+        .fetch_optional(&mut *conn)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let status = parse_status(row.try_get("status")?)?;
+        let decided_at: Option<i64> = row.try_get("decided_at")?;
+        let mut token: Option<String> = row.try_get("token")?;
+        let mut secret: Option<String> = row.try_get("secret")?;
+
+        let expired = decided_at.is_some_and(|at| now_millis() - at > CREDENTIAL_PICKUP_MS);
+        if (token.is_some() || secret.is_some()) && expired {
+            sqlx::query("UPDATE node_registrations SET token = NULL, secret = NULL WHERE id = ?")
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            info!(
+                "Credential of registration {} was not collected in time",
+                id
+            );
+            token = None;
+            secret = None;
+        } else if token.is_some() {
+            debug!("Handing out the credential of registration {}", id);
+        }
+
+        Ok(Some(RegistrationResponse {
+            registration_id: id.to_string(),
+            status,
+            claim: None,
+            token,
+            secret,
+        }))
+    }
+
+    /// Registrations, newest first; only those with `status` if given
+    pub async fn list_registrations(
+        &self,
+        status: Option<RegistrationStatus>,
+    ) -> Result<Vec<RegistrationInfo>, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let rows = sqlx::query(&format!(
+            "SELECT id, node_id, hostname, agent_version, remote_addr, status,
+                requested_at, decided_at, {} AS has_credentials
+             FROM node_registrations WHERE ?1 IS NULL OR status = ?1
+             ORDER BY requested_at DESC, id",
+            HAS_CREDENTIALS
+        ))
+        .bind(status.map(RegistrationStatus::as_str))
+        .fetch_all(&mut *conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(RegistrationInfo {
+                    id: row.try_get("id")?,
+                    node_id: row.try_get("node_id")?,
+                    hostname: row.try_get("hostname")?,
+                    agent_version: row.try_get("agent_version")?,
+                    remote_addr: row.try_get("remote_addr")?,
+                    status: parse_status(row.try_get("status")?)?,
+                    requested_at: row.try_get("requested_at")?,
+                    decided_at: row.try_get("decided_at")?,
+                    has_credentials: row.try_get("has_credentials")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Approve a pending registration, issuing the node a token and a
+    /// signing secret. A node_id that already has credentials is only
+    /// approved with `replace`, as its new secret replaces the one the
+    /// node holding them signs with.
+    pub async fn approve_registration(
+        &self,
+        id: &str,
+        replace: bool,
+    ) -> Result<Approval, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let mut tx = conn.begin().await?;
+
+        let Some(row) = sqlx::query(&format!(
+            "SELECT node_id, {} AS has_credentials FROM node_registrations
+             WHERE id = ? AND status = ?",
+            HAS_CREDENTIALS
+        ))
+        .bind(id)
+        .bind(RegistrationStatus::Pending.as_str())
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(Approval::NotPending);
+        };
+        let node_id: String = row.try_get("node_id")?;
+        if row.try_get("has_credentials")? && !replace {
+            return Ok(Approval::HasCredentials);
+        }
+
+        let (token_id, token) = insert_token(
+            &mut tx,
+            std::slice::from_ref(&node_id),
+            &format!("registration {}", id),
+        )
+        .await?;
+        let secret = replace_secret(&mut tx, &node_id).await?;
+
+        sqlx::query(
+            "UPDATE node_registrations SET status = ?, decided_at = ?, token = ?, secret = ?
+             WHERE id = ?",
+        )
+        .bind(RegistrationStatus::Approved.as_str())
+        .bind(now_millis())
+        .bind(&token)
+        .bind(&secret)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        info!(
+            "Approved registration {} for node {}, issued token {}",
+            id, node_id, token_id
+        );
+        Ok(Approval::Approved)
+    }
+
+    /// Reject a pending registration; `false` if there is no such pending registration
+    pub async fn reject_registration(&self, id: &str) -> Result<bool, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let result = sqlx::query(
+            "UPDATE node_registrations SET status = ?, decided_at = ? WHERE id = ? AND status = ?",
+        )
+        .bind(RegistrationStatus::Rejected.as_str())
+        .bind(now_millis())
+        .bind(id)
+        .bind(RegistrationStatus::Pending.as_str())
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            info!("Rejected registration {}", id);
+        }
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Status of the latest registration for `node_id`, `None` if it never registered
+    pub async fn registration_status(
+        &self,
+        node_id: &str,
+    ) -> Result<Option<RegistrationStatus>, StoreError> {
+        let mut conn = self.conn.lock().await;
+        let status: Option<String> = sqlx::query_scalar(
+            "SELECT status FROM node_registrations WHERE node_id = ?
+             ORDER BY requested_at DESC LIMIT 1",
+        )
+        .bind(node_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        status.map(parse_status).transpose()
+    }
+}
+
+async fn insert_token(
+    conn: &mut SqliteConnection,
+    node_ids: &[String],
+    description: &str,
+) -> Result<(i64, String), StoreError> {
+    let token = generate_token()?;
+
+    let id = sqlx::query(
+        "INSERT INTO api_tokens (token_hash, description, created_at) VALUES (?, ?, ?)",
+    )
+    .bind(hash_token(&token))
+    .bind(description)
+    .bind(now_millis())
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    for node_id in node_ids {
+        sqlx::query("INSERT OR IGNORE INTO api_token_nodes (token_id, node_id) VALUES (?, ?)")
+            .bind(id)
+            .bind(node_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok((id, token))
+}
+
+async fn replace_secret(conn: &mut SqliteConnection, node_id: &str) -> Result<String, StoreError> {
+    let secret = signing::generate_secret();
+    sqlx::query(
+        "INSERT INTO node_secrets (node_id, secret, created_at) VALUES (?, ?, ?)
+         ON CONFLICT (node_id) DO UPDATE SET secret = excluded.secret,
+             created_at = excluded.created_at",
+    )
+    .bind(node_id)
+    .bind(&secret)
+    .bind(now_millis())
+    .execute(&mut *conn)
+    .await?;
+    Ok(secret)
+}
+
+fn parse_status(status: String) -> Result<RegistrationStatus, StoreError> {
+    RegistrationStatus::parse(&status)
+        .ok_or_else(|| StoreError::InvalidQuery(format!("unknown registration status {}", status)))
+}
+
+fn generate_token() -> Result<String, StoreError> {
+    Ok(format!("{}{}", TOKEN_PREFIX, random_hex(TOKEN_BYTES)?))
+}
+
+fn random_hex(len: usize) -> Result<String, StoreError> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| StoreError::Io(std::io::Error::other("no system randomness available")))?;
+    Ok(to_hex(&bytes))
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(digest(&SHA256, token.as_bytes()).as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_and_authorize() {
+        let store = AuthStore::in_memory().await;
+        let (id, token) = store
+            .create_token(&["node-1".to_string(), "node-2".to_string()], "web")
+            .await
+            .unwrap();
+        assert!(token.starts_with(TOKEN_PREFIX));
+
+        let grant = store.authorize_token(&token).await.unwrap().unwrap();
+        assert_eq!(grant.token_id, id);
+        assert!(grant.permits("node-1"));
+        assert!(grant.permits("node-2"));
+        assert!(!grant.permits("node-3"));
+
+        assert!(
+            store
+                .authorize_token("fvt_unknown")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // The token itself is never stored
+        let mut conn = store.conn.lock().await;
+        let stored: String = sqlx::query_scalar("SELECT token_hash FROM api_tokens")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_ne!(stored, token);
+    }
+
+    #[tokio::test]
+    async fn test_revoke() {
+        let store = AuthStore::in_memory().await;
+        let (first, first_token) = store.create_token(&["a".to_string()], "").await.unwrap();
+        let (_, second_token) = store.create_token(&["b".to_string()], "").await.unwrap();
+
+        assert!(store.revoke_token(first).await.unwrap());
+        assert!(!store.revoke_token(first).await.unwrap());
+        assert!(!store.revoke_token(999).await.unwrap());
+
+        assert!(store.authorize_token(&first_token).await.unwrap().is_none());
+        assert!(
+            store
+                .authorize_token(&second_token)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        let tokens = store.list_tokens().await.unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].node_ids, vec!["a"]);
+        assert!(tokens[0].revoked_at.is_some());
+        assert!(tokens[1].revoked_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_node_secrets() {
+        let store = AuthStore::in_memory().await;
+        assert!(store.node_secret("node-1").await.unwrap().is_none());
+
+        let first = store.rotate_node_secret("node-1").await.unwrap();
+        assert_eq!(
+            store.node_secret("node-1").await.unwrap(),
+            Some(first.clone())
+        );
+
+        let second = store.rotate_node_secret("node-1").await.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(store.node_secret("node-1").await.unwrap(), Some(second));
+        assert_eq!(store.list_node_secrets().await.unwrap().len(), 1);
+
+        assert!(store.remove_node_secret("node-1").await.unwrap());
+        assert!(!store.remove_node_secret("node-1").await.unwrap());
+        assert!(store.node_secret("node-1").await.unwrap().is_none());
+    }
+
+    fn registration_request(node_id: &str) -> RegistrationRequest {
+        RegistrationRequest {
+            node_id: node_id.to_string(),
+            hostname: Some("web-01".to_string()),
+            agent_version: "0.6.0".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approved_registration_hands_out_credential_until_used() {
+        let store = AuthStore::in_memory().await;
+        let (id, claim) = store
+            .create_registration(&registration_request("node-1"), "10.0.0.5:4711")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let pending = store.poll_registration(&id, &claim).await.unwrap().unwrap();
+        assert_eq!(pending.status, RegistrationStatus::Pending);
+        assert!(pending.token.is_none());
+        assert_eq!(
+            store.registration_status("node-1").await.unwrap(),
+            Some(RegistrationStatus::Pending)
+        );
+
+        // Only the holder of the claim learns anything
+        assert!(
+            store
+                .poll_registration(&id, "guess")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        assert_eq!(
+            store.approve_registration(&id, false).await.unwrap(),
+            Approval::Approved
+        );
+        assert_eq!(
+            store.approve_registration(&id, false).await.unwrap(),
+            Approval::NotPending
+        );
+
+        let approved = store.poll_registration(&id, &claim).await.unwrap().unwrap();
+        assert_eq!(approved.status, RegistrationStatus::Approved);
+
+        // A response lost on the way can be asked for again
+        let again = store.poll_registration(&id, &claim).await.unwrap().unwrap();
+        assert_eq!(again.token, approved.token);
+        assert_eq!(again.secret, approved.secret);
+
+        let grant = store
+            .authorize_token(&approved.token.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(grant.permits("node-1"));
+        assert_eq!(store.node_secret("node-1").await.unwrap(), approved.secret);
+
+        // Once used, it is no longer handed out
+        let used = store.poll_registration(&id, &claim).await.unwrap().unwrap();
+        assert_eq!(used.status, RegistrationStatus::Approved);
+        assert!(used.token.is_none() && used.secret.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_uncollected_credential_expires() {
+        let store = AuthStore::in_memory().await;
+        let (id, claim) = store
+            .create_registration(&registration_request("node-1"), "10.0.0.5:4711")
+            .await
+            .unwrap()
+            .unwrap();
+        store.approve_registration(&id, false).await.unwrap();
+
+        sqlx::query("UPDATE node_registrations SET decided_at = ? WHERE id = ?")
+            .bind(now_millis() - CREDENTIAL_PICKUP_MS - 1)
+            .bind(&id)
+            .execute(&mut *store.conn.lock().await)
+            .await
+            .unwrap();
+
+        let expired = store.poll_registration(&id, &claim).await.unwrap().unwrap();
+        assert_eq!(expired.status, RegistrationStatus::Approved);
+        assert!(expired.token.is_none() && expired.secret.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_registration() {
+        let store = AuthStore::in_memory().await;
+        let (id, claim) = store
+            .create_registration(&registration_request("node-1"), "10.0.0.5:4711")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(store.reject_registration(&id).await.unwrap());
+        assert_eq!(
+            store.approve_registration(&id, false).await.unwrap(),
+            Approval::NotPending
+        );
+
+        let rejected = store.poll_registration(&id, &claim).await.unwrap().unwrap();
+        assert_eq!(rejected.status, RegistrationStatus::Rejected);
+        assert!(rejected.token.is_none());
+        assert!(store.node_secret("node-1").await.unwrap().is_none());
+
+        let listed = store
+            .list_registrations(Some(RegistrationStatus::Rejected))
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].hostname.as_deref(), Some("web-01"));
+        assert!(listed[0].decided_at.is_some());
+        assert!(
+            store
+                .list_registrations(Some(RegistrationStatus::Pending))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registration_for_node_with_credentials() {
+        let store = AuthStore::in_memory().await;
+        let secret = store.rotate_node_secret("node-1").await.unwrap();
+        let (id, _) = store
+            .create_registration(&registration_request("node-1"), "10.0.0.5:4711")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let listed = store.list_registrations(None).await.unwrap();
+        assert!(listed[0].has_credentials);
+
+        // The node already signing as node-1 keeps its secret
+        assert_eq!(
+            store.approve_registration(&id, false).await.unwrap(),
+            Approval::HasCredentials
+        );
+        assert_eq!(
+            store.node_secret("node-1").await.unwrap().as_deref(),
+            Some(secret.as_str())
+        );
+
+        assert_eq!(
+            store.approve_registration(&id, true).await.unwrap(),
+            Approval::Approved
+        );
+        assert_ne!(
+            store.node_secret("node-1").await.unwrap().as_deref(),
+            Some(secret.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_needs_node_ids() {
+        let store = AuthStore::in_memory().await;
+        assert!(store.create_token(&[], "").await.is_err());
+    }
+}
@@ -1,5 +1,6 @@
 // ferrview-collector/src/store/date_range.rs
 
+use ferrview_common::timestamp::to_millis;
 use time::{Date, Duration, Month, OffsetDateTime};
 
 use crate::store::errors::StoreError;
@@ -100,13 +101,13 @@ impl DateRange {
         dates
     }
 
-    /// Format timestamps for SQL queries (RFC3339)
-    pub fn start_time_str(&self) -> String {
-        format_timestamp(self.start_time)
+    /// Range bounds as epoch milliseconds, matching the `timestamp` column
+    pub fn start_millis(&self) -> i64 {
+        to_millis(self.start_time)
     }
 
-    pub fn end_time_str(&self) -> String {
-        format_timestamp(self.end_time)
+    pub fn end_millis(&self) -> i64 {
+        to_millis(self.end_time)
     }
 }
 
@@ -146,12 +147,6 @@ fn format_date(date: Date) -> String {
     )
 }
 
-/// Format timestamp in RFC3339 format for SQLite queries
-fn format_timestamp(dt: OffsetDateTime) -> String {
-    dt.format(&time::format_description::well_known::Rfc3339)
-        .unwrap_or_else(|_| String::from("1970-01-01T00:00:00Z"))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,6 +179,13 @@ mod tests {
         assert_eq!(dates, vec!["2024-12-01", "2024-12-02", "2024-12-03"]);
     }
 
+    #[test]
+    fn test_custom_range_millis() {
+        let range = DateRange::custom("2024-12-01", "2024-12-03").unwrap();
+        assert_eq!(range.start_millis(), 1_733_011_200_000);
+        assert_eq!(range.end_millis(), 1_733_270_400_000);
+    }
+
     #[test]
     fn test_custom_range_invalid_order() {
         let result = DateRange::custom("2024-12-10", "2024-12-01");
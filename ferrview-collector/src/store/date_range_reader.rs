@@ -2,11 +2,13 @@
 
 use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
 use sqlx::{Connection, Row};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::str::FromStr;
 use tracing::{debug, info, warn};
 
 use crate::store::date_range::DateRange;
+use crate::store::db::{self, Database};
 use crate::store::errors::StoreError;
 use crate::store::queries::MetricDataPoint;
 
@@ -52,24 +54,26 @@ impl DateRangeReader {
         dates
     }
 
-    /// Query metrics for a specific node across the date range
+    /// Query metrics for a specific node across the date range, keeping only
+    /// points whose labels contain every given key/value pair
     pub async fn query_node_metrics(
         &self,
         node_id: &str,
         metric_pattern: &str,
+        labels: &BTreeMap<String, String>,
         range: &DateRange,
     ) -> Result<Vec<MetricDataPoint>, StoreError> {
         let dates = range.dates();
-        let start_str = range.start_time_str();
-        let end_str = range.end_time_str();
+        let start = range.start_millis();
+        let end = range.end_millis();
 
         debug!(
             "Querying metrics for node {} with pattern '{}' across {} dates ({} to {})",
             node_id,
             metric_pattern,
             dates.len(),
-            start_str,
-            end_str
+            range.start_time,
+            range.end_time
         );
 
         let mut all_results = Vec::new();
@@ -83,7 +87,7 @@ impl DateRangeReader {
             }
 
             match self
-                .query_single_db(&db_path, node_id, metric_pattern, &start_str, &end_str)
+                .query_single_db(&db_path, node_id, metric_pattern, labels, start, end)
                 .await
             {
                 Ok(mut results) => {
@@ -99,7 +103,7 @@ impl DateRangeReader {
 
         // Results should already be sorted by timestamp within each db,
         // but we need to merge-sort across databases
-        all_results.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        all_results.sort_by_key(|m| m.timestamp);
 
         debug!(
             "Total results across all databases: {}",
@@ -115,8 +119,9 @@ impl DateRangeReader {
         db_path: &str,
         node_id: &str,
         metric_pattern: &str,
-        start_str: &str,
-        end_str: &str,
+        labels: &BTreeMap<String, String>,
+        start: i64,
+        end: i64,
     ) -> Result<Vec<MetricDataPoint>, StoreError> {
         let db_url = format!("sqlite://{}", db_path);
 
@@ -126,23 +131,38 @@ impl DateRangeReader {
 
         let mut conn = SqliteConnection::connect_with(&options).await?;
 
-        let rows = sqlx::query(
+        // Older day files may predate the current schema; upgrade them once
+        if db::schema_version(&mut conn).await? < db::SCHEMA_VERSION {
+            conn.close().await?;
+            Database::open(Path::new(db_path)).await?.close().await?;
+            conn = SqliteConnection::connect_with(&options).await?;
+        }
+
+        let sql = format!(
             r#"
-            SELECT node_id, timestamp, probe_type, probe_name, probe_value
+            SELECT node_id, timestamp, probe_type, probe_name,
+                   value_kind, value_int, value_real, value_text, labels
             FROM probe_data
-            WHERE node_id = ?1
-              AND probe_name LIKE ?2
-              AND timestamp >= ?3
-              AND timestamp <= ?4
+            WHERE node_id = ?
+              AND probe_name LIKE ?
+              AND timestamp >= ?
+              AND timestamp <= ?
+              {}
             ORDER BY timestamp ASC
             "#,
-        )
-        .bind(node_id)
-        .bind(metric_pattern)
-        .bind(start_str)
-        .bind(end_str)
-        .fetch_all(&mut conn)
-        .await?;
+            label_conditions(labels.len())
+        );
+
+        let mut query = sqlx::query(&sql)
+            .bind(node_id)
+            .bind(metric_pattern)
+            .bind(start)
+            .bind(end);
+        for (key, value) in labels {
+            query = query.bind(label_path(key)).bind(value);
+        }
+
+        let rows = query.fetch_all(&mut conn).await?;
 
         let results: Vec<MetricDataPoint> = rows
             .into_iter()
@@ -151,7 +171,13 @@ impl DateRangeReader {
                 timestamp: row.get("timestamp"),
                 probe_type: row.get("probe_type"),
                 probe_name: row.get("probe_name"),
-                probe_value: row.get("probe_value"),
+                probe_value: db::decode_value(
+                    row.get("value_kind"),
+                    row.get("value_int"),
+                    row.get("value_real"),
+                    row.get("value_text"),
+                ),
+                labels: db::decode_labels(row.get("labels")),
             })
             .collect();
 
@@ -161,6 +187,16 @@ impl DateRangeReader {
     }
 }
 
+/// SQL conditions matching `count` label key/value pairs, bound in order
+fn label_conditions(count: usize) -> String {
+    "AND json_extract(labels, ?) = ? ".repeat(count)
+}
+
+/// JSON path selecting a label key, quoted so keys may contain dots
+fn label_path(key: &str) -> String {
+    format!("$.\"{}\"", key.replace('"', "\\\""))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,4 +206,11 @@ mod tests {
         let reader = DateRangeReader::new("/tmp/test");
         assert_eq!(reader.data_dir, "/tmp/test");
     }
+
+    #[test]
+    fn test_label_conditions() {
+        assert_eq!(label_conditions(0), "");
+        assert_eq!(label_conditions(2).matches("json_extract").count(), 2);
+        assert_eq!(label_path("mount"), "$.\"mount\"");
+    }
 }
@@ -1,21 +1,37 @@
 // ferrview-collector/src/store/db.rs
+use ferrview_common::ProbeValue;
 use sqlx::Connection;
 use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::str::FromStr;
 use tracing::{debug, info};
 
 use crate::store::errors::StoreError;
 
+/// Current schema version, tracked in `PRAGMA user_version`
+pub const SCHEMA_VERSION: i64 = 3;
+
+/// Schema for freshly created databases
+///
+/// `timestamp` is Unix epoch milliseconds (UTC). Values are stored in typed
+/// columns selected by `value_kind`: `int` and `bool` use `value_int`,
+/// `float` uses `value_real`, `text` uses `value_text`. `labels` holds the
+/// point's labels as a JSON object with sorted keys, so equal label sets
+/// compare equal as text.
 const SCHEMA: &str = r#"
 CREATE TABLE IF NOT EXISTS probe_data (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     node_id TEXT NOT NULL,
-    timestamp TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
     probe_type TEXT NOT NULL,
     probe_name TEXT NOT NULL,
-    probe_value TEXT NOT NULL,
-    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+    value_kind TEXT NOT NULL,
+    value_int INTEGER,
+    value_real REAL,
+    value_text TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+    labels TEXT NOT NULL DEFAULT '{}'
 );
 
 CREATE INDEX IF NOT EXISTS idx_probe_data_node_timestamp
@@ -23,8 +39,174 @@ CREATE INDEX IF NOT EXISTS idx_probe_data_node_timestamp
 
 CREATE INDEX IF NOT EXISTS idx_probe_data_probe_type
     ON probe_data(probe_type);
+
+CREATE INDEX IF NOT EXISTS idx_probe_data_node_name_timestamp
+    ON probe_data(node_id, probe_name, timestamp);
 "#;
 
+/// Migrations for existing databases, indexed by the version they upgrade from
+const MIGRATIONS: &[&str] = &[
+    // v0 -> v1: split the TEXT probe_value column into typed value columns
+    r#"
+    ALTER TABLE probe_data RENAME TO probe_data_v0;
+    DROP INDEX IF EXISTS idx_probe_data_node_timestamp;
+    DROP INDEX IF EXISTS idx_probe_data_probe_type;
+
+    CREATE TABLE probe_data (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        node_id TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        probe_type TEXT NOT NULL,
+        probe_name TEXT NOT NULL,
+        value_kind TEXT NOT NULL,
+        value_int INTEGER,
+        value_real REAL,
+        value_text TEXT,
+        created_at TEXT DEFAULT CURRENT_TIMESTAMP
+    );
+
+    INSERT INTO probe_data
+        (id, node_id, timestamp, probe_type, probe_name,
+         value_kind, value_int, value_real, value_text, created_at)
+    SELECT id, node_id, timestamp, probe_type, probe_name, kind,
+        CASE
+            WHEN kind = 'int' THEN CAST(probe_value AS INTEGER)
+            WHEN kind = 'bool' THEN probe_value = 'true'
+        END,
+        CASE WHEN kind = 'float' THEN CAST(probe_value AS REAL) END,
+        CASE WHEN kind = 'text' THEN probe_value END,
+        created_at
+    FROM (
+        -- Only values that read back unchanged become numbers, so text
+        -- such as '24.10' or '6.8.0' is kept as it was sent
+        SELECT *,
+            CASE
+                WHEN probe_value = CAST(CAST(probe_value AS INTEGER) AS TEXT) THEN 'int'
+                WHEN probe_value = CAST(CAST(probe_value AS REAL) AS TEXT) THEN 'float'
+                WHEN probe_value IN ('true', 'false') THEN 'bool'
+                ELSE 'text'
+            END AS kind
+        FROM probe_data_v0
+    );
+
+    DROP TABLE probe_data_v0;
+
+    CREATE INDEX idx_probe_data_node_timestamp ON probe_data(node_id, timestamp);
+    CREATE INDEX idx_probe_data_probe_type ON probe_data(probe_type);
+    "#,
+    // v1 -> v2: move the object index out of probe_name into labels,
+    // e.g. disk_0_usage_percent -> disk_usage_percent {"mount": "/var"}
+    r#"
+    ALTER TABLE probe_data ADD COLUMN labels TEXT NOT NULL DEFAULT '{}';
+
+    CREATE TEMP TABLE legacy_indexed AS
+    SELECT id, node_id, timestamp, prefix,
+        substr(tail, 1, instr(tail, '_') - 1) AS idx,
+        substr(tail, instr(tail, '_') + 1) AS metric
+    FROM (
+        SELECT id, node_id, timestamp, prefix,
+            substr(probe_name, length(prefix) + 1) AS tail
+        FROM (
+            SELECT id, node_id, timestamp, probe_name,
+                CASE
+                    WHEN probe_name GLOB 'cpu_core_[0-9]*_*' THEN 'cpu_core_'
+                    WHEN probe_name GLOB 'disk_[0-9]*_*' THEN 'disk_'
+                    WHEN probe_name GLOB 'network_interface_[0-9]*_*' THEN 'network_interface_'
+                    WHEN probe_name GLOB 'temperature_sensor_[0-9]*_*' THEN 'temperature_sensor_'
+                END AS prefix
+            FROM probe_data
+        )
+        WHERE prefix IS NOT NULL
+    );
+
+    -- The object name was sent as its own metric next to the indexed values
+    CREATE TEMP TABLE legacy_identity AS
+    SELECT l.node_id, l.timestamp, l.prefix, l.idx, p.value_text AS identity
+    FROM legacy_indexed l JOIN probe_data p ON p.id = l.id
+    WHERE (l.prefix = 'disk_' AND l.metric = 'mount_point')
+       OR (l.prefix = 'network_interface_' AND l.metric = 'name')
+       OR (l.prefix = 'temperature_sensor_' AND l.metric = 'label');
+
+    CREATE INDEX temp.idx_legacy_identity
+        ON legacy_identity(node_id, timestamp, prefix, idx);
+
+    UPDATE probe_data SET
+        labels = (
+            SELECT json_object(
+                CASE l.prefix
+                    WHEN 'cpu_core_' THEN 'core'
+                    WHEN 'disk_' THEN 'mount'
+                    WHEN 'network_interface_' THEN 'iface'
+                    ELSE 'sensor'
+                END,
+                COALESCE(
+                    (SELECT i.identity FROM legacy_identity i
+                     WHERE i.node_id = l.node_id AND i.timestamp = l.timestamp
+                       AND i.prefix = l.prefix AND i.idx = l.idx),
+                    l.idx))
+            FROM legacy_indexed l WHERE l.id = probe_data.id),
+        probe_name = (
+            SELECT CASE l.prefix
+                WHEN 'network_interface_' THEN 'network_' || l.metric
+                WHEN 'temperature_sensor_' THEN 'temperature_' || l.metric
+                ELSE l.prefix || l.metric
+            END
+            FROM legacy_indexed l WHERE l.id = probe_data.id)
+    WHERE id IN (SELECT id FROM legacy_indexed);
+
+    -- Identity metrics are now carried by the labels themselves
+    DELETE FROM probe_data
+    WHERE probe_name IN ('disk_mount_point', 'network_name', 'temperature_label');
+
+    DROP TABLE legacy_identity;
+    DROP TABLE legacy_indexed;
+
+    CREATE INDEX idx_probe_data_node_name_timestamp
+        ON probe_data(node_id, probe_name, timestamp);
+    "#,
+    // v2 -> v3: store timestamps as INTEGER epoch milliseconds instead of
+    // RFC 3339 text; rows with unparseable timestamps are dropped
+    r#"
+    ALTER TABLE probe_data RENAME TO probe_data_v2;
+    DROP INDEX IF EXISTS idx_probe_data_node_timestamp;
+    DROP INDEX IF EXISTS idx_probe_data_probe_type;
+    DROP INDEX IF EXISTS idx_probe_data_node_name_timestamp;
+
+    CREATE TABLE probe_data (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        node_id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        probe_type TEXT NOT NULL,
+        probe_name TEXT NOT NULL,
+        value_kind TEXT NOT NULL,
+        value_int INTEGER,
+        value_real REAL,
+        value_text TEXT,
+        created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+        labels TEXT NOT NULL DEFAULT '{}'
+    );
+
+    INSERT INTO probe_data
+        (id, node_id, timestamp, probe_type, probe_name,
+         value_kind, value_int, value_real, value_text, created_at, labels)
+    SELECT id, node_id, millis, probe_type, probe_name,
+        value_kind, value_int, value_real, value_text, created_at, labels
+    FROM (
+        SELECT *,
+            CAST(ROUND((julianday(timestamp) - 2440587.5) * 86400000.0) AS INTEGER) AS millis
+        FROM probe_data_v2
+    )
+    WHERE millis IS NOT NULL;
+
+    DROP TABLE probe_data_v2;
+
+    CREATE INDEX idx_probe_data_node_timestamp ON probe_data(node_id, timestamp);
+    CREATE INDEX idx_probe_data_probe_type ON probe_data(probe_type);
+    CREATE INDEX idx_probe_data_node_name_timestamp
+        ON probe_data(node_id, probe_name, timestamp);
+    "#,
+];
+
 pub struct Database {
     conn: SqliteConnection,
 }
@@ -37,6 +219,16 @@ impl Database {
 
         let db_filename = format!("ferrview_{}.db", date);
         let db_path = Path::new(data_dir).join(&db_filename);
+
+        let db = Self::open(&db_path).await?;
+
+        info!("Database initialized successfully: {}", db_filename);
+
+        Ok(db)
+    }
+
+    /// Open (or create) a database file and bring its schema up to date
+    pub async fn open(db_path: &Path) -> Result<Self, StoreError> {
         let db_url = format!("sqlite://{}", db_path.display());
 
         info!("Initializing database at: {}", db_url);
@@ -48,9 +240,7 @@ impl Database {
         let mut conn = SqliteConnection::connect_with(&options).await?;
 
         debug!("Running database migrations");
-        sqlx::query(SCHEMA).execute(&mut conn).await?;
-
-        info!("Database initialized successfully: {}", db_filename);
+        migrate(&mut conn).await?;
 
         Ok(Self { conn })
     }
@@ -66,3 +256,309 @@ impl Database {
         Ok(())
     }
 }
+
+/// Read the schema version of an open database
+pub async fn schema_version(conn: &mut SqliteConnection) -> Result<i64, StoreError> {
+    let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+        .fetch_one(conn)
+        .await?;
+    Ok(version)
+}
+
+/// Create the schema, or upgrade an existing one to `SCHEMA_VERSION`
+async fn migrate(conn: &mut SqliteConnection) -> Result<(), StoreError> {
+    let mut version = schema_version(conn).await?;
+
+    if version == 0 {
+        let (tables,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'probe_data'",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if tables == 0 {
+            sqlx::query(SCHEMA).execute(&mut *conn).await?;
+            set_schema_version(conn, SCHEMA_VERSION).await?;
+            return Ok(());
+        }
+    }
+
+    while version < SCHEMA_VERSION {
+        info!("Migrating database schema v{} -> v{}", version, version + 1);
+
+        let mut tx = conn.begin().await?;
+        sqlx::query(MIGRATIONS[version as usize])
+            .execute(&mut *tx)
+            .await?;
+        set_schema_version(&mut tx, version + 1).await?;
+        tx.commit().await?;
+
+        version += 1;
+    }
+
+    Ok(())
+}
+
+async fn set_schema_version(conn: &mut SqliteConnection, version: i64) -> Result<(), StoreError> {
+    // PRAGMA does not accept bound parameters
+    sqlx::query(&format!("PRAGMA user_version = {}", version))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Column values for storing a `ProbeValue`
+pub struct ValueColumns<'a> {
+    pub kind: &'static str,
+    pub int: Option<i64>,
+    pub real: Option<f64>,
+    pub text: Option<&'a str>,
+}
+
+/// Split a value into its storage columns
+pub fn encode_value(value: &ProbeValue) -> ValueColumns<'_> {
+    let kind = value.kind();
+    match value {
+        ProbeValue::Integer(i) => ValueColumns {
+            kind,
+            int: Some(*i),
+            real: None,
+            text: None,
+        },
+        ProbeValue::Boolean(b) => ValueColumns {
+            kind,
+            int: Some(i64::from(*b)),
+            real: None,
+            text: None,
+        },
+        ProbeValue::Float(f) => ValueColumns {
+            kind,
+            int: None,
+            real: Some(*f),
+            text: None,
+        },
+        ProbeValue::Text(s) => ValueColumns {
+            kind,
+            int: None,
+            real: None,
+            text: Some(s),
+        },
+    }
+}
+
+/// Serialize labels for the `labels` column
+pub fn encode_labels(labels: &BTreeMap<String, String>) -> String {
+    serde_json::to_string(labels).unwrap_or_else(|_| String::from("{}"))
+}
+
+/// Parse the `labels` column, treating malformed content as no labels
+pub fn decode_labels(labels: &str) -> BTreeMap<String, String> {
+    serde_json::from_str(labels).unwrap_or_default()
+}
+
+/// Rebuild a value from its storage columns
+pub fn decode_value(
+    kind: &str,
+    int: Option<i64>,
+    real: Option<f64>,
+    text: Option<String>,
+) -> ProbeValue {
+    match kind {
+        "int" => ProbeValue::Integer(int.unwrap_or_default()),
+        "bool" => ProbeValue::Boolean(int.unwrap_or_default() != 0),
+        "float" => ProbeValue::Float(real.unwrap_or_default()),
+        _ => ProbeValue::Text(text.unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Row;
+
+    async fn memory_conn() -> SqliteConnection {
+        SqliteConnection::connect("sqlite::memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_gets_current_schema() {
+        let mut conn = memory_conn().await;
+        migrate(&mut conn).await.unwrap();
+        assert_eq!(schema_version(&mut conn).await.unwrap(), SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_text_values() {
+        let mut conn = memory_conn().await;
+        sqlx::query(
+            r#"
+            CREATE TABLE probe_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                node_id TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                probe_type TEXT NOT NULL,
+                probe_name TEXT NOT NULL,
+                probe_value TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO probe_data (node_id, timestamp, probe_type, probe_name, probe_value) VALUES
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'memory_total_bytes', '16777216'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'cpu_core_0_usage_percent', '12.5'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'system_os_name', 'Linux'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'system_os_version', '24.10'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'system_kernel_version', '6.8.0'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'system_build', '1-2'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'swap_enabled', 'true'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'smt_enabled', 'false');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        migrate(&mut conn).await.unwrap();
+        assert_eq!(schema_version(&mut conn).await.unwrap(), SCHEMA_VERSION);
+
+        let rows = sqlx::query(
+            "SELECT timestamp, value_kind, value_int, value_real, value_text
+             FROM probe_data ORDER BY id",
+        )
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+
+        for row in &rows {
+            assert_eq!(row.get::<i64, _>("timestamp"), 1_733_652_000_000);
+        }
+
+        let values: Vec<ProbeValue> = rows
+            .iter()
+            .map(|row| {
+                decode_value(
+                    row.get("value_kind"),
+                    row.get("value_int"),
+                    row.get("value_real"),
+                    row.get("value_text"),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                ProbeValue::Integer(16777216),
+                ProbeValue::Float(12.5),
+                ProbeValue::from("Linux"),
+                ProbeValue::from("24.10"),
+                ProbeValue::from("6.8.0"),
+                ProbeValue::from("1-2"),
+                ProbeValue::Boolean(true),
+                ProbeValue::Boolean(false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrate_text_timestamps() {
+        let mut conn = memory_conn().await;
+        sqlx::query(
+            r#"
+            CREATE TABLE probe_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                node_id TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                probe_type TEXT NOT NULL,
+                probe_name TEXT NOT NULL,
+                probe_value TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO probe_data (node_id, timestamp, probe_type, probe_name, probe_value) VALUES
+                ('n', '2024-12-08T10:00:00.250Z', 'procfs', 'forks_total', '1'),
+                ('n', '2024-12-08T11:00:00+01:00', 'procfs', 'forks_total', '2'),
+                ('n', 'not a timestamp', 'procfs', 'forks_total', '3');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        migrate(&mut conn).await.unwrap();
+
+        let timestamps: Vec<i64> =
+            sqlx::query_scalar("SELECT timestamp FROM probe_data ORDER BY id")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+
+        assert_eq!(timestamps, vec![1_733_652_000_250, 1_733_652_000_000]);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_indexed_names_to_labels() {
+        let mut conn = memory_conn().await;
+        sqlx::query(
+            r#"
+            CREATE TABLE probe_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                node_id TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                probe_type TEXT NOT NULL,
+                probe_name TEXT NOT NULL,
+                probe_value TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO probe_data (node_id, timestamp, probe_type, probe_name, probe_value) VALUES
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'disk_count', '1'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'disk_0_mount_point', '/var'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'disk_0_usage_percent', '41.5'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'cpu_core_3_usage_percent', '7.5'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo', 'network_interface_0_name', 'eth0'),
+                ('n', '2024-12-08T10:00:00Z', 'sysinfo',
+                 'network_interface_0_total_received_bytes', '1024');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        migrate(&mut conn).await.unwrap();
+
+        let rows = sqlx::query("SELECT probe_name, labels FROM probe_data ORDER BY id")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+
+        let migrated: Vec<(String, BTreeMap<String, String>)> = rows
+            .iter()
+            .map(|row| (row.get("probe_name"), decode_labels(row.get("labels"))))
+            .collect();
+
+        let label = |k: &str, v: &str| BTreeMap::from([(k.to_string(), v.to_string())]);
+        assert_eq!(
+            migrated,
+            vec![
+                ("disk_count".to_string(), BTreeMap::new()),
+                ("disk_usage_percent".to_string(), label("mount", "/var")),
+                ("cpu_core_usage_percent".to_string(), label("core", "3")),
+                (
+                    "network_total_received_bytes".to_string(),
+                    label("iface", "eth0")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        for value in [
+            ProbeValue::Integer(-3),
+            ProbeValue::Float(0.25),
+            ProbeValue::Boolean(true),
+            ProbeValue::from("eth0"),
+        ] {
+            let cols = encode_value(&value);
+            let decoded = decode_value(cols.kind, cols.int, cols.real, cols.text.map(String::from));
+            assert_eq!(decoded, value);
+        }
+    }
+}
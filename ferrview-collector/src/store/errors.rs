@@ -11,6 +11,9 @@ pub enum StoreError {
     #[error("Database not initialized")]
     NotInitialized,
 
+    #[error("Writer queue is full")]
+    WriterBusy,
+
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
 }
@@ -1,6 +1,8 @@
+pub mod auth;
 pub mod date_range;
 pub mod date_range_reader;
 pub mod db;
 pub mod errors;
+pub mod nodes;
 pub mod queries;
 pub mod writer;
@@ -0,0 +1,289 @@
+// ferrview-collector/src/store/nodes.rs
+use ferrview_common::timestamp::now_millis;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{Connection, Row};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+use crate::store::errors::StoreError;
+
+/// File holding node metadata, next to the daily data files
+pub const NODES_DB_FILENAME: &str = "ferrview_nodes.db";
+
+/// Tags per node, and the length of each key and value
+pub const MAX_TAGS: usize = 32;
+pub const MAX_TAG_LEN: usize = 128;
+
+/// Node metadata outlives the daily data files, so it has its own database.
+/// `node_tags` holds the tags from the node's latest batch.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS node_metadata (
+    node_id TEXT PRIMARY KEY,
+    agent_version TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS node_tags (
+    node_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (node_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_tags_key_value ON node_tags(key, value);
+"#;
+
+/// Tags of one node
+pub type Tags = BTreeMap<String, String>;
+
+/// What the collector knows about a node beyond its data points
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeMetadata {
+    pub node_id: String,
+    pub agent_version: String,
+    pub tags: Tags,
+    /// Unix epoch milliseconds of the last change
+    pub updated_at: i64,
+}
+
+/// Node metadata in `ferrview_nodes.db`
+///
+/// Every envelope carries the node's tags, so the tags last written are
+/// cached and the database is only touched when they change.
+pub struct NodeStore {
+    conn: Mutex<SqliteConnection>,
+    known: std::sync::Mutex<HashMap<String, (String, Tags)>>,
+}
+
+impl NodeStore {
+    /// Open (or create) the node store in `data_dir`
+    pub async fn open(data_dir: &str) -> Result<Self, StoreError> {
+        std::fs::create_dir_all(data_dir)?;
+
+        let db_path = Path::new(data_dir).join(NODES_DB_FILENAME);
+        let db_url = format!("sqlite://{}", db_path.display());
+        debug!("Opening node store at: {}", db_url);
+
+        let options = SqliteConnectOptions::from_str(&db_url)?
+            .create_if_missing(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+
+        Self::with_connection(SqliteConnection::connect_with(&options).await?).await
+    }
+
+    /// A store that lives only as long as the value, for tests
+    #[cfg(test)]
+    pub async fn in_memory() -> Self {
+        let conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        Self::with_connection(conn).await.unwrap()
+    }
+
+    async fn with_connection(mut conn: SqliteConnection) -> Result<Self, StoreError> {
+        sqlx::query(SCHEMA).execute(&mut conn).await?;
+
+        let store = Self {
+            conn: Mutex::new(conn),
+            known: std::sync::Mutex::new(HashMap::new()),
+        };
+        let known = store
+            .list()
+            .await?
+            .into_iter()
+            .map(|node| (node.node_id, (node.agent_version, node.tags)))
+            .collect();
+        *store.known.lock().unwrap() = known;
+        Ok(store)
+    }
+
+    /// Record the agent version and tags a node sent with its latest batch
+    pub async fn update(
+        &self,
+        node_id: &str,
+        agent_version: &str,
+        tags: &Tags,
+    ) -> Result<(), StoreError> {
+        let unchanged = self
+            .known
+            .lock()
+            .unwrap()
+            .get(node_id)
+            .is_some_and(|(version, known)| version == agent_version && known == tags);
+        if unchanged {
+            return Ok(());
+        }
+
+        let mut conn = self.conn.lock().await;
+        let mut tx = conn.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO node_metadata (node_id, agent_version, updated_at) VALUES (?, ?, ?)
+             ON CONFLICT (node_id) DO UPDATE SET agent_version = excluded.agent_version,
+                 updated_at = excluded.updated_at",
+        )
+        .bind(node_id)
+        .bind(agent_version)
+        .bind(now_millis())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM node_tags WHERE node_id = ?")
+            .bind(node_id)
+            .execute(&mut *tx)
+            .await?;
+        for (key, value) in tags {
+            sqlx::query("INSERT INTO node_tags (node_id, key, value) VALUES (?, ?, ?)")
+                .bind(node_id)
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        info!(
+            "Node {} (agent {}) reports tags {:?}",
+            node_id, agent_version, tags
+        );
+        self.known.lock().unwrap().insert(
+            node_id.to_string(),
+            (agent_version.to_string(), tags.clone()),
+        );
+        Ok(())
+    }
+
+    /// Tags of every node, from the cache
+    pub fn all_tags(&self) -> HashMap<String, Tags> {
+        self.known
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(node_id, (_, tags))| (node_id.clone(), tags.clone()))
+            .collect()
+    }
+
+    /// Metadata of every node that sent an envelope, by node_id
+    pub async fn list(&self) -> Result<Vec<NodeMetadata>, StoreError> {
+        let mut conn = self.conn.lock().await;
+
+        let mut nodes: BTreeMap<String, NodeMetadata> = BTreeMap::new();
+        for row in sqlx::query("SELECT node_id, agent_version, updated_at FROM node_metadata")
+            .fetch_all(&mut *conn)
+            .await?
+        {
+            let node_id: String = row.try_get("node_id")?;
+            nodes.insert(
+                node_id.clone(),
+                NodeMetadata {
+                    node_id,
+                    agent_version: row.try_get("agent_version")?,
+                    tags: Tags::new(),
+                    updated_at: row.try_get("updated_at")?,
+                },
+            );
+        }
+
+        for row in sqlx::query("SELECT node_id, key, value FROM node_tags")
+            .fetch_all(&mut *conn)
+            .await?
+        {
+            let node_id: String = row.try_get("node_id")?;
+            if let Some(node) = nodes.get_mut(&node_id) {
+                node.tags.insert(row.try_get("key")?, row.try_get("value")?);
+            }
+        }
+
+        Ok(nodes.into_values().collect())
+    }
+}
+
+/// Why a node's tags are refused, `None` if they are fine
+pub fn validate_tags(tags: &Tags) -> Option<String> {
+    if tags.len() > MAX_TAGS {
+        return Some(format!("at most {} tags are allowed", MAX_TAGS));
+    }
+    tags.iter().find_map(|(key, value)| {
+        if key.is_empty() || key.len() > MAX_TAG_LEN || value.len() > MAX_TAG_LEN {
+            Some(format!(
+                "tag keys must be 1 to {0} bytes and values at most {0}",
+                MAX_TAG_LEN
+            ))
+        } else if key.chars().chain(value.chars()).any(char::is_control) {
+            Some(format!("tag {} contains control characters", key))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether `tags` has every key/value pair in `filter`
+pub fn matches_tags(tags: &Tags, filter: &Tags) -> bool {
+    filter
+        .iter()
+        .all(|(key, value)| tags.get(key).is_some_and(|v| v == value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> Tags {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_update_and_list() {
+        let store = NodeStore::in_memory().await;
+        store
+            .update(
+                "node-1",
+                "0.6.0",
+                &tags(&[("env", "prod"), ("role", "web")]),
+            )
+            .await
+            .unwrap();
+        store
+            .update("node-2", "0.6.0", &tags(&[("env", "staging")]))
+            .await
+            .unwrap();
+        // Dropping a tag removes it
+        store
+            .update("node-1", "0.6.1", &tags(&[("env", "prod")]))
+            .await
+            .unwrap();
+
+        let nodes = store.list().await.unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].node_id, "node-1");
+        assert_eq!(nodes[0].agent_version, "0.6.1");
+        assert_eq!(nodes[0].tags, tags(&[("env", "prod")]));
+
+        let all = store.all_tags();
+        assert_eq!(all["node-2"], tags(&[("env", "staging")]));
+    }
+
+    #[test]
+    fn test_tag_filter_and_validation() {
+        let node = tags(&[("env", "prod"), ("dc", "fra1")]);
+        assert!(matches_tags(&node, &Tags::new()));
+        assert!(matches_tags(&node, &tags(&[("env", "prod")])));
+        assert!(!matches_tags(
+            &node,
+            &tags(&[("env", "prod"), ("dc", "ams3")])
+        ));
+        assert!(!matches_tags(&node, &tags(&[("role", "web")])));
+
+        assert!(validate_tags(&node).is_none());
+        assert!(validate_tags(&tags(&[("", "x")])).is_some());
+        assert!(validate_tags(&tags(&[("env", "a\nb")])).is_some());
+        let too_many: Tags = (0..=MAX_TAGS)
+            .map(|i| (i.to_string(), String::new()))
+            .collect();
+        assert!(validate_tags(&too_many).is_some());
+    }
+}
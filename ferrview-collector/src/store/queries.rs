@@ -1,9 +1,13 @@
 // ferrview-collector/src/store/queries.rs
 
+use ferrview_common::ProbeValue;
+use ferrview_common::timestamp::to_millis;
 use sqlx::SqliteConnection;
+use std::collections::BTreeMap;
 use time::OffsetDateTime;
 use tracing::debug;
 
+use crate::store::db::{decode_labels, decode_value};
 use crate::store::errors::StoreError;
 
 /// A single metric data point from the database
@@ -11,10 +15,12 @@ use crate::store::errors::StoreError;
 #[allow(dead_code)]
 pub struct MetricDataPoint {
     pub node_id: String,
-    pub timestamp: String,
+    /// Unix epoch milliseconds
+    pub timestamp: i64,
     pub probe_type: String,
     pub probe_name: String,
-    pub probe_value: String,
+    pub probe_value: ProbeValue,
+    pub labels: BTreeMap<String, String>,
 }
 
 /// Query metrics for a specific node within a time range
@@ -28,17 +34,15 @@ pub async fn query_node_metrics(
     let now = OffsetDateTime::now_utc();
     let start_time = now - time::Duration::hours(hours as i64);
 
-    let start_str = format_timestamp(start_time);
-    let now_str = format_timestamp(now);
-
     debug!(
         "Querying metrics for node {} with pattern '{}' from {} to {}",
-        node_id, metric_pattern, start_str, now_str
+        node_id, metric_pattern, start_time, now
     );
 
     let rows = sqlx::query_as::<_, MetricRow>(
         r#"
-        SELECT node_id, timestamp, probe_type, probe_name, probe_value
+        SELECT node_id, timestamp, probe_type, probe_name,
+               value_kind, value_int, value_real, value_text, labels
         FROM probe_data
         WHERE node_id = ?1
           AND probe_name LIKE ?2
@@ -49,21 +53,12 @@ pub async fn query_node_metrics(
     )
     .bind(node_id)
     .bind(metric_pattern)
-    .bind(&start_str)
-    .bind(&now_str)
+    .bind(to_millis(start_time))
+    .bind(to_millis(now))
     .fetch_all(conn)
     .await?;
 
-    let result: Vec<MetricDataPoint> = rows
-        .into_iter()
-        .map(|row| MetricDataPoint {
-            node_id: row.node_id,
-            timestamp: row.timestamp,
-            probe_type: row.probe_type,
-            probe_name: row.probe_name,
-            probe_value: row.probe_value,
-        })
-        .collect();
+    let result: Vec<MetricDataPoint> = rows.into_iter().map(MetricDataPoint::from).collect();
 
     debug!("Found {} data points", result.len());
 
@@ -100,7 +95,8 @@ pub async fn query_latest_node_metrics(
 
     let rows = sqlx::query_as::<_, MetricRow>(
         r#"
-        SELECT node_id, timestamp, probe_type, probe_name, probe_value
+        SELECT node_id, timestamp, probe_type, probe_name,
+               value_kind, value_int, value_real, value_text, labels
         FROM probe_data
         WHERE node_id = ?1
           AND timestamp = (
@@ -114,22 +110,38 @@ pub async fn query_latest_node_metrics(
     .fetch_all(conn)
     .await?;
 
-    let result: Vec<MetricDataPoint> = rows
-        .into_iter()
-        .map(|row| MetricDataPoint {
-            node_id: row.node_id,
-            timestamp: row.timestamp,
-            probe_type: row.probe_type,
-            probe_name: row.probe_name,
-            probe_value: row.probe_value,
-        })
-        .collect();
+    let result: Vec<MetricDataPoint> = rows.into_iter().map(MetricDataPoint::from).collect();
 
     debug!("Found {} latest data points", result.len());
 
     Ok(result)
 }
 
+/// Query the distinct metric names a node has of one probe type
+pub async fn query_node_metric_names(
+    conn: &mut SqliteConnection,
+    node_id: &str,
+    probe_type: &str,
+) -> Result<Vec<String>, StoreError> {
+    debug!("Querying {} metric names for node {}", probe_type, node_id);
+
+    let rows = sqlx::query_as::<_, (String,)>(
+        r#"
+        SELECT DISTINCT probe_name
+        FROM probe_data
+        WHERE node_id = ?1
+          AND probe_type = ?2
+        ORDER BY probe_name
+        "#,
+    )
+    .bind(node_id)
+    .bind(probe_type)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|(name,)| name).collect())
+}
+
 /// Query metrics for all nodes within a time range
 #[allow(dead_code)]
 pub async fn query_all_nodes_metrics(
@@ -140,17 +152,15 @@ pub async fn query_all_nodes_metrics(
     let now = OffsetDateTime::now_utc();
     let start_time = now - time::Duration::hours(hours as i64);
 
-    let start_str = format_timestamp(start_time);
-    let now_str = format_timestamp(now);
-
     debug!(
         "Querying metrics for all nodes with pattern '{}' from {} to {}",
-        metric_pattern, start_str, now_str
+        metric_pattern, start_time, now
     );
 
     let rows = sqlx::query_as::<_, MetricRow>(
         r#"
-        SELECT node_id, timestamp, probe_type, probe_name, probe_value
+        SELECT node_id, timestamp, probe_type, probe_name,
+               value_kind, value_int, value_real, value_text, labels
         FROM probe_data
         WHERE probe_name LIKE ?1
           AND timestamp >= ?2
@@ -159,21 +169,12 @@ pub async fn query_all_nodes_metrics(
         "#,
     )
     .bind(metric_pattern)
-    .bind(&start_str)
-    .bind(&now_str)
+    .bind(to_millis(start_time))
+    .bind(to_millis(now))
     .fetch_all(conn)
     .await?;
 
-    let result: Vec<MetricDataPoint> = rows
-        .into_iter()
-        .map(|row| MetricDataPoint {
-            node_id: row.node_id,
-            timestamp: row.timestamp,
-            probe_type: row.probe_type,
-            probe_name: row.probe_name,
-            probe_value: row.probe_value,
-        })
-        .collect();
+    let result: Vec<MetricDataPoint> = rows.into_iter().map(MetricDataPoint::from).collect();
 
     debug!("Found {} data points across all nodes", result.len());
 
@@ -184,44 +185,52 @@ pub async fn query_all_nodes_metrics(
 #[derive(sqlx::FromRow)]
 struct MetricRow {
     node_id: String,
-    timestamp: String,
+    timestamp: i64,
     probe_type: String,
     probe_name: String,
-    probe_value: String,
+    value_kind: String,
+    value_int: Option<i64>,
+    value_real: Option<f64>,
+    value_text: Option<String>,
+    labels: String,
 }
 
-/// Format timestamp in ISO 8601 format for SQLite queries
-#[allow(dead_code)]
-fn format_timestamp(dt: OffsetDateTime) -> String {
-    dt.format(&time::format_description::well_known::Rfc3339)
-        .unwrap_or_else(|_| String::from("1970-01-01T00:00:00Z"))
+impl From<MetricRow> for MetricDataPoint {
+    fn from(row: MetricRow) -> Self {
+        Self {
+            node_id: row.node_id,
+            timestamp: row.timestamp,
+            probe_type: row.probe_type,
+            probe_name: row.probe_name,
+            probe_value: decode_value(
+                &row.value_kind,
+                row.value_int,
+                row.value_real,
+                row.value_text,
+            ),
+            labels: decode_labels(&row.labels),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use time::macros::datetime;
-
-    #[test]
-    fn test_format_timestamp() {
-        let dt = datetime!(2024-12-08 10:30:00 UTC);
-        let formatted = format_timestamp(dt);
-        assert!(formatted.starts_with("2024-12-08"));
-        assert!(formatted.contains("10:30:00"));
-    }
 
     #[test]
     fn test_metric_data_point_clone() {
         let point = MetricDataPoint {
             node_id: "test".to_string(),
-            timestamp: "2024-12-08T10:00:00Z".to_string(),
+            timestamp: 1_733_652_000_000,
             probe_type: "sysinfo".to_string(),
             probe_name: "cpu_usage".to_string(),
-            probe_value: "50.0".to_string(),
+            probe_value: ProbeValue::Float(50.0),
+            labels: BTreeMap::from([("core".to_string(), "0".to_string())]),
         };
 
         let cloned = point.clone();
         assert_eq!(point.node_id, cloned.node_id);
         assert_eq!(point.probe_value, cloned.probe_value);
+        assert_eq!(point.labels, cloned.labels);
     }
 }
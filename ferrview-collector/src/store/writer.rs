@@ -3,9 +3,10 @@ use ferrview_common::ProbeDataPoint;
 use sqlx::{Acquire, SqliteConnection};
 use time::OffsetDateTime;
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
 use tracing::{debug, error, info, warn};
 
-use crate::store::db::Database;
+use crate::store::db::{Database, encode_labels, encode_value};
 use crate::store::errors::StoreError;
 
 const CHANNEL_BUFFER_SIZE: usize = 1000;
@@ -32,6 +33,17 @@ impl WriterHandle {
         Ok(())
     }
 
+    /// Queue a batch without waiting, failing with `WriterBusy` when the
+    /// writer is already `CHANNEL_BUFFER_SIZE` commands behind
+    pub fn try_insert_batch(&self, data: Vec<ProbeDataPoint>) -> Result<(), StoreError> {
+        self.tx
+            .try_send(WriteCommand::InsertBatch(data))
+            .map_err(|e| match e {
+                TrySendError::Full(_) => StoreError::WriterBusy,
+                TrySendError::Closed(_) => StoreError::NotInitialized,
+            })
+    }
+
     /// Signal the writer task to shut down gracefully
     pub async fn shutdown(&self) -> Result<(), StoreError> {
         self.tx
@@ -177,17 +189,25 @@ async fn insert_batch(
     let mut count = 0u64;
 
     for data in data_points {
+        let value = encode_value(&data.probe_value);
+
         sqlx::query(
             r#"
-            INSERT INTO probe_data (node_id, timestamp, probe_type, probe_name, probe_value)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO probe_data
+                (node_id, timestamp, probe_type, probe_name,
+                 value_kind, value_int, value_real, value_text, labels)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&data.node_id)
-        .bind(&data.timestamp)
+        .bind(data.timestamp)
         .bind(&data.probe_type)
         .bind(&data.probe_name)
-        .bind(&data.probe_value)
+        .bind(value.kind)
+        .bind(value.int)
+        .bind(value.real)
+        .bind(value.text)
+        .bind(encode_labels(&data.labels))
         .execute(&mut *tx)
         .await?;
 
@@ -210,3 +230,29 @@ fn get_current_utc_date() -> String {
         now.day()
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_try_insert_batch_when_full() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let handle = WriterHandle { tx };
+
+        handle.try_insert_batch(Vec::new()).unwrap();
+        assert!(matches!(
+            handle.try_insert_batch(Vec::new()),
+            Err(StoreError::WriterBusy)
+        ));
+
+        rx.recv().await.unwrap();
+        handle.try_insert_batch(Vec::new()).unwrap();
+
+        drop(rx);
+        assert!(matches!(
+            handle.try_insert_batch(Vec::new()),
+            Err(StoreError::NotInitialized)
+        ));
+    }
+}
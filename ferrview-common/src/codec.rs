@@ -0,0 +1,270 @@
+// ferrview-common/src/codec.rs
+
+//! Body encodings for node-to-collector requests, selected by `Content-Type`,
+//! and body compression, selected by `Content-Encoding`.
+
+use flate2::Compression as GzipLevel;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+
+pub const CONTENT_TYPE_JSON: &str = "application/json";
+pub const CONTENT_TYPE_MSGPACK: &str = "application/msgpack";
+
+/// Encoding of a request body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    Json,
+    /// MessagePack with named fields, so optional fields can be omitted
+    MessagePack,
+}
+
+/// Formats this build can decode, in order of preference
+pub const SUPPORTED_WIRE_FORMATS: &[WireFormat] = &[WireFormat::MessagePack, WireFormat::Json];
+
+#[derive(Debug)]
+pub struct CodecError(String);
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for CodecError {}
+
+impl WireFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => CONTENT_TYPE_JSON,
+            Self::MessagePack => CONTENT_TYPE_MSGPACK,
+        }
+    }
+
+    /// Parse a `Content-Type` header value, ignoring parameters such as
+    /// `charset`
+    pub fn from_content_type(value: &str) -> Option<Self> {
+        let mime = value.split(';').next().unwrap_or("").trim();
+
+        if mime.eq_ignore_ascii_case(CONTENT_TYPE_JSON) {
+            Some(Self::Json)
+        } else if mime.eq_ignore_ascii_case(CONTENT_TYPE_MSGPACK)
+            || mime.eq_ignore_ascii_case("application/x-msgpack")
+        {
+            Some(Self::MessagePack)
+        } else {
+            None
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Self::Json => serde_json::to_vec(value).map_err(|e| CodecError(e.to_string())),
+            Self::MessagePack => {
+                rmp_serde::to_vec_named(value).map_err(|e| CodecError(e.to_string()))
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        match self {
+            Self::Json => serde_json::from_slice(bytes).map_err(|e| CodecError(e.to_string())),
+            Self::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|e| CodecError(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Compression applied to a request body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Compressions this build can decode, excluding `None`
+pub const SUPPORTED_COMPRESSIONS: &[Compression] = &[Compression::Zstd, Compression::Gzip];
+
+const ZSTD_LEVEL: i32 = 3;
+
+impl Compression {
+    /// `Content-Encoding` header value, `None` for an uncompressed body
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gzip"),
+            Self::Zstd => Some("zstd"),
+        }
+    }
+
+    /// Parse a `Content-Encoding` header value
+    pub fn from_content_encoding(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        if value.eq_ignore_ascii_case("identity") {
+            Some(Self::None)
+        } else if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(Self::Gzip)
+        } else if value.eq_ignore_ascii_case("zstd") {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Self::None => Ok(bytes.to_vec()),
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzipLevel::default());
+                encoder
+                    .write_all(bytes)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|e| CodecError(e.to_string()))
+            }
+            Self::Zstd => {
+                zstd::encode_all(bytes, ZSTD_LEVEL).map_err(|e| CodecError(e.to_string()))
+            }
+        }
+    }
+
+    /// Decompress `bytes`, stopping after `limit + 1` bytes of output
+    ///
+    /// A result longer than `limit` means the body inflates past the limit;
+    /// the rest is never decompressed, so a small bomb cannot exhaust memory.
+    pub fn decompress(&self, bytes: &[u8], limit: usize) -> Result<Vec<u8>, CodecError> {
+        let reader: Box<dyn Read + '_> = match self {
+            Self::None => Box::new(bytes),
+            Self::Gzip => Box::new(GzDecoder::new(bytes)),
+            Self::Zstd => {
+                Box::new(zstd::Decoder::new(bytes).map_err(|e| CodecError(e.to_string()))?)
+            }
+        };
+
+        let mut out = Vec::new();
+        reader
+            .take(limit as u64 + 1)
+            .read_to_end(&mut out)
+            .map_err(|e| CodecError(e.to_string()))?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{BatchEnvelope, IncomingBatch};
+    use crate::{ProbeDataPoint, ProbeValue};
+    use std::collections::BTreeMap;
+
+    fn points() -> Vec<ProbeDataPoint> {
+        let values = [
+            ProbeValue::Integer(-3),
+            ProbeValue::Integer(i64::MAX),
+            ProbeValue::Float(42.5),
+            ProbeValue::Boolean(true),
+            ProbeValue::from("Linux"),
+        ];
+
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| ProbeDataPoint {
+                node_id: "node-1".to_string(),
+                timestamp: 1_704_110_400_000,
+                probe_type: "sysinfo".to_string(),
+                probe_name: format!("metric_{}", i),
+                probe_value: value,
+                labels: BTreeMap::from([("core".to_string(), i.to_string())]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_content_type_parsing() {
+        assert_eq!(
+            WireFormat::from_content_type("application/json; charset=utf-8"),
+            Some(WireFormat::Json)
+        );
+        assert_eq!(
+            WireFormat::from_content_type("application/x-msgpack"),
+            Some(WireFormat::MessagePack)
+        );
+        assert_eq!(WireFormat::from_content_type("text/plain"), None);
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let envelope = BatchEnvelope::new("node-1", "0.6.0", 5, points());
+
+        let bytes = WireFormat::MessagePack.encode(&envelope).unwrap();
+        let decoded: IncomingBatch = WireFormat::MessagePack.decode(&bytes).unwrap();
+
+        assert_eq!(decoded, IncomingBatch::Envelope(envelope));
+    }
+
+    #[test]
+    fn test_msgpack_is_smaller_than_json() {
+        let envelope = BatchEnvelope::new("node-1", "0.6.0", 5, points());
+
+        let json = WireFormat::Json.encode(&envelope).unwrap();
+        let msgpack = WireFormat::MessagePack.encode(&envelope).unwrap();
+
+        assert!(msgpack.len() < json.len());
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let body = WireFormat::Json
+            .encode(&BatchEnvelope::new("node-1", "0.6.0", 5, points()))
+            .unwrap();
+
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let compressed = compression.compress(&body).unwrap();
+            let restored = compression.decompress(&compressed, body.len()).unwrap();
+            assert_eq!(restored, body, "{:?}", compression);
+        }
+    }
+
+    #[test]
+    fn test_decompress_stops_at_limit() {
+        let bomb = vec![0u8; 1024 * 1024];
+
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let compressed = compression.compress(&bomb).unwrap();
+            assert!(compressed.len() < 8 * 1024);
+
+            let restored = compression.decompress(&compressed, 1000).unwrap();
+            assert_eq!(restored.len(), 1001, "{:?}", compression);
+        }
+    }
+
+    #[test]
+    fn test_content_encoding_parsing() {
+        assert_eq!(
+            Compression::from_content_encoding("GZIP"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_content_encoding("identity"),
+            Some(Compression::None)
+        );
+        assert_eq!(Compression::from_content_encoding("br"), None);
+        assert_eq!(Compression::Zstd.content_encoding(), Some("zstd"));
+        assert_eq!(Compression::None.content_encoding(), None);
+    }
+
+    #[test]
+    fn test_decode_garbage() {
+        let result: Result<IncomingBatch, _> = WireFormat::MessagePack.decode(b"\xc1\xc1");
+        assert!(result.is_err());
+    }
+}
@@ -1,12 +1,42 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+pub mod codec;
+pub mod logging;
+pub mod metrics;
+pub mod protocol;
+pub mod signing;
+pub mod timestamp;
+mod value;
+
+pub use value::ProbeValue;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ProbeDataPoint {
     pub node_id: String,
-    pub timestamp: String,
+    /// Unix epoch milliseconds (UTC); RFC 3339 strings are accepted on input
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub timestamp: i64,
     pub probe_type: String,
     pub probe_name: String,
-    pub probe_value: String,
+    pub probe_value: ProbeValue,
+    /// Stable identity of the measured object, e.g. `mount = "/var"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// An error and its sources, so connection failures show the underlying
+/// cause (refused, TLS verification, ...) rather than just "Connect"
+pub fn error_chain(e: &dyn Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
 }
 
 #[cfg(test)]
@@ -17,15 +47,19 @@ mod tests {
     fn test_serialize_probe_data_point() {
         let point = ProbeDataPoint {
             node_id: "test-node".to_string(),
-            timestamp: "2024-01-01T12:00:00Z".to_string(),
+            timestamp: 1_704_110_400_000,
             probe_type: "sysinfo".to_string(),
             probe_name: "cpu_count".to_string(),
-            probe_value: "8".to_string(),
+            probe_value: ProbeValue::Integer(8),
+            labels: BTreeMap::new(),
         };
 
         let json = serde_json::to_string(&point).unwrap();
         assert!(json.contains("test-node"));
         assert!(json.contains("cpu_count"));
+        assert!(json.contains("\"timestamp\":1704110400000"));
+        assert!(json.contains("\"probe_value\":8"));
+        assert!(!json.contains("labels"));
     }
 
     #[test]
@@ -40,17 +74,21 @@ mod tests {
 
         let point: ProbeDataPoint = serde_json::from_str(json).unwrap();
         assert_eq!(point.node_id, "test-node");
-        assert_eq!(point.probe_value, "8");
+        assert_eq!(point.timestamp, 1_704_110_400_000);
+        // Inferring a number from a string is left to version 1 batches
+        assert_eq!(point.probe_value, ProbeValue::from("8"));
+        assert!(point.labels.is_empty());
     }
 
     #[test]
     fn test_round_trip() {
         let original = ProbeDataPoint {
             node_id: "node-123".to_string(),
-            timestamp: "2024-01-01T12:00:00Z".to_string(),
+            timestamp: 1_704_110_400_000,
             probe_type: "sysinfo".to_string(),
-            probe_name: "memory_total".to_string(),
-            probe_value: "16777216".to_string(),
+            probe_name: "disk_total_bytes".to_string(),
+            probe_value: ProbeValue::Integer(16777216),
+            labels: BTreeMap::from([("mount".to_string(), "/var".to_string())]),
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -58,4 +96,40 @@ mod tests {
 
         assert_eq!(original, deserialized);
     }
+
+    #[test]
+    fn test_deserialize_typed_probe_value() {
+        let json = r#"{
+            "node_id": "test-node",
+            "timestamp": 1704110400123,
+            "probe_type": "sysinfo",
+            "probe_name": "cpu_core_0_usage_percent",
+            "probe_value": 12.5
+        }"#;
+
+        let point: ProbeDataPoint = serde_json::from_str(json).unwrap();
+        assert_eq!(point.timestamp, 1_704_110_400_123);
+        assert_eq!(point.probe_value, ProbeValue::Float(12.5));
+    }
+
+    #[test]
+    fn test_error_chain() {
+        #[derive(Debug)]
+        struct Connect(std::io::Error);
+
+        impl std::fmt::Display for Connect {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "Connect")
+            }
+        }
+
+        impl Error for Connect {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let refused = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        assert_eq!(error_chain(&Connect(refused)), "Connect: refused");
+    }
 }
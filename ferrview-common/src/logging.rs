@@ -0,0 +1,249 @@
+// ferrview-common/src/logging.rs
+
+//! Log output shared by the node and the collector: human-readable text or
+//! one JSON object per line, to stdout, stderr or a file rotated by size.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+/// Rotated log files kept next to the current one
+pub const LOG_FILES_KEPT: usize = 5;
+
+/// Size at which the log file is rotated unless told otherwise
+pub const DEFAULT_LOG_MAX_SIZE_MB: u64 = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One object per line with `timestamp`, `level`, `target` and the
+    /// event's `fields`, `message` among them
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "unknown log format {}, expected text or json",
+                other
+            )),
+        }
+    }
+}
+
+/// Parse `--log-max-size-mb`, which must leave room for at least one event
+pub fn parse_log_max_size_mb(value: &str) -> Result<u64, String> {
+    match value.parse() {
+        Ok(0) => Err("the log file size must be at least 1 MB".to_string()),
+        Ok(size) => Ok(size),
+        Err(e) => Err(format!("invalid log file size {}: {}", value, e)),
+    }
+}
+
+/// Where logs go and what they look like
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    pub format: LogFormat,
+    /// Log to this file instead of the console
+    pub file: Option<PathBuf>,
+    /// Size in bytes at which `file` is rotated
+    pub max_size_bytes: u64,
+}
+
+/// Install the global subscriber. Without a log file, logs go to stdout,
+/// or to stderr when stdout is taken by a command's results. The level is
+/// taken from `RUST_LOG`, `info` by default.
+pub fn init<T>(options: &LogOptions, timer: T, stderr: bool) -> io::Result<()>
+where
+    T: FormatTime + Send + Sync + 'static,
+{
+    let writer = match &options.file {
+        Some(path) => BoxMakeWriter::new(Mutex::new(RotatingFile::open(
+            path,
+            options.max_size_bytes,
+            LOG_FILES_KEPT,
+        )?)),
+        None if stderr => BoxMakeWriter::new(io::stderr),
+        None => BoxMakeWriter::new(io::stdout),
+    };
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(env_filter)
+        .with_writer(writer);
+
+    match options.format {
+        LogFormat::Text => builder.with_timer(timer).init(),
+        LogFormat::Json => builder.json().with_timer(timer).init(),
+    }
+    Ok(())
+}
+
+/// A log file that is moved aside once it reaches `max_size_bytes`:
+/// `node.log` becomes `node.log.1`, `node.log.1` becomes `node.log.2` and
+/// so on, and the oldest beyond `keep` is deleted
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size_bytes: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+    /// Whether the last rotation failed, so the error is reported once
+    rotation_failed: bool,
+}
+
+impl RotatingFile {
+    /// Open `path` for appending, creating it if needed
+    pub fn open(path: &Path, max_size_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = Self::append(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size_bytes,
+            keep,
+            file,
+            size,
+            rotation_failed: false,
+        })
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// `path` with `.{index}` appended
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            for index in (1..self.keep).rev() {
+                match fs::rename(self.rotated(index), self.rotated(index + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = Self::append(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    /// Events are written whole, so a rotation never splits one. When the
+    /// rotation fails, events keep going to the current file: there is
+    /// nowhere to log that, so it is reported on stderr, once until a
+    /// rotation succeeds again.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size_bytes {
+            match self.rotate() {
+                Ok(()) => self.rotation_failed = false,
+                Err(e) if !self.rotation_failed => {
+                    eprintln!("Failed to rotate log file {}: {}", self.path.display(), e);
+                    self.rotation_failed = true;
+                }
+                Err(_) => {}
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!("text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_parse_log_max_size_mb() {
+        assert_eq!(parse_log_max_size_mb("10"), Ok(10));
+        assert!(parse_log_max_size_mb("0").is_err());
+        assert!(parse_log_max_size_mb("-1").is_err());
+        assert!(parse_log_max_size_mb("ten").is_err());
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = std::env::temp_dir().join(format!("ferrview-common-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("node.log");
+
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in ["aaaaaaa\n", "bbbbbbb\n", "ccccccc\n", "ddddddd\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        let read = |path: &Path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "ddddddd\n");
+        assert_eq!(read(&dir.join("node.log.1")), "ccccccc\n");
+        assert_eq!(read(&dir.join("node.log.2")), "bbbbbbb\n");
+        assert!(!dir.join("node.log.3").exists());
+
+        // Reopening carries on from the current size
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        file.write_all(b"e\n").unwrap();
+        assert_eq!(read(&path), "ddddddd\ne\n");
+        file.write_all(b"f\n").unwrap();
+        assert_eq!(read(&path), "f\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotation_failure_keeps_logging() {
+        let dir =
+            std::env::temp_dir().join(format!("ferrview-common-rotation-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("node.log");
+
+        // A directory in the way of node.log.1 makes the rotation fail
+        fs::create_dir(dir.join("node.log.1")).unwrap();
+        let mut file = RotatingFile::open(&path, 10, 1).unwrap();
+        file.write_all(b"aaaaaaa\n").unwrap();
+        file.write_all(b"bbbbbbb\n").unwrap();
+        file.write_all(b"ccccccc\n").unwrap();
+        let read = |path: &Path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "aaaaaaa\nbbbbbbb\nccccccc\n");
+        assert!(file.rotation_failed);
+
+        // Once it is out of the way, rotating works again
+        fs::remove_dir(dir.join("node.log.1")).unwrap();
+        file.write_all(b"ddddddd\n").unwrap();
+        assert_eq!(read(&path), "ddddddd\n");
+        assert_eq!(read(&dir.join("node.log.1")), "aaaaaaa\nbbbbbbb\nccccccc\n");
+        assert!(!file.rotation_failed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
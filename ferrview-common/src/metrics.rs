@@ -0,0 +1,435 @@
+// ferrview-common/src/metrics.rs
+
+//! Registry of the metrics emitted by ferrview-node probes, and of those the
+//! collector records itself when it scrapes nodes.
+//!
+//! Both the node and the collector refer to metrics through the constants
+//! in this module, so renaming a metric is a compile-time change on both
+//! sides instead of a silently empty chart.
+
+use serde::Serialize;
+
+/// How a metric's value evolves over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricKind {
+    /// A value that can go up and down (usage, temperature)
+    Gauge,
+    /// A monotonically increasing total (bytes received, forks)
+    Counter,
+    /// A descriptive, usually non-numeric value (hostname, filesystem type)
+    Info,
+}
+
+/// Static description of a single metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MetricDef {
+    /// Value of `ProbeDataPoint::probe_name`
+    pub name: &'static str,
+    /// Value of `ProbeDataPoint::probe_type`
+    pub probe_type: &'static str,
+    pub kind: MetricKind,
+    /// Unit symbol, empty for dimensionless values
+    pub unit: &'static str,
+    /// Short quantity name, used for chart axes
+    pub title: &'static str,
+    pub description: &'static str,
+    /// Label keys identifying the measured object
+    pub labels: &'static [&'static str],
+}
+
+/// Declares the metric constants, their `names::*` string constants
+/// (usable in `match` patterns) and the `ALL` catalog in one place
+macro_rules! metrics {
+    ($(
+        $(#[$meta:meta])*
+        $ident:ident {
+            name: $name:literal,
+            probe_type: $probe_type:literal,
+            kind: $kind:ident,
+            unit: $unit:literal,
+            title: $title:literal,
+            description: $description:literal,
+            labels: [$($label:literal),*] $(,)?
+        }
+    )*) => {
+        /// Metric names as plain string constants
+        pub mod names {
+            $(
+                pub const $ident: &str = $name;
+            )*
+        }
+
+        $(
+            $(#[$meta])*
+            pub const $ident: MetricDef = MetricDef {
+                name: names::$ident,
+                probe_type: $probe_type,
+                kind: MetricKind::$kind,
+                unit: $unit,
+                title: $title,
+                description: $description,
+                labels: &[$($label),*],
+            };
+        )*
+
+        /// Every known metric, in declaration order
+        pub const ALL: &[MetricDef] = &[$($ident),*];
+    };
+}
+
+metrics! {
+    // Static system information
+    SYSTEM_CPU_ARCH {
+        name: "system_cpu_arch",
+        probe_type: "sysinfo",
+        kind: Info,
+        unit: "",
+        title: "CPU architecture",
+        description: "CPU architecture of the host",
+        labels: [],
+    }
+    SYSTEM_OS_NAME {
+        name: "system_os_name",
+        probe_type: "sysinfo",
+        kind: Info,
+        unit: "",
+        title: "OS",
+        description: "Operating system name",
+        labels: [],
+    }
+    SYSTEM_KERNEL_VERSION {
+        name: "system_kernel_version",
+        probe_type: "sysinfo",
+        kind: Info,
+        unit: "",
+        title: "Kernel",
+        description: "Kernel version",
+        labels: [],
+    }
+    SYSTEM_OS_VERSION {
+        name: "system_os_version",
+        probe_type: "sysinfo",
+        kind: Info,
+        unit: "",
+        title: "OS version",
+        description: "Operating system version",
+        labels: [],
+    }
+    SYSTEM_HOSTNAME {
+        name: "system_hostname",
+        probe_type: "sysinfo",
+        kind: Info,
+        unit: "",
+        title: "Hostname",
+        description: "Hostname of the node",
+        labels: [],
+    }
+
+    // CPU
+    CPU_CORE_COUNT {
+        name: "cpu_core_count",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Cores",
+        description: "Number of logical CPU cores",
+        labels: [],
+    }
+    CPU_CORE_FREQUENCY_MHZ {
+        name: "cpu_core_frequency_mhz",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "MHz",
+        title: "Frequency",
+        description: "Current frequency of a CPU core",
+        labels: ["core"],
+    }
+    CPU_CORE_USAGE_PERCENT {
+        name: "cpu_core_usage_percent",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "%",
+        title: "Usage",
+        description: "Utilisation of a CPU core since the previous collection",
+        labels: ["core"],
+    }
+
+    // Memory
+    MEMORY_TOTAL_BYTES {
+        name: "memory_total_bytes",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "B",
+        title: "Memory",
+        description: "Total physical memory",
+        labels: [],
+    }
+    MEMORY_USED_BYTES {
+        name: "memory_used_bytes",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "B",
+        title: "Memory",
+        description: "Physical memory in use",
+        labels: [],
+    }
+    MEMORY_AVAILABLE_BYTES {
+        name: "memory_available_bytes",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "B",
+        title: "Memory",
+        description: "Physical memory available for new allocations",
+        labels: [],
+    }
+    SWAP_TOTAL_BYTES {
+        name: "swap_total_bytes",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "B",
+        title: "Swap",
+        description: "Total swap space",
+        labels: [],
+    }
+    SWAP_USED_BYTES {
+        name: "swap_used_bytes",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "B",
+        title: "Swap",
+        description: "Swap space in use",
+        labels: [],
+    }
+
+    // Disks
+    DISK_COUNT {
+        name: "disk_count",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Disks",
+        description: "Number of mounted disks",
+        labels: [],
+    }
+    DISK_NAME {
+        name: "disk_name",
+        probe_type: "sysinfo",
+        kind: Info,
+        unit: "",
+        title: "Device",
+        description: "Device name backing a mount point",
+        labels: ["mount"],
+    }
+    DISK_TOTAL_BYTES {
+        name: "disk_total_bytes",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "B",
+        title: "Capacity",
+        description: "Total capacity of a mounted disk",
+        labels: ["mount"],
+    }
+    DISK_AVAILABLE_BYTES {
+        name: "disk_available_bytes",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "B",
+        title: "Available",
+        description: "Space available on a mounted disk",
+        labels: ["mount"],
+    }
+    DISK_USAGE_PERCENT {
+        name: "disk_usage_percent",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "%",
+        title: "Usage",
+        description: "Share of a mounted disk's capacity in use",
+        labels: ["mount"],
+    }
+    DISK_FILESYSTEM_TYPE {
+        name: "disk_filesystem_type",
+        probe_type: "sysinfo",
+        kind: Info,
+        unit: "",
+        title: "Filesystem",
+        description: "Filesystem type of a mounted disk",
+        labels: ["mount"],
+    }
+
+    // Network
+    NETWORK_INTERFACE_COUNT {
+        name: "network_interface_count",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Interfaces",
+        description: "Number of network interfaces",
+        labels: [],
+    }
+    NETWORK_TOTAL_RECEIVED_BYTES {
+        name: "network_total_received_bytes",
+        probe_type: "sysinfo",
+        kind: Counter,
+        unit: "B",
+        title: "Traffic",
+        description: "Bytes received on an interface since boot",
+        labels: ["iface"],
+    }
+    NETWORK_TOTAL_TRANSMITTED_BYTES {
+        name: "network_total_transmitted_bytes",
+        probe_type: "sysinfo",
+        kind: Counter,
+        unit: "B",
+        title: "Traffic",
+        description: "Bytes transmitted on an interface since boot",
+        labels: ["iface"],
+    }
+    NETWORK_PACKETS_RECEIVED {
+        name: "network_packets_received",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Packets",
+        description: "Packets received on an interface since the previous collection",
+        labels: ["iface"],
+    }
+    NETWORK_PACKETS_TRANSMITTED {
+        name: "network_packets_transmitted",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Packets",
+        description: "Packets transmitted on an interface since the previous collection",
+        labels: ["iface"],
+    }
+    NETWORK_ERRORS_ON_RECEIVED {
+        name: "network_errors_on_received",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Errors",
+        description: "Receive errors on an interface since the previous collection",
+        labels: ["iface"],
+    }
+    NETWORK_ERRORS_ON_TRANSMITTED {
+        name: "network_errors_on_transmitted",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Errors",
+        description: "Transmit errors on an interface since the previous collection",
+        labels: ["iface"],
+    }
+
+    // Temperature
+    TEMPERATURE_SENSOR_COUNT {
+        name: "temperature_sensor_count",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "",
+        title: "Sensors",
+        description: "Number of temperature sensors",
+        labels: [],
+    }
+    TEMPERATURE_CELSIUS {
+        name: "temperature_celsius",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "°C",
+        title: "Temperature",
+        description: "Current reading of a temperature sensor",
+        labels: ["sensor"],
+    }
+    TEMPERATURE_MAX_CELSIUS {
+        name: "temperature_max_celsius",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "°C",
+        title: "Temperature",
+        description: "Highest reading seen by a temperature sensor",
+        labels: ["sensor"],
+    }
+    TEMPERATURE_CRITICAL_CELSIUS {
+        name: "temperature_critical_celsius",
+        probe_type: "sysinfo",
+        kind: Gauge,
+        unit: "°C",
+        title: "Temperature",
+        description: "Critical threshold of a temperature sensor",
+        labels: ["sensor"],
+    }
+
+    // Recorded by the collector for every scrape of a node
+    SCRAPE_UP {
+        name: "up",
+        probe_type: "scrape",
+        kind: Gauge,
+        unit: "",
+        title: "Up",
+        description: "Whether the last scrape of the node succeeded",
+        labels: [],
+    }
+    SCRAPE_DURATION_SECONDS {
+        name: "scrape_duration_seconds",
+        probe_type: "scrape",
+        kind: Gauge,
+        unit: "s",
+        title: "Scrape duration",
+        description: "How long the last scrape of the node took",
+        labels: [],
+    }
+
+    // procfs
+    FORKS_TOTAL {
+        name: "forks_total",
+        probe_type: "procfs",
+        kind: Counter,
+        unit: "",
+        title: "Total Forks",
+        description: "Processes created since boot",
+        labels: [],
+    }
+}
+
+/// Look up a metric by its `probe_name`
+pub fn lookup(name: &str) -> Option<&'static MetricDef> {
+    ALL.iter().find(|m| m.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_names_are_unique() {
+        let names: HashSet<_> = ALL.iter().map(|m| m.name).collect();
+        assert_eq!(names.len(), ALL.len());
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("memory_used_bytes"), Some(&MEMORY_USED_BYTES));
+        assert_eq!(
+            lookup("forks_total").map(|m| m.kind),
+            Some(MetricKind::Counter)
+        );
+        assert!(lookup("no_such_metric").is_none());
+    }
+
+    #[test]
+    fn test_names_constants_match_defs() {
+        assert_eq!(names::DISK_USAGE_PERCENT, DISK_USAGE_PERCENT.name);
+        assert_eq!(DISK_USAGE_PERCENT.labels, &["mount"]);
+    }
+
+    #[test]
+    fn test_serialize_catalog_entry() {
+        let json = serde_json::to_string(&CPU_CORE_USAGE_PERCENT).unwrap();
+        assert!(json.contains("\"name\":\"cpu_core_usage_percent\""));
+        assert!(json.contains("\"kind\":\"gauge\""));
+        assert!(json.contains("\"labels\":[\"core\"]"));
+    }
+}
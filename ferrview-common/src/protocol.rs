@@ -0,0 +1,611 @@
+// ferrview-common/src/protocol.rs
+
+//! Wire format between ferrview-node and ferrview-collector.
+//!
+//! Version 1 is the original `{"data": [...]}` batch in which every point
+//! repeats its node id and timestamp. Version 2 wraps the points in a
+//! [`BatchEnvelope`] that carries the node metadata once.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::codec::{CONTENT_TYPE_JSON, Compression, SUPPORTED_WIRE_FORMATS, WireFormat};
+use crate::{ProbeDataPoint, ProbeValue, timestamp};
+
+/// Legacy `{"data": [...]}` batch
+pub const PROTOCOL_V1: u32 = 1;
+
+/// Versioned [`BatchEnvelope`]
+pub const PROTOCOL_V2: u32 = 2;
+
+/// Protocol versions this build can speak, oldest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u32] = &[PROTOCOL_V1, PROTOCOL_V2];
+
+/// Version 1 request payload
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProbeDataBatch {
+    pub data: Vec<ProbeDataPoint>,
+}
+
+/// Version 2 request payload
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchEnvelope {
+    pub schema_version: u32,
+    pub node_id: String,
+    pub agent_version: String,
+    /// When the batch was collected, in epoch milliseconds; the default
+    /// timestamp of its points
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub collected_at: i64,
+    /// Collection cycle counter, restarts at 0 when the agent restarts
+    pub sequence: u64,
+    /// Free-form node tags from the node's `[tags]` config, such as
+    /// environment or role; sent with every batch
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    pub points: Vec<EnvelopePoint>,
+}
+
+/// A data point inside a [`BatchEnvelope`], without the node id
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EnvelopePoint {
+    /// Only present when it differs from the envelope's `collected_at`
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "timestamp::deserialize_option"
+    )]
+    pub timestamp: Option<i64>,
+    pub probe_type: String,
+    pub probe_name: String,
+    pub probe_value: ProbeValue,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl BatchEnvelope {
+    /// Wrap points collected by `node_id` into a version 2 envelope
+    ///
+    /// The first point's timestamp becomes `collected_at`.
+    pub fn new(
+        node_id: &str,
+        agent_version: &str,
+        sequence: u64,
+        points: Vec<ProbeDataPoint>,
+    ) -> Self {
+        let collected_at = points.first().map(|p| p.timestamp).unwrap_or_default();
+
+        let points = points
+            .into_iter()
+            .map(|p| EnvelopePoint {
+                timestamp: (p.timestamp != collected_at).then_some(p.timestamp),
+                probe_type: p.probe_type,
+                probe_name: p.probe_name,
+                probe_value: p.probe_value,
+                labels: p.labels,
+            })
+            .collect();
+
+        Self {
+            schema_version: PROTOCOL_V2,
+            node_id: node_id.to_string(),
+            agent_version: agent_version.to_string(),
+            collected_at,
+            sequence,
+            tags: BTreeMap::new(),
+            points,
+        }
+    }
+
+    /// Attach the node's tags
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Expand the envelope back into self-contained data points
+    pub fn into_points(self) -> Vec<ProbeDataPoint> {
+        let node_id = self.node_id;
+        let collected_at = self.collected_at;
+
+        self.points
+            .into_iter()
+            .map(|p| ProbeDataPoint {
+                node_id: node_id.clone(),
+                timestamp: p.timestamp.unwrap_or(collected_at),
+                probe_type: p.probe_type,
+                probe_name: p.probe_name,
+                probe_value: p.probe_value,
+                labels: p.labels,
+            })
+            .collect()
+    }
+}
+
+/// Any batch format the collector accepts
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum IncomingBatch {
+    Envelope(BatchEnvelope),
+    Legacy(ProbeDataBatch),
+}
+
+impl IncomingBatch {
+    /// The batch's points. Version 1 values arrive as strings and get
+    /// numbers and booleans inferred. Version 1 metric names that number the
+    /// object they measure, such as `disk_0_*`, are relabelled into labels.
+    pub fn into_points(self) -> Vec<ProbeDataPoint> {
+        match self {
+            Self::Envelope(envelope) => envelope.into_points(),
+            Self::Legacy(batch) => relabel_indexed(batch.data)
+                .into_iter()
+                .map(|mut point| {
+                    if let ProbeValue::Text(text) = &point.probe_value {
+                        point.probe_value = ProbeValue::parse_legacy(text);
+                    }
+                    point
+                })
+                .collect(),
+        }
+    }
+}
+
+/// An object version 1 nodes numbered in metric names
+struct IndexedObject {
+    /// Name prefix up to the index, e.g. `disk_` in `disk_0_usage_percent`
+    prefix: &'static str,
+    /// Label taking the object's name, or its index without one
+    label: &'static str,
+    /// Prefix of the metric names since, e.g. `network_` for `network_interface_`
+    renamed: &'static str,
+    /// Metric holding the object's name, e.g. `mount_point` in `disk_0_mount_point`
+    identity: Option<&'static str>,
+}
+
+const INDEXED_OBJECTS: &[IndexedObject] = &[
+    IndexedObject {
+        prefix: "cpu_core_",
+        label: "core",
+        renamed: "cpu_core_",
+        identity: None,
+    },
+    IndexedObject {
+        prefix: "disk_",
+        label: "mount",
+        renamed: "disk_",
+        identity: Some("mount_point"),
+    },
+    IndexedObject {
+        prefix: "network_interface_",
+        label: "iface",
+        renamed: "network_",
+        identity: Some("name"),
+    },
+    IndexedObject {
+        prefix: "temperature_sensor_",
+        label: "sensor",
+        renamed: "temperature_",
+        identity: Some("label"),
+    },
+];
+
+/// The object, index and metric of a name such as `disk_0_usage_percent`
+fn split_indexed(name: &str) -> Option<(&'static IndexedObject, &str, &str)> {
+    INDEXED_OBJECTS.iter().find_map(|object| {
+        let (index, metric) = name.strip_prefix(object.prefix)?.split_once('_')?;
+        let numbered = !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit());
+        (numbered && !metric.is_empty()).then_some((object, index, metric))
+    })
+}
+
+/// Move the object index out of version 1 metric names into labels, as the
+/// collector's database migration does for stored readings:
+/// `disk_0_usage_percent` becomes `disk_usage_percent {mount = "/var"}`,
+/// the mount point taken from the `disk_0_mount_point` sent along, which
+/// is dropped.
+fn relabel_indexed(points: Vec<ProbeDataPoint>) -> Vec<ProbeDataPoint> {
+    let mut identities = HashMap::new();
+    for point in points.iter().filter(|p| p.labels.is_empty()) {
+        if let Some((object, index, metric)) = split_indexed(&point.probe_name)
+            && object.identity == Some(metric)
+            && let ProbeValue::Text(name) = &point.probe_value
+        {
+            let key = (&point.node_id, point.timestamp, object.prefix, index);
+            identities.insert(key, name.clone());
+        }
+    }
+
+    let mut relabeled = Vec::with_capacity(points.len());
+    for point in &points {
+        let split = point
+            .labels
+            .is_empty()
+            .then(|| split_indexed(&point.probe_name))
+            .flatten();
+        let Some((object, index, metric)) = split else {
+            relabeled.push(point.clone());
+            continue;
+        };
+        // Identity metrics are carried by the labels themselves
+        if object.identity == Some(metric) {
+            continue;
+        }
+
+        let key = (&point.node_id, point.timestamp, object.prefix, index);
+        let name = identities.get(&key).map_or(index, String::as_str);
+        let mut labels = point.labels.clone();
+        labels.insert(object.label.to_string(), name.to_string());
+        relabeled.push(ProbeDataPoint {
+            probe_name: format!("{}{}", object.renamed, metric),
+            labels,
+            ..point.clone()
+        });
+    }
+    relabeled
+}
+
+/// Collector `/health` response
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+    pub max_request_size_bytes: usize,
+    /// Collectors that predate negotiation omit this and only speak version 1
+    #[serde(default = "default_protocol_versions")]
+    pub protocol_versions: Vec<u32>,
+    /// Request body `Content-Type`s the collector can decode
+    #[serde(default = "default_content_types")]
+    pub content_types: Vec<String>,
+    /// Request body `Content-Encoding`s the collector can decompress
+    #[serde(default)]
+    pub content_encodings: Vec<String>,
+}
+
+fn default_protocol_versions() -> Vec<u32> {
+    vec![PROTOCOL_V1]
+}
+
+fn default_content_types() -> Vec<String> {
+    vec![CONTENT_TYPE_JSON.to_string()]
+}
+
+impl HealthResponse {
+    /// Highest protocol version supported by both sides
+    pub fn negotiate_protocol(&self) -> u32 {
+        self.protocol_versions
+            .iter()
+            .copied()
+            .filter(|v| SUPPORTED_PROTOCOL_VERSIONS.contains(v))
+            .max()
+            .unwrap_or(PROTOCOL_V1)
+    }
+
+    /// Most compact body encoding supported by both sides
+    ///
+    /// Version 1 batches are always JSON.
+    pub fn negotiate_wire_format(&self) -> WireFormat {
+        if self.negotiate_protocol() == PROTOCOL_V1 {
+            return WireFormat::Json;
+        }
+
+        SUPPORTED_WIRE_FORMATS
+            .iter()
+            .copied()
+            .find(|format| {
+                self.content_types
+                    .iter()
+                    .any(|ct| WireFormat::from_content_type(ct) == Some(*format))
+            })
+            .unwrap_or(WireFormat::Json)
+    }
+
+    /// `preferred` if the collector can decompress it, otherwise `None`
+    pub fn negotiate_compression(&self, preferred: Compression) -> Compression {
+        let supported = self
+            .content_encodings
+            .iter()
+            .any(|ce| Compression::from_content_encoding(ce) == Some(preferred));
+
+        if supported {
+            preferred
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Header carrying the claim returned on registration; only the node that
+/// registered knows it, so only that node can collect the credential
+pub const HEADER_REGISTRATION_CLAIM: &str = "x-ferrview-registration-claim";
+
+/// `POST /api/v1/register` request from a node without credentials
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegistrationRequest {
+    pub node_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub agent_version: String,
+}
+
+/// Where a registration stands
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl RegistrationStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// Response to a registration or to polling it
+///
+/// `claim` is only sent when the registration is created. `token` and
+/// `secret` are sent once, on the first poll after approval.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegistrationResponse {
+    pub registration_id: String,
+    pub status: RegistrationStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(name: &str, timestamp: i64) -> ProbeDataPoint {
+        ProbeDataPoint {
+            node_id: "node-1".to_string(),
+            timestamp,
+            probe_type: "sysinfo".to_string(),
+            probe_name: name.to_string(),
+            probe_value: ProbeValue::Integer(1),
+            labels: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let points = vec![
+            point("cpu_core_count", 1_704_110_400_000),
+            point("forks_total", 1_704_110_400_500),
+        ];
+
+        let envelope = BatchEnvelope::new("node-1", "0.6.0", 7, points.clone());
+        assert_eq!(envelope.collected_at, 1_704_110_400_000);
+        assert_eq!(envelope.points[0].timestamp, None);
+        assert_eq!(envelope.points[1].timestamp, Some(1_704_110_400_500));
+
+        assert_eq!(envelope.into_points(), points);
+    }
+
+    #[test]
+    fn test_envelope_omits_repeated_fields() {
+        let envelope = BatchEnvelope::new(
+            "node-1",
+            "0.6.0",
+            0,
+            vec![point("cpu_core_count", 1_704_110_400_000)],
+        );
+
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert_eq!(json.matches("node-1").count(), 1);
+        assert_eq!(json.matches("1704110400000").count(), 1);
+        assert!(!json.contains("tags"));
+    }
+
+    #[test]
+    fn test_envelope_tags() {
+        let tags = BTreeMap::from([
+            ("env".to_string(), "prod".to_string()),
+            ("role".to_string(), "web".to_string()),
+        ]);
+        let envelope = BatchEnvelope::new("node-1", "0.6.0", 0, vec![]).with_tags(tags.clone());
+
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert!(json.contains(r#""tags":{"env":"prod","role":"web"}"#));
+
+        let decoded: BatchEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.tags, tags);
+    }
+
+    #[test]
+    fn test_incoming_batch_accepts_both_formats() {
+        let legacy = r#"{"data": [{
+            "node_id": "node-1",
+            "timestamp": "2024-01-01T12:00:00Z",
+            "probe_type": "sysinfo",
+            "probe_name": "cpu_core_count",
+            "probe_value": "8"
+        }]}"#;
+        let batch: IncomingBatch = serde_json::from_str(legacy).unwrap();
+        assert!(matches!(batch, IncomingBatch::Legacy(_)));
+        let points = batch.into_points();
+        assert_eq!(points[0].node_id, "node-1");
+        assert_eq!(points[0].probe_value, ProbeValue::Integer(8));
+
+        let envelope = r#"{
+            "schema_version": 2,
+            "node_id": "node-2",
+            "agent_version": "0.6.0",
+            "collected_at": "2024-01-01T12:00:00Z",
+            "sequence": 3,
+            "points": [{
+                "probe_type": "sysinfo",
+                "probe_name": "cpu_core_count",
+                "probe_value": 8
+            }, {
+                "probe_type": "sysinfo",
+                "probe_name": "system_os_version",
+                "probe_value": "24.10"
+            }]
+        }"#;
+        let batch: IncomingBatch = serde_json::from_str(envelope).unwrap();
+        let points = batch.into_points();
+        assert_eq!(points[0].node_id, "node-2");
+        assert_eq!(points[0].timestamp, 1_704_110_400_000);
+        // Strings in an envelope are text, however numeric they look
+        assert_eq!(points[1].probe_value, ProbeValue::from("24.10"));
+    }
+
+    #[test]
+    fn test_legacy_indexed_names_become_labels() {
+        let legacy = r#"{"data": [
+            {"node_id": "n", "timestamp": 1, "probe_type": "sysinfo",
+             "probe_name": "disk_count", "probe_value": "1"},
+            {"node_id": "n", "timestamp": 1, "probe_type": "sysinfo",
+             "probe_name": "disk_0_mount_point", "probe_value": "/var"},
+            {"node_id": "n", "timestamp": 1, "probe_type": "sysinfo",
+             "probe_name": "disk_0_usage_percent", "probe_value": "41.5"},
+            {"node_id": "n", "timestamp": 1, "probe_type": "sysinfo",
+             "probe_name": "cpu_core_3_usage_percent", "probe_value": "7.5"},
+            {"node_id": "n", "timestamp": 1, "probe_type": "sysinfo",
+             "probe_name": "network_interface_0_name", "probe_value": "eth0"},
+            {"node_id": "n", "timestamp": 1, "probe_type": "sysinfo",
+             "probe_name": "network_interface_0_total_received_bytes", "probe_value": "1024"},
+            {"node_id": "n", "timestamp": 1, "probe_type": "sysinfo",
+             "probe_name": "temperature_sensor_1_celsius", "probe_value": "40"}
+        ]}"#;
+        let batch: IncomingBatch = serde_json::from_str(legacy).unwrap();
+        let points: Vec<(String, BTreeMap<String, String>, ProbeValue)> = batch
+            .into_points()
+            .into_iter()
+            .map(|p| (p.probe_name, p.labels, p.probe_value))
+            .collect();
+
+        let label = |k: &str, v: &str| BTreeMap::from([(k.to_string(), v.to_string())]);
+        assert_eq!(
+            points,
+            vec![
+                (
+                    "disk_count".to_string(),
+                    BTreeMap::new(),
+                    ProbeValue::Integer(1)
+                ),
+                (
+                    "disk_usage_percent".to_string(),
+                    label("mount", "/var"),
+                    ProbeValue::Float(41.5)
+                ),
+                (
+                    "cpu_core_usage_percent".to_string(),
+                    label("core", "3"),
+                    ProbeValue::Float(7.5)
+                ),
+                (
+                    "network_total_received_bytes".to_string(),
+                    label("iface", "eth0"),
+                    ProbeValue::Integer(1024)
+                ),
+                // Without a name the index stands in for it
+                (
+                    "temperature_celsius".to_string(),
+                    label("sensor", "1"),
+                    ProbeValue::Integer(40)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negotiate_protocol() {
+        let legacy: HealthResponse = serde_json::from_str(
+            r#"{"status":"healthy","version":"0.5.0","max_request_size_bytes":1024}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.negotiate_protocol(), PROTOCOL_V1);
+        assert_eq!(legacy.negotiate_wire_format(), WireFormat::Json);
+
+        let current = HealthResponse {
+            protocol_versions: vec![PROTOCOL_V1, PROTOCOL_V2, 99],
+            ..legacy
+        };
+        assert_eq!(current.negotiate_protocol(), PROTOCOL_V2);
+        assert_eq!(current.negotiate_wire_format(), WireFormat::Json);
+
+        let binary = HealthResponse {
+            content_types: vec![
+                CONTENT_TYPE_JSON.to_string(),
+                "application/msgpack".to_string(),
+            ],
+            ..current
+        };
+        assert_eq!(binary.negotiate_wire_format(), WireFormat::MessagePack);
+    }
+
+    #[test]
+    fn test_negotiate_compression() {
+        let legacy: HealthResponse = serde_json::from_str(
+            r#"{"status":"healthy","version":"0.5.0","max_request_size_bytes":1024}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            legacy.negotiate_compression(Compression::Zstd),
+            Compression::None
+        );
+
+        let current = HealthResponse {
+            content_encodings: vec!["gzip".to_string(), "zstd".to_string()],
+            ..legacy
+        };
+        assert_eq!(
+            current.negotiate_compression(Compression::Zstd),
+            Compression::Zstd
+        );
+        assert_eq!(
+            current.negotiate_compression(Compression::None),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn test_registration_response_wire_format() {
+        let pending: RegistrationResponse =
+            serde_json::from_str(r#"{"registration_id":"r1","status":"pending","claim":"c1"}"#)
+                .unwrap();
+        assert_eq!(pending.status, RegistrationStatus::Pending);
+        assert_eq!(pending.claim.as_deref(), Some("c1"));
+        assert!(pending.token.is_none());
+
+        let approved = RegistrationResponse {
+            registration_id: "r1".to_string(),
+            status: RegistrationStatus::Approved,
+            claim: None,
+            token: Some("fvt_1".to_string()),
+            secret: Some("s".to_string()),
+        };
+        let json = serde_json::to_string(&approved).unwrap();
+        assert!(json.contains(r#""status":"approved""#));
+        assert!(!json.contains("claim"));
+
+        for status in [
+            RegistrationStatus::Pending,
+            RegistrationStatus::Approved,
+            RegistrationStatus::Rejected,
+        ] {
+            assert_eq!(RegistrationStatus::parse(status.as_str()), Some(status));
+        }
+    }
+}
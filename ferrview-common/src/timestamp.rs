@@ -0,0 +1,135 @@
+// ferrview-common/src/timestamp.rs
+
+//! Timestamps are Unix epoch milliseconds (UTC) on the wire and in storage.
+//!
+//! Older nodes send RFC 3339 strings such as `2024-01-01T12:00:00Z`; the
+//! deserializers here accept both forms.
+
+use serde::Deserializer;
+use serde::de::{self, Visitor};
+use std::fmt;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// Current time in epoch milliseconds
+pub fn now_millis() -> i64 {
+    to_millis(OffsetDateTime::now_utc())
+}
+
+pub fn to_millis(dt: OffsetDateTime) -> i64 {
+    (dt.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// Convert epoch milliseconds back to a date-time, `None` if out of range
+pub fn from_millis(millis: i64) -> Option<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000).ok()
+}
+
+/// Parse an RFC 3339 timestamp into epoch milliseconds
+pub fn parse_rfc3339(s: &str) -> Option<i64> {
+    OffsetDateTime::parse(s, &Rfc3339).ok().map(to_millis)
+}
+
+/// Deserialize epoch milliseconds from an integer or an RFC 3339 string
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    deserializer.deserialize_any(MillisVisitor)
+}
+
+/// Like [`deserialize`], for optional fields
+pub fn deserialize_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i64>, D::Error> {
+    deserializer.deserialize_option(OptionMillisVisitor)
+}
+
+struct MillisVisitor;
+
+impl Visitor<'_> for MillisVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("epoch milliseconds or an RFC 3339 timestamp")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+        Ok(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<i64, E> {
+        i64::try_from(v).map_err(|_| E::custom("timestamp out of range"))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<i64, E> {
+        parse_rfc3339(v).ok_or_else(|| E::custom(format!("invalid timestamp: {}", v)))
+    }
+}
+
+struct OptionMillisVisitor;
+
+impl<'de> Visitor<'de> for OptionMillisVisitor {
+    type Value = Option<i64>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an optional timestamp")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<i64>, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<i64>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<i64>, D::Error> {
+        deserialize(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Stamped {
+        #[serde(deserialize_with = "deserialize")]
+        at: i64,
+        #[serde(default, deserialize_with = "deserialize_option")]
+        until: Option<i64>,
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(
+            parse_rfc3339("2024-01-01T12:00:00Z"),
+            Some(1_704_110_400_000)
+        );
+        assert_eq!(
+            parse_rfc3339("2024-01-01T12:00:00.250Z"),
+            Some(1_704_110_400_250)
+        );
+        assert_eq!(parse_rfc3339("yesterday"), None);
+    }
+
+    #[test]
+    fn test_deserialize_both_forms() {
+        let s: Stamped = serde_json::from_str(r#"{"at": 1704110400000}"#).unwrap();
+        assert_eq!(s.at, 1_704_110_400_000);
+        assert_eq!(s.until, None);
+
+        let s: Stamped = serde_json::from_str(
+            r#"{"at": "2024-01-01T12:00:00Z", "until": "2024-01-01T12:00:01Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(s.at, 1_704_110_400_000);
+        assert_eq!(s.until, Some(1_704_110_401_000));
+
+        assert!(serde_json::from_str::<Stamped>(r#"{"at": "noon"}"#).is_err());
+    }
+
+    #[test]
+    fn test_now_is_after_2024() {
+        assert!(now_millis() > 1_704_067_200_000);
+    }
+}
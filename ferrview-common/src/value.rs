@@ -0,0 +1,243 @@
+// ferrview-common/src/value.rs
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Typed value of a single probe reading
+///
+/// On the wire a value is a plain JSON scalar (number, boolean or string),
+/// and a string is always `Text`. Older nodes send every value as a string;
+/// their version 1 batches go through [`ProbeValue::parse_legacy`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeValue {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Text(String),
+}
+
+impl ProbeValue {
+    /// Parse a legacy string value, inferring the numeric type when possible
+    pub fn parse_legacy(s: &str) -> Self {
+        if let Ok(i) = s.parse::<i64>() {
+            return Self::Integer(i);
+        }
+        if let Ok(f) = s.parse::<f64>()
+            && f.is_finite()
+        {
+            return Self::Float(f);
+        }
+        match s {
+            "true" => Self::Boolean(true),
+            "false" => Self::Boolean(false),
+            _ => Self::Text(s.to_string()),
+        }
+    }
+
+    /// Numeric view of the value, if it has one
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Integer(i) => Some(*i as f64),
+            Self::Float(f) => Some(*f),
+            Self::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Self::Text(_) => None,
+        }
+    }
+
+    /// Integer view of the value, if it is an integer
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Text view of the value, if it is text
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Short type tag, used as the storage discriminator
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Integer(_) => "int",
+            Self::Float(_) => "float",
+            Self::Boolean(_) => "bool",
+            Self::Text(_) => "text",
+        }
+    }
+}
+
+impl fmt::Display for ProbeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(i) => write!(f, "{}", i),
+            Self::Float(v) => write!(f, "{}", v),
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+impl From<i64> for ProbeValue {
+    fn from(v: i64) -> Self {
+        Self::Integer(v)
+    }
+}
+
+impl From<u64> for ProbeValue {
+    fn from(v: u64) -> Self {
+        i64::try_from(v).map_or(Self::Float(v as f64), Self::Integer)
+    }
+}
+
+impl From<usize> for ProbeValue {
+    fn from(v: usize) -> Self {
+        Self::from(v as u64)
+    }
+}
+
+impl From<f64> for ProbeValue {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<f32> for ProbeValue {
+    fn from(v: f32) -> Self {
+        // Go through the decimal representation so 42.1f32 stays 42.1
+        Self::Float(v.to_string().parse().unwrap_or(v as f64))
+    }
+}
+
+impl From<bool> for ProbeValue {
+    fn from(v: bool) -> Self {
+        Self::Boolean(v)
+    }
+}
+
+impl From<String> for ProbeValue {
+    fn from(v: String) -> Self {
+        Self::Text(v)
+    }
+}
+
+impl From<&str> for ProbeValue {
+    fn from(v: &str) -> Self {
+        Self::Text(v.to_string())
+    }
+}
+
+impl Serialize for ProbeValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Integer(i) => serializer.serialize_i64(*i),
+            Self::Float(f) => serializer.serialize_f64(*f),
+            Self::Boolean(b) => serializer.serialize_bool(*b),
+            Self::Text(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ProbeValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ProbeValueVisitor)
+    }
+}
+
+struct ProbeValueVisitor;
+
+impl Visitor<'_> for ProbeValueVisitor {
+    type Value = ProbeValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a number, boolean or string")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<ProbeValue, E> {
+        Ok(ProbeValue::Integer(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<ProbeValue, E> {
+        Ok(ProbeValue::from(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<ProbeValue, E> {
+        Ok(ProbeValue::Float(v))
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<ProbeValue, E> {
+        Ok(ProbeValue::Boolean(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ProbeValue, E> {
+        Ok(ProbeValue::Text(v.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_as_json_scalars() {
+        assert_eq!(serde_json::to_string(&ProbeValue::Integer(8)).unwrap(), "8");
+        assert_eq!(
+            serde_json::to_string(&ProbeValue::Float(1.5)).unwrap(),
+            "1.5"
+        );
+        assert_eq!(
+            serde_json::to_string(&ProbeValue::Boolean(true)).unwrap(),
+            "true"
+        );
+        assert_eq!(
+            serde_json::to_string(&ProbeValue::from("x86_64")).unwrap(),
+            "\"x86_64\""
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy() {
+        assert_eq!(
+            ProbeValue::parse_legacy("16777216"),
+            ProbeValue::Integer(16777216)
+        );
+        assert_eq!(ProbeValue::parse_legacy("42.25"), ProbeValue::Float(42.25));
+        assert_eq!(ProbeValue::parse_legacy("true"), ProbeValue::Boolean(true));
+        assert_eq!(ProbeValue::parse_legacy("Linux"), ProbeValue::from("Linux"));
+
+        // NaN and inf are not useful as numbers, keep them as text
+        assert_eq!(ProbeValue::parse_legacy("NaN"), ProbeValue::from("NaN"));
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        for text in ["24.10", "007", "12345", "true"] {
+            let value = ProbeValue::from(text);
+
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<ProbeValue>(&json).unwrap(), value);
+
+            let packed = rmp_serde::to_vec(&value).unwrap();
+            assert_eq!(rmp_serde::from_slice::<ProbeValue>(&packed).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_from_conversions() {
+        assert_eq!(ProbeValue::from(8usize), ProbeValue::Integer(8));
+        assert_eq!(ProbeValue::from(u64::MAX).kind(), "float");
+        assert_eq!(ProbeValue::from(42.1f32), ProbeValue::Float(42.1));
+    }
+
+    #[test]
+    fn test_as_f64() {
+        assert_eq!(ProbeValue::Integer(3).as_f64(), Some(3.0));
+        assert_eq!(ProbeValue::Boolean(true).as_f64(), Some(1.0));
+        assert_eq!(ProbeValue::from("eth0").as_f64(), None);
+    }
+}
@@ -0,0 +1,150 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Batches that must fail in a row before a collector is left alone
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long a collector is left alone the first time; doubled each time
+/// the trial batch after it fails too
+const MIN_COOLDOWN: Duration = Duration::from_secs(15);
+/// Longest a collector is left alone, whatever its `Retry-After` says
+pub const MAX_COOLDOWN: Duration = Duration::from_secs(300);
+
+/// Whether batches may go to a collector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    /// Send as usual
+    Closed,
+    /// The cooldown is over; one batch may try whether the collector is back
+    HalfOpen,
+    /// Send nothing for this long
+    Open(Duration),
+}
+
+/// Stops a node from hammering a collector that is down. After a few failed
+/// batches in a row nothing is sent to it for a cooldown (or for as long as
+/// its `Retry-After` asked, if longer); then a single batch is let through,
+/// closing the circuit on success and reopening it for longer on failure.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    consecutive_failures: u32,
+    cooldown: Duration,
+    open_until: Option<Instant>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            consecutive_failures: 0,
+            cooldown: MIN_COOLDOWN,
+            open_until: None,
+        }
+    }
+}
+
+impl CircuitBreaker {
+    pub fn state(&self, now: Instant) -> CircuitState {
+        match self.open_until {
+            None => CircuitState::Closed,
+            Some(until) if now < until => CircuitState::Open(until - now),
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Count a failed batch, returning how long the circuit opens for if
+    /// this failure opened it
+    pub fn record_failure(
+        &mut self,
+        now: Instant,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        self.consecutive_failures += 1;
+
+        let cooldown = match self.state(now) {
+            // The trial batch failed too
+            CircuitState::HalfOpen => {
+                self.cooldown = (self.cooldown * 2).min(MAX_COOLDOWN);
+                self.cooldown
+            }
+            CircuitState::Closed
+                if self.consecutive_failures >= FAILURE_THRESHOLD || retry_after.is_some() =>
+            {
+                self.cooldown
+            }
+            _ => return None,
+        };
+
+        let retry_after = retry_after.unwrap_or_default().min(MAX_COOLDOWN);
+        let cooldown = cooldown.max(retry_after);
+        self.open_until = Some(now.checked_add(cooldown).unwrap_or(now));
+        Some(cooldown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_opens_after_threshold() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+
+        for _ in 1..FAILURE_THRESHOLD {
+            assert_eq!(breaker.record_failure(now, None), None);
+            assert_eq!(breaker.state(now), CircuitState::Closed);
+        }
+        assert_eq!(breaker.record_failure(now, None), Some(MIN_COOLDOWN));
+        assert_eq!(breaker.state(now), CircuitState::Open(MIN_COOLDOWN));
+
+        // After the cooldown one batch may try, and its failure doubles it
+        tokio::time::advance(MIN_COOLDOWN).await;
+        let later = Instant::now();
+        assert_eq!(breaker.state(later), CircuitState::HalfOpen);
+        assert_eq!(breaker.record_failure(later, None), Some(MIN_COOLDOWN * 2));
+        assert!(matches!(breaker.state(later), CircuitState::Open(_)));
+
+        breaker.record_success();
+        assert_eq!(breaker.state(later), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_honors_retry_after() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+        let wait = Duration::from_secs(120);
+
+        assert_eq!(breaker.record_failure(now, Some(wait)), Some(wait));
+        assert_eq!(breaker.state(now), CircuitState::Open(wait));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_huge_retry_after_is_capped() {
+        let mut breaker = CircuitBreaker::default();
+        let wait = Duration::from_secs(u64::MAX);
+
+        assert_eq!(
+            breaker.record_failure(Instant::now(), Some(wait)),
+            Some(MAX_COOLDOWN)
+        );
+        tokio::time::advance(MAX_COOLDOWN).await;
+        assert_eq!(breaker.state(Instant::now()), CircuitState::HalfOpen);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cooldown_is_capped() {
+        let mut breaker = CircuitBreaker::default();
+
+        for _ in 0..FAILURE_THRESHOLD + 10 {
+            if let Some(cooldown) = breaker.record_failure(Instant::now(), None) {
+                assert!(cooldown <= MAX_COOLDOWN);
+                tokio::time::advance(cooldown).await;
+            }
+        }
+        assert_eq!(breaker.cooldown, MAX_COOLDOWN);
+    }
+}
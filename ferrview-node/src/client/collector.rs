@@ -0,0 +1,86 @@
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use super::breaker::{CircuitBreaker, CircuitState};
+use super::http::{ClientError, HttpClient};
+
+/// A collector batches are delivered to, and how delivery to it is going
+pub struct Collector {
+    pub name: String,
+    pub client: HttpClient,
+    status: DeliveryStatus,
+    breaker: CircuitBreaker,
+}
+
+/// Delivery to one collector since the node started
+#[derive(Debug, Default, Clone)]
+pub struct DeliveryStatus {
+    pub batches_sent: u64,
+    pub batches_failed: u64,
+    /// Batches that failed since the last one that went through
+    pub consecutive_failures: u64,
+    pub last_error: Option<String>,
+}
+
+impl Collector {
+    pub fn new(name: &str, client: HttpClient) -> Self {
+        Self {
+            name: name.to_string(),
+            client,
+            status: DeliveryStatus::default(),
+            breaker: CircuitBreaker::default(),
+        }
+    }
+
+    pub fn status(&self) -> &DeliveryStatus {
+        &self.status
+    }
+
+    /// How many attempts the next batch gets: `retries` as usual, a single
+    /// one to find out whether a collector that was down is back, or none
+    /// while it is left alone, which counts as a failed batch
+    pub fn admit(&mut self, retries: u32) -> Result<u32, ClientError> {
+        match self.breaker.state(Instant::now()) {
+            CircuitState::Closed => Ok(retries),
+            CircuitState::HalfOpen => Ok(1),
+            CircuitState::Open(remaining) => {
+                self.status.batches_failed += 1;
+                Err(ClientError::CircuitOpen(remaining))
+            }
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        if self.status.consecutive_failures > 0 {
+            info!(
+                "Collector {} is reachable again after {} failed batch(es)",
+                self.name, self.status.consecutive_failures
+            );
+        }
+        self.status.batches_sent += 1;
+        self.status.consecutive_failures = 0;
+        self.breaker.record_success();
+    }
+
+    pub fn record_failure(&mut self, error: &ClientError) {
+        if self.status.consecutive_failures == 0 {
+            warn!("Collector {} is failing: {}", self.name, error);
+        }
+        self.status.batches_failed += 1;
+        self.status.consecutive_failures += 1;
+        self.status.last_error = Some(error.to_string());
+
+        // A collector refusing a request is up; only being unable to
+        // reach it, or it being overloaded, keeps batches away from it
+        if error.is_retryable()
+            && let Some(cooldown) = self
+                .breaker
+                .record_failure(Instant::now(), error.retry_after())
+        {
+            warn!(
+                "Not sending to collector {} for {:?} after {} failed batch(es)",
+                self.name, cooldown, self.status.consecutive_failures
+            );
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use ferrview_common::ProbeDataPoint;
+use ferrview_common::protocol::RegistrationStatus;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{self, Receiver, UnboundedReceiver};
+use tracing::{debug, error, info, warn};
+
+use super::collector::Collector;
+use super::http::{ClientError, HttpClient};
+use super::registration::Registration;
+use super::retry::send_with_retry;
+use super::spool::Spool;
+
+/// Attempts per batch and collector while the collector is healthy
+const RETRIES: u32 = 3;
+
+/// Batches waiting for delivery; while sending is stuck, batches beyond
+/// these go to the spool, or are dropped without one
+pub const QUEUE_CAPACITY: usize = 64;
+
+/// Queued batches sent together at most
+const MAX_MERGED_BATCHES: usize = 8;
+
+/// A route's spool, shared by its delivery task and the [`Queue`] feeding it
+pub type SharedSpool = Arc<Mutex<Spool>>;
+
+/// Where collected batches enter a route
+pub struct Queue {
+    batches: mpsc::Sender<Vec<ProbeDataPoint>>,
+    spool: Option<SharedSpool>,
+}
+
+impl Queue {
+    /// A queue, and its end to hand to [`deliver`] along with `spool`
+    pub fn new(spool: Option<SharedSpool>) -> (Self, Receiver<Vec<ProbeDataPoint>>) {
+        let (batches, queue) = mpsc::channel(QUEUE_CAPACITY);
+        (Self { batches, spool }, queue)
+    }
+
+    /// Queue a batch without waiting; `false` once the delivery task is
+    /// gone. When sending is stuck and the queue is full, the batch is
+    /// spooled right away.
+    pub fn push(&self, data: Vec<ProbeDataPoint>) -> bool {
+        match self.batches.try_send(data) {
+            Ok(()) => true,
+            Err(TrySendError::Full(data)) => {
+                match &self.spool {
+                    Some(spool) => match spool.lock().unwrap().push(&data) {
+                        Ok(()) => debug!("Delivery fell behind, spooled a batch"),
+                        Err(e) => error!("Failed to spool batch, dropping it: {}", e),
+                    },
+                    None => warn!("Delivery fell behind, dropping a batch"),
+                }
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Send collected readings as they arrive, until the queue is closed.
+/// Readings that queued up while a previous send was in flight go out
+/// together, up to [`MAX_MERGED_BATCHES`] at a time.
+///
+/// Each batch goes to the first of `collectors` that takes it. Fanning
+/// out to several collectors runs one of these per collector.
+///
+/// With a spool, batches that cannot be delivered, or that do not fit the
+/// [`Queue`] while a send is stuck, are written to disk and replayed oldest
+/// first before anything newer is sent. Batches the
+/// collector refuses as invalid are dropped rather than kept.
+///
+/// With a registration, nothing is sent until the collector has approved
+/// it; batches collected meanwhile are spooled, or dropped without a spool.
+///
+/// Clients arriving on `reloads`, built from a reloaded config, replace
+/// those of `collectors` in order, between batches; queued and spooled
+/// batches go out through the new ones.
+pub async fn deliver(
+    mut collectors: Vec<Collector>,
+    spool: Option<SharedSpool>,
+    mut registration: Option<Registration>,
+    mut queue: Receiver<Vec<ProbeDataPoint>>,
+    mut reloads: UnboundedReceiver<Vec<HttpClient>>,
+) {
+    let mut sequence: u64 = 0;
+    let mut status = match registration {
+        Some(_) => RegistrationStatus::Pending,
+        None => RegistrationStatus::Approved,
+    };
+
+    // Batches left over from a previous run go first
+    if status == RegistrationStatus::Approved
+        && let Some(spool) = &spool
+    {
+        replay(&mut collectors, spool, &mut sequence).await;
+    }
+
+    loop {
+        let received = tokio::select! {
+            data = queue.recv() => data,
+            Some(clients) = reloads.recv() => {
+                for (collector, client) in collectors.iter_mut().zip(clients) {
+                    collector.client = client;
+                }
+                info!("Switched to the reloaded collector settings");
+                if status == RegistrationStatus::Approved
+                    && let Some(spool) = &spool
+                {
+                    replay(&mut collectors, spool, &mut sequence).await;
+                }
+                continue;
+            }
+            _ = tick(&mut registration) => {
+                let Some(pending) = &mut registration else {
+                    continue;
+                };
+                // Registration is only allowed with a single collector
+                status = pending.check(&collectors[0].client).await;
+                match status {
+                    RegistrationStatus::Pending => {}
+                    RegistrationStatus::Approved => {
+                        registration = None;
+                        if let Some(spool) = &spool {
+                            replay(&mut collectors, spool, &mut sequence).await;
+                        }
+                    }
+                    // There is nothing more to ask; batches are dropped
+                    RegistrationStatus::Rejected => registration = None,
+                }
+                continue;
+            }
+        };
+        let Some(mut data) = received else {
+            break;
+        };
+        for _ in 1..MAX_MERGED_BATCHES {
+            match queue.try_recv() {
+                Ok(more) => data.extend(more),
+                Err(_) => break,
+            }
+        }
+
+        if status != RegistrationStatus::Approved {
+            hold(spool.as_deref(), &data, status);
+            continue;
+        }
+
+        match &spool {
+            Some(spool) if !spool.lock().unwrap().is_empty() => {
+                // Queue behind the spooled batches to keep delivery in order
+                if let Err(e) = spool.lock().unwrap().push(&data) {
+                    error!("Failed to spool batch, dropping it: {}", e);
+                }
+                replay(&mut collectors, spool, &mut sequence).await;
+            }
+            _ => {
+                info!("Sending {} metrics", data.len());
+                match send(&mut collectors, &data, &mut sequence).await {
+                    Ok(name) => info!("Batch sent to {}", name),
+                    Err(e) if e.is_rejection() => {
+                        error!("Collector rejected batch, dropping it: {}", e)
+                    }
+                    Err(e) => {
+                        error!("Failed to send batch after retries: {}", e);
+                        if let Some(spool) = &spool {
+                            match spool.lock().unwrap().push(&data) {
+                                Ok(()) => info!("Spooled batch for later delivery"),
+                                Err(e) => error!("Failed to spool batch, dropping it: {}", e),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for collector in &collectors {
+        let status = collector.status();
+        info!(
+            "Collector {}: {} batch(es) sent, {} failed{}",
+            collector.name,
+            status.batches_sent,
+            status.batches_failed,
+            status
+                .last_error
+                .as_ref()
+                .map(|e| format!(", last error: {}", e))
+                .unwrap_or_default()
+        );
+    }
+    if let Some(spool) = &spool {
+        let spooled = spool.lock().unwrap().len();
+        if spooled > 0 {
+            info!("{} batch(es) remain spooled", spooled);
+        }
+    }
+}
+
+/// Wait for the next registration check; never without a registration
+async fn tick(registration: &mut Option<Registration>) {
+    match registration {
+        Some(pending) => pending.tick().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Keep a batch that cannot be sent before the registration is approved
+fn hold(spool: Option<&Mutex<Spool>>, data: &[ProbeDataPoint], status: RegistrationStatus) {
+    match (spool, status) {
+        (Some(spool), RegistrationStatus::Pending) => match spool.lock().unwrap().push(data) {
+            Ok(()) => info!("Spooled batch until the registration is approved"),
+            Err(e) => error!("Failed to spool batch, dropping it: {}", e),
+        },
+        (None, RegistrationStatus::Pending) => {
+            warn!("Dropping batch, the registration is awaiting approval")
+        }
+        _ => warn!("Dropping batch, the node has no credentials for the collector"),
+    }
+}
+
+/// Deliver spooled batches oldest first, stopping at the first failure.
+/// The spool is only locked in between sends, so batches can be spooled
+/// while one is under way.
+async fn replay(collectors: &mut [Collector], spool: &Mutex<Spool>, sequence: &mut u64) {
+    loop {
+        let front = spool.lock().unwrap().front();
+        let data = match front {
+            Ok(Some(data)) => data,
+            Ok(None) => break,
+            Err(e) => {
+                error!("Failed to read spool: {}", e);
+                break;
+            }
+        };
+
+        match send(collectors, &data, sequence).await {
+            Ok(_) => info!("Replayed spooled batch of {} metrics", data.len()),
+            Err(e) if e.is_rejection() => {
+                error!("Collector rejected spooled batch, dropping it: {}", e)
+            }
+            // Pausing was logged by the collector; no need to repeat it per batch
+            Err(ClientError::CircuitOpen(_)) => {
+                debug!(
+                    "Collectors paused, {} batch(es) remain spooled",
+                    spool.lock().unwrap().len()
+                );
+                break;
+            }
+            Err(e) => {
+                let names: Vec<&str> = collectors.iter().map(|c| c.name.as_str()).collect();
+                warn!(
+                    "Collector {} unreachable, {} batch(es) remain spooled: {}",
+                    names.join(", "),
+                    spool.lock().unwrap().len(),
+                    e
+                );
+                break;
+            }
+        }
+
+        let popped = spool.lock().unwrap().pop();
+        if let Err(e) = popped {
+            error!("Failed to remove delivered batch from spool: {}", e);
+            break;
+        }
+    }
+}
+
+/// Send a batch to the first collector that takes it, returning its name.
+/// Collectors that are being left alone after failing are skipped.
+async fn send<'a>(
+    collectors: &'a mut [Collector],
+    data: &[ProbeDataPoint],
+    sequence: &mut u64,
+) -> Result<&'a str, ClientError> {
+    let mut result = Err(ClientError::Http("no collector configured".to_string()));
+
+    for collector in collectors.iter_mut() {
+        let attempts = match collector.admit(RETRIES) {
+            Ok(attempts) => attempts,
+            Err(e) => {
+                result = Err(e);
+                continue;
+            }
+        };
+        let client = &collector.client;
+        match send_with_retry(|| client.send_batch(data.to_vec(), *sequence), attempts).await {
+            Ok(()) => {
+                collector.record_success();
+                result = Ok(collector.name.as_str());
+                break;
+            }
+            Err(e) => {
+                collector.record_failure(&e);
+                result = Err(e);
+            }
+        }
+    }
+
+    *sequence += 1;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    fn batch(value: usize) -> Vec<ProbeDataPoint> {
+        vec![ProbeDataPoint {
+            node_id: "node".to_string(),
+            timestamp: 1_704_110_400_000,
+            probe_type: "sysinfo".to_string(),
+            probe_name: "cpu_count".to_string(),
+            probe_value: (value as u64).into(),
+            labels: BTreeMap::new(),
+        }]
+    }
+
+    fn value(data: &[ProbeDataPoint]) -> Option<i64> {
+        data[0].probe_value.as_i64()
+    }
+
+    #[tokio::test]
+    async fn test_stalled_route_spools_what_does_not_fit() {
+        let dir = TestDir::new("delivery-stalled");
+        let spool = Spool::open(dir.path(), u64::MAX, Duration::from_secs(3600)).unwrap();
+        let spool = Arc::new(Mutex::new(spool));
+
+        // Nothing reads this route's queue, as while its sender is stuck
+        // retrying an unreachable collector
+        let (stalled, mut stalled_queue) = Queue::new(Some(Arc::clone(&spool)));
+        let (healthy, mut healthy_queue) = Queue::new(None);
+        let drained = tokio::spawn(async move {
+            let mut received = Vec::new();
+            while let Some(data) = healthy_queue.recv().await {
+                received.push(value(&data).unwrap());
+            }
+            received
+        });
+
+        let batches = QUEUE_CAPACITY + 5;
+        for i in 0..batches {
+            assert!(stalled.push(batch(i)));
+            assert!(healthy.push(batch(i)));
+            tokio::task::yield_now().await;
+        }
+
+        // The other route got everything
+        drop(healthy);
+        let received = drained.await.unwrap();
+        assert_eq!(received, (0..batches as i64).collect::<Vec<_>>());
+
+        // The stalled one kept what fits queued and spooled the rest
+        assert_eq!(value(&stalled_queue.recv().await.unwrap()), Some(0));
+        let mut spool = spool.lock().unwrap();
+        assert_eq!(spool.len(), 5);
+        assert_eq!(
+            value(&spool.front().unwrap().unwrap()),
+            Some(QUEUE_CAPACITY as i64)
+        );
+        drop(spool);
+
+        drop(stalled_queue);
+        assert!(!stalled.push(batch(0)));
+    }
+}
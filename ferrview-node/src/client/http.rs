@@ -1,26 +1,86 @@
 use bytes::Bytes;
-use ferrview_common::ProbeDataPoint;
-use http_body_util::Full;
+use ferrview_common::codec::{Compression, WireFormat};
+use ferrview_common::protocol::{
+    BatchEnvelope, HEADER_REGISTRATION_CLAIM, HealthResponse, PROTOCOL_V1, ProbeDataBatch,
+    RegistrationRequest, RegistrationResponse,
+};
+use ferrview_common::signing::{
+    HEADER_NODE_ID, HEADER_NONCE, HEADER_SIGNATURE, HEADER_TIMESTAMP, Signature,
+};
+use ferrview_common::{ProbeDataPoint, error_chain};
+use http_body_util::{BodyExt, Full};
+use hyper::header::{AUTHORIZATION, HeaderMap, HeaderValue, RETRY_AFTER};
 use hyper::{Request, StatusCode};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
 use hyper_util::{
     client::legacy::{Client, connect::HttpConnector},
     rt::TokioExecutor,
 };
-use serde::Serialize;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
-use tracing::{debug, error};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc2822;
+use tracing::{debug, error, info, warn};
 
-#[derive(Debug, Serialize)]
-struct ProbeDataBatch {
-    data: Vec<ProbeDataPoint>,
-}
+use super::breaker::MAX_COOLDOWN;
+use super::tls;
+use crate::config::{AuthConfig, TlsConfig};
+use crate::utils::timestamp::get_utc_timestamp;
+
+const AGENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Debug)]
 pub enum ClientError {
     Http(String),
     Serialization(String),
     InvalidResponse(StatusCode),
+    /// The collector is overloaded and said when to try again
+    Throttled(StatusCode, Duration),
+    /// Nothing was sent; the collector kept failing and is left alone
+    /// for the given time
+    CircuitOpen(Duration),
+    Tls(String),
+}
+
+impl ClientError {
+    /// Whether the same request may succeed if sent again. Client errors
+    /// other than 408 and 429, TLS setup and encoding will not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Http(_) | ClientError::Throttled(..) | ClientError::CircuitOpen(_) => true,
+            ClientError::InvalidResponse(status) => {
+                *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+                    || status.is_server_error()
+            }
+            ClientError::Serialization(_) | ClientError::Tls(_) => false,
+        }
+    }
+
+    /// Whether the collector refused the batch itself, so no collector
+    /// will ever take it and keeping it for later is pointless
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            ClientError::InvalidResponse(
+                StatusCode::BAD_REQUEST
+                    | StatusCode::PAYLOAD_TOO_LARGE
+                    | StatusCode::UNPROCESSABLE_ENTITY
+            )
+        )
+    }
+
+    /// How long the collector asked us to wait before trying again
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ClientError::Throttled(_, wait) => Some(*wait),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ClientError {
@@ -31,71 +91,725 @@ impl fmt::Display for ClientError {
             ClientError::InvalidResponse(status) => {
                 write!(f, "Invalid response status: {}", status)
             }
+            ClientError::Throttled(status, wait) => {
+                write!(f, "Collector busy ({}), retry after {:?}", status, wait)
+            }
+            ClientError::CircuitOpen(wait) => {
+                write!(f, "Collector paused after repeated failures for {:?}", wait)
+            }
+            ClientError::Tls(msg) => write!(f, "TLS error: {}", msg),
         }
     }
 }
 
 impl Error for ClientError {}
 
-pub struct HttpClient {
-    client: Client<HttpConnector, Full<Bytes>>,
-    collector_url: String,
+/// What the collector told us about itself on `/health`
+#[derive(Debug, Clone, Copy)]
+struct Session {
+    protocol: u32,
+    format: WireFormat,
+    compression: Compression,
+    max_request_size: usize,
 }
 
-impl HttpClient {
-    pub fn new(collector_addr: &str) -> Self {
-        let connector = HttpConnector::new();
+/// Credentials sent with each batch. They can be replaced at runtime,
+/// once a registration has been approved.
+#[derive(Default)]
+struct Credentials {
+    authorization: Option<HeaderValue>,
+    secret: Option<String>,
+}
 
-        let client = Client::builder(TokioExecutor::new())
-            .http2_only(false) // Allow HTTP/1.1 fallback
-            .build(connector);
+/// How much of a batch the collector has already taken when one of the
+/// requests it was split into failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Progress {
+    /// [`fingerprint`] of the whole batch
+    batch: u64,
+    /// Leading points of the batch that were sent
+    points: usize,
+}
 
+impl Credentials {
+    fn new(token: Option<&str>, secret: Option<&str>) -> Result<Self, ClientError> {
+        let authorization = match token {
+            Some(token) => {
+                let mut value = HeaderValue::from_str(&format!("Bearer {}", token.trim()))
+                    .map_err(|_| ClientError::Http("invalid characters in auth.token".into()))?;
+                value.set_sensitive(true);
+                Some(value)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            authorization,
+            secret: secret.map(str::to_string),
+        })
+    }
+}
+
+pub struct HttpClient {
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    base_url: String,
+    node_id: String,
+    tags: BTreeMap<String, String>,
+    compression: Compression,
+    credentials: RwLock<Credentials>,
+    session: Mutex<Option<Session>>,
+    progress: Mutex<Option<Progress>>,
+}
+
+impl HttpClient {
+    /// Client for the collector at `collector_addr`; plain HTTP unless the
+    /// address starts with `https://`
+    pub fn new(
+        collector_addr: &str,
+        node_id: &str,
+        tags: &BTreeMap<String, String>,
+        compression: Compression,
+        tls: &TlsConfig,
+        auth: &AuthConfig,
+    ) -> Result<Self, ClientError> {
         // Ensure URL has http:// prefix
-        let collector_url =
+        let base_url =
             if collector_addr.starts_with("http://") || collector_addr.starts_with("https://") {
-                format!("{}/api/v1/probe", collector_addr)
+                collector_addr.trim_end_matches('/').to_string()
             } else {
-                format!("http://{}/api/v1/probe", collector_addr)
+                format!("http://{}", collector_addr.trim_end_matches('/'))
             };
 
-        debug!("HTTP client initialized for: {}", collector_url);
+        if !base_url.starts_with("https://") && tls.is_configured() {
+            warn!(
+                "TLS settings are ignored for plain HTTP collector {}",
+                base_url
+            );
+        }
+
+        let credentials = Credentials::new(auth.token.as_deref(), auth.secret.as_deref())?;
+
+        let connector = HttpsConnectorBuilder::new()
+            .with_tls_config(tls::client_config(tls)?)
+            .https_or_http()
+            .enable_http1()
+            .enable_http2()
+            .build();
 
-        Self {
+        let client = Client::builder(TokioExecutor::new())
+            .http2_only(false) // Allow HTTP/1.1 fallback
+            .build(connector);
+
+        debug!("HTTP client initialized for: {}", base_url);
+
+        Ok(Self {
             client,
-            collector_url,
-        }
+            base_url,
+            node_id: node_id.to_string(),
+            tags: tags.clone(),
+            compression,
+            credentials: RwLock::new(credentials),
+            session: Mutex::new(None),
+            progress: Mutex::new(None),
+        })
     }
 
-    pub async fn send_batch(&self, data: Vec<ProbeDataPoint>) -> Result<(), ClientError> {
-        let batch = ProbeDataBatch { data };
-        let count = batch.data.len();
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
 
-        debug!("Sending batch of {} probe data points", count);
+    /// Whether batches go out with a token or a signature
+    pub fn has_credentials(&self) -> bool {
+        let credentials = self.credentials.read().unwrap();
+        credentials.authorization.is_some() || credentials.secret.is_some()
+    }
 
-        let json =
-            serde_json::to_string(&batch).map_err(|e| ClientError::Serialization(e.to_string()))?;
+    /// Send batches with these credentials from now on
+    pub fn set_credentials(
+        &self,
+        token: Option<&str>,
+        secret: Option<&str>,
+    ) -> Result<(), ClientError> {
+        *self.credentials.write().unwrap() = Credentials::new(token, secret)?;
+        Ok(())
+    }
 
+    /// Ask the collector for credentials for this node
+    pub async fn register(
+        &self,
+        request: &RegistrationRequest,
+    ) -> Result<RegistrationResponse, ClientError> {
+        let body =
+            serde_json::to_vec(request).map_err(|e| ClientError::Serialization(e.to_string()))?;
         let request = Request::builder()
             .method("POST")
-            .uri(&self.collector_url)
+            .uri(format!("{}/api/v1/register", self.base_url))
             .header("Content-Type", "application/json")
-            .body(Full::new(Bytes::from(json)))
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| ClientError::Http(e.to_string()))?;
+
+        self.fetch_json(request, StatusCode::ACCEPTED).await
+    }
+
+    /// Current state of a registration made with [`Self::register`]
+    pub async fn registration_status(
+        &self,
+        registration_id: &str,
+        claim: &str,
+    ) -> Result<RegistrationResponse, ClientError> {
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "{}/api/v1/register/{}",
+                self.base_url, registration_id
+            ))
+            .header(HEADER_REGISTRATION_CLAIM, claim)
+            .body(Full::new(Bytes::new()))
+            .map_err(|e| ClientError::Http(e.to_string()))?;
+
+        self.fetch_json(request, StatusCode::OK).await
+    }
+
+    /// Send one collection cycle, split into as many requests as the
+    /// collector's size limit requires.
+    ///
+    /// When one of those requests fails, sending the same batch again,
+    /// whether retried or replayed from the spool, picks up from the first
+    /// point the collector did not take, so it never stores one twice.
+    pub async fn send_batch(
+        &self,
+        mut data: Vec<ProbeDataPoint>,
+        sequence: u64,
+    ) -> Result<(), ClientError> {
+        let session = self.session().await?;
+        let batch = fingerprint(&data)?;
+        let mut sent = match *self.progress.lock().unwrap() {
+            Some(progress) if progress.batch == batch => progress.points,
+            _ => 0,
+        };
+        if sent > 0 {
+            debug!(
+                "Resuming batch after the {} probe data points already sent",
+                sent
+            );
+            data.drain(..sent);
+        }
+        let count = data.len();
+
+        let parts = encode_batches(
+            session.protocol,
+            session.format,
+            &self.node_id,
+            &self.tags,
+            sequence,
+            data,
+            session.max_request_size,
+        )?;
+
+        debug!(
+            "Sending batch of {} probe data points in {} request(s), protocol v{} ({})",
+            count,
+            parts.len(),
+            session.protocol,
+            session.format.content_type()
+        );
+
+        for (points, body) in parts {
+            let body = session
+                .compression
+                .compress(&body)
+                .map_err(|e| ClientError::Serialization(e.to_string()))?;
+
+            if let Err(e) = self.post(body, session).await {
+                // The collector may have been replaced; ask again next time
+                *self.session.lock().unwrap() = None;
+                *self.progress.lock().unwrap() = (sent > 0).then_some(Progress {
+                    batch,
+                    points: sent,
+                });
+                return Err(e);
+            }
+            sent += points;
+        }
+
+        *self.progress.lock().unwrap() = None;
+        Ok(())
+    }
+
+    async fn post(&self, body: Vec<u8>, session: Session) -> Result<(), ClientError> {
+        let mut builder = Request::builder()
+            .method("POST")
+            .uri(format!("{}/api/v1/probe", self.base_url))
+            .header("Content-Type", session.format.content_type());
+
+        if let Some(encoding) = session.compression.content_encoding() {
+            builder = builder.header("Content-Encoding", encoding);
+        }
+        let (authorization, secret) = {
+            let credentials = self.credentials.read().unwrap();
+            (
+                credentials.authorization.clone(),
+                credentials.secret.clone(),
+            )
+        };
+        if let Some(authorization) = authorization {
+            builder = builder.header(AUTHORIZATION, authorization);
+        }
+        // Signed per attempt, so a retried request gets a fresh nonce
+        if let Some(secret) = &secret {
+            let signature =
+                Signature::sign(secret, &self.node_id, get_utc_timestamp() / 1000, &body);
+            builder = builder
+                .header(HEADER_NODE_ID, signature.node_id)
+                .header(HEADER_TIMESTAMP, signature.timestamp)
+                .header(HEADER_NONCE, signature.nonce)
+                .header(HEADER_SIGNATURE, signature.signature);
+        }
+
+        let request = builder
+            .body(Full::new(Bytes::from(body)))
             .map_err(|e| ClientError::Http(e.to_string()))?;
 
         let response = self
             .client
             .request(request)
             .await
-            .map_err(|e| ClientError::Http(e.to_string()))?;
+            .map_err(|e| ClientError::Http(error_chain(&e)))?;
 
         let status = response.status();
 
         if status != StatusCode::ACCEPTED && status != StatusCode::OK {
             error!("Collector returned error status: {}", status);
-            return Err(ClientError::InvalidResponse(status));
+            return Err(status_error(status, response.headers()));
         }
 
         debug!("Batch sent successfully, status: {}", status);
         Ok(())
     }
+
+    /// Negotiated session, fetching the collector's `/health` if needed
+    async fn session(&self) -> Result<Session, ClientError> {
+        if let Some(session) = *self.session.lock().unwrap() {
+            return Ok(session);
+        }
+
+        let health = self.health().await?;
+        let session = Session {
+            protocol: health.negotiate_protocol(),
+            format: health.negotiate_wire_format(),
+            compression: health.negotiate_compression(self.compression),
+            max_request_size: health.max_request_size_bytes,
+        };
+
+        info!(
+            "Collector {} speaks protocol v{} ({}), max request size {} bytes",
+            health.version,
+            session.protocol,
+            session.format.content_type(),
+            session.max_request_size
+        );
+
+        if session.compression != self.compression {
+            warn!(
+                "Collector does not support {:?} compression, sending uncompressed",
+                self.compression
+            );
+        }
+
+        *self.session.lock().unwrap() = Some(session);
+        Ok(session)
+    }
+
+    /// The collector's `/health`: its version and what it accepts
+    pub async fn health(&self) -> Result<HealthResponse, ClientError> {
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("{}/health", self.base_url))
+            .body(Full::new(Bytes::new()))
+            .map_err(|e| ClientError::Http(e.to_string()))?;
+
+        self.fetch_json(request, StatusCode::OK).await
+    }
+
+    /// Send `request` and decode its JSON response, which must come with `expected`
+    async fn fetch_json<T: serde::de::DeserializeOwned>(
+        &self,
+        request: Request<Full<Bytes>>,
+        expected: StatusCode,
+    ) -> Result<T, ClientError> {
+        let uri = request.uri().path().to_string();
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| ClientError::Http(error_chain(&e)))?;
+
+        let status = response.status();
+        if status != expected {
+            error!("Collector returned status {} for {}", status, uri);
+            return Err(status_error(status, response.headers()));
+        }
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| ClientError::Http(e.to_string()))?
+            .to_bytes();
+
+        serde_json::from_slice(&body).map_err(|e| ClientError::Serialization(e.to_string()))
+    }
+}
+
+/// The error for an unexpected response status, keeping the collector's
+/// `Retry-After` when it is overloaded
+fn status_error(status: StatusCode, headers: &HeaderMap) -> ClientError {
+    let throttled =
+        status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE;
+    match headers.get(RETRY_AFTER).and_then(|v| v.to_str().ok()) {
+        Some(value) if throttled => match parse_retry_after(value, OffsetDateTime::now_utc()) {
+            Some(wait) => ClientError::Throttled(status, wait),
+            None => ClientError::InvalidResponse(status),
+        },
+        _ => ClientError::InvalidResponse(status),
+    }
+}
+
+/// A `Retry-After` value, either seconds or an HTTP date, cut down to
+/// `MAX_COOLDOWN`
+fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    let value = value.trim();
+    let wait = match value.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => {
+            let at = OffsetDateTime::parse(value, &Rfc2822).ok()?;
+            (at - now).try_into().unwrap_or(Duration::ZERO)
+        }
+    };
+    Some(wait.min(MAX_COOLDOWN))
+}
+
+/// Identifies a batch when it is sent again
+fn fingerprint(data: &[ProbeDataPoint]) -> Result<u64, ClientError> {
+    let encoded = WireFormat::MessagePack
+        .encode(&data)
+        .map_err(|e| ClientError::Serialization(e.to_string()))?;
+    let mut hasher = DefaultHasher::new();
+    encoded.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Serialize `data` in the given protocol version and format, halving the
+/// batch until every request body fits in `max_size` bytes. Each body comes
+/// with the number of points it carries.
+fn encode_batches(
+    protocol: u32,
+    format: WireFormat,
+    node_id: &str,
+    tags: &BTreeMap<String, String>,
+    sequence: u64,
+    data: Vec<ProbeDataPoint>,
+    max_size: usize,
+) -> Result<Vec<(usize, Vec<u8>)>, ClientError> {
+    let body = if protocol == PROTOCOL_V1 {
+        format.encode(&ProbeDataBatch { data: data.clone() })
+    } else {
+        format.encode(
+            &BatchEnvelope::new(node_id, AGENT_VERSION, sequence, data.clone())
+                .with_tags(tags.clone()),
+        )
+    }
+    .map_err(|e| ClientError::Serialization(e.to_string()))?;
+
+    if body.len() <= max_size {
+        return Ok(vec![(data.len(), body)]);
+    }
+
+    if data.len() <= 1 {
+        return Err(ClientError::Serialization(format!(
+            "data point of {} bytes exceeds the collector limit of {} bytes",
+            body.len(),
+            max_size
+        )));
+    }
+
+    let mut first = data;
+    let second = first.split_off(first.len() / 2);
+
+    let mut bodies = encode_batches(protocol, format, node_id, tags, sequence, first, max_size)?;
+    bodies.extend(encode_batches(
+        protocol, format, node_id, tags, sequence, second, max_size,
+    )?);
+    Ok(bodies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ferrview_common::codec::CONTENT_TYPE_JSON;
+    use ferrview_common::protocol::{IncomingBatch, PROTOCOL_V2};
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper::{Method, Response};
+    use hyper_util::rt::TokioIo;
+    use std::collections::BTreeMap;
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    fn points(n: usize) -> Vec<ProbeDataPoint> {
+        (0..n)
+            .map(|i| ProbeDataPoint {
+                node_id: "node-1".to_string(),
+                timestamp: 1_704_110_400_000,
+                probe_type: "sysinfo".to_string(),
+                probe_name: format!("metric_{}", i),
+                probe_value: i.into(),
+                labels: BTreeMap::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_single_request() {
+        let tags = BTreeMap::from([("env".to_string(), "prod".to_string())]);
+        let bodies = encode_batches(
+            PROTOCOL_V2,
+            WireFormat::Json,
+            "node-1",
+            &tags,
+            1,
+            points(10),
+            usize::MAX,
+        )
+        .unwrap();
+        assert_eq!(bodies.len(), 1);
+
+        let batch: IncomingBatch = serde_json::from_slice(&bodies[0].1).unwrap();
+        let IncomingBatch::Envelope(envelope) = &batch else {
+            panic!("expected an envelope");
+        };
+        assert_eq!(envelope.tags, tags);
+        assert_eq!(batch.into_points(), points(10));
+    }
+
+    #[test]
+    fn test_encode_legacy_format() {
+        let bodies = encode_batches(
+            PROTOCOL_V1,
+            WireFormat::Json,
+            "node-1",
+            &BTreeMap::new(),
+            1,
+            points(2),
+            usize::MAX,
+        )
+        .unwrap();
+
+        let batch: IncomingBatch = serde_json::from_slice(&bodies[0].1).unwrap();
+        assert!(matches!(batch, IncomingBatch::Legacy(_)));
+    }
+
+    #[test]
+    fn test_encode_splits_to_fit_limit() {
+        let bodies = encode_batches(
+            PROTOCOL_V2,
+            WireFormat::Json,
+            "node-1",
+            &BTreeMap::new(),
+            1,
+            points(100),
+            1024,
+        )
+        .unwrap();
+        assert!(bodies.len() > 1);
+        assert!(bodies.iter().all(|(_, b)| b.len() <= 1024));
+        assert_eq!(bodies.iter().map(|(n, _)| n).sum::<usize>(), 100);
+
+        let all: Vec<_> = bodies
+            .iter()
+            .flat_map(|(n, b)| {
+                let points = serde_json::from_slice::<IncomingBatch>(b)
+                    .unwrap()
+                    .into_points();
+                assert_eq!(points.len(), *n);
+                points
+            })
+            .collect();
+        assert_eq!(all, points(100));
+    }
+
+    #[test]
+    fn test_encode_msgpack() {
+        let bodies = encode_batches(
+            PROTOCOL_V2,
+            WireFormat::MessagePack,
+            "node-1",
+            &BTreeMap::new(),
+            1,
+            points(10),
+            usize::MAX,
+        )
+        .unwrap();
+
+        let batch: IncomingBatch = WireFormat::MessagePack.decode(&bodies[0].1).unwrap();
+        assert_eq!(batch.into_points(), points(10));
+    }
+
+    #[test]
+    fn test_encode_oversized_point() {
+        let result = encode_batches(
+            PROTOCOL_V2,
+            WireFormat::Json,
+            "node-1",
+            &BTreeMap::new(),
+            1,
+            points(1),
+            16,
+        );
+        assert!(matches!(result, Err(ClientError::Serialization(_))));
+    }
+
+    /// Requests the collector received, and whether to fail the next one
+    #[derive(Default)]
+    struct Received {
+        points: Vec<ProbeDataPoint>,
+        requests: usize,
+        fail_request: Option<usize>,
+    }
+
+    /// A collector accepting requests of up to 1 KiB, except the one
+    /// numbered `fail_request`
+    async fn collector(received: Arc<Mutex<Received>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let received = received.clone();
+                let service = service_fn(move |req: Request<Incoming>| {
+                    let received = received.clone();
+                    async move {
+                        let status = match (req.method(), req.uri().path()) {
+                            (&Method::GET, "/health") => {
+                                let health = HealthResponse {
+                                    status: "ok".to_string(),
+                                    version: "test".to_string(),
+                                    max_request_size_bytes: 1024,
+                                    protocol_versions: vec![PROTOCOL_V2],
+                                    content_types: vec![CONTENT_TYPE_JSON.to_string()],
+                                    content_encodings: Vec::new(),
+                                };
+                                let body = serde_json::to_vec(&health).unwrap();
+                                return Ok::<_, Infallible>(Response::new(Full::new(body.into())));
+                            }
+                            _ => {
+                                let body = req.into_body().collect().await.unwrap().to_bytes();
+                                let batch: IncomingBatch = serde_json::from_slice(&body).unwrap();
+                                let mut received = received.lock().unwrap();
+                                received.requests += 1;
+                                if received.fail_request == Some(received.requests) {
+                                    received.fail_request = None;
+                                    StatusCode::SERVICE_UNAVAILABLE
+                                } else {
+                                    received.points.extend(batch.into_points());
+                                    StatusCode::ACCEPTED
+                                }
+                            }
+                        };
+                        let mut response = Response::new(Full::new(Bytes::new()));
+                        *response.status_mut() = status;
+                        Ok(response)
+                    }
+                });
+                tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_resend_skips_points_already_sent() {
+        let received = Arc::new(Mutex::new(Received {
+            fail_request: Some(3),
+            ..Default::default()
+        }));
+        let addr = collector(received.clone()).await;
+        let client = HttpClient::new(
+            &addr,
+            "node-1",
+            &BTreeMap::new(),
+            Compression::None,
+            &TlsConfig::default(),
+            &AuthConfig::default(),
+        )
+        .unwrap();
+
+        // Two requests go through before the third fails
+        let result = client.send_batch(points(100), 1).await;
+        assert!(matches!(
+            result,
+            Err(ClientError::InvalidResponse(
+                StatusCode::SERVICE_UNAVAILABLE
+            ))
+        ));
+        let taken = received.lock().unwrap().points.len();
+        assert!(taken > 0 && taken < 100);
+
+        // Sending it again, as a retry or spool replay would, only sends the rest
+        client.send_batch(points(100), 2).await.unwrap();
+        assert_eq!(received.lock().unwrap().points, points(100));
+
+        // A new batch is sent whole
+        client.send_batch(points(10), 3).await.unwrap();
+        assert_eq!(received.lock().unwrap().points.len(), 110);
+    }
+
+    #[test]
+    fn test_error_classification() {
+        assert!(ClientError::Http("refused".into()).is_retryable());
+        assert!(ClientError::InvalidResponse(StatusCode::BAD_GATEWAY).is_retryable());
+        assert!(ClientError::InvalidResponse(StatusCode::TOO_MANY_REQUESTS).is_retryable());
+        assert!(!ClientError::InvalidResponse(StatusCode::UNAUTHORIZED).is_retryable());
+        assert!(!ClientError::InvalidResponse(StatusCode::UNAUTHORIZED).is_rejection());
+
+        let bad_request = ClientError::InvalidResponse(StatusCode::BAD_REQUEST);
+        assert!(!bad_request.is_retryable());
+        assert!(bad_request.is_rejection());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("5"));
+        let error = status_error(StatusCode::SERVICE_UNAVAILABLE, &headers);
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(5)));
+        assert!(error.is_retryable());
+
+        // Only an overloaded collector is taken at its word
+        let error = status_error(StatusCode::INTERNAL_SERVER_ERROR, &headers);
+        assert_eq!(error.retry_after(), None);
+
+        let now = OffsetDateTime::parse("Sun, 06 Nov 1994 08:49:37 GMT", &Rfc2822).unwrap();
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(
+            parse_retry_after("18446744073709551615", now),
+            Some(MAX_COOLDOWN)
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 2094 08:49:37 GMT", now),
+            Some(MAX_COOLDOWN)
+        );
+    }
 }
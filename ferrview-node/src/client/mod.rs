@@ -1,2 +1,8 @@
+pub mod breaker;
+pub mod collector;
+pub mod delivery;
 pub mod http;
+pub mod registration;
 pub mod retry;
+pub mod spool;
+mod tls;
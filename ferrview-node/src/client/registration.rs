@@ -0,0 +1,266 @@
+use ferrview_common::protocol::{RegistrationRequest, RegistrationStatus};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use sysinfo::System;
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use super::http::{ClientError, HttpClient};
+
+const AGENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What the node got from registering, kept next to its config file
+///
+/// Until approval only the registration id and claim are set; the claim
+/// proves to the collector that a status request comes from the node that
+/// registered. Once approved, the token and secret are filled in.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Credentials {
+    /// Node the credentials were issued to
+    pub node_id: Option<String>,
+    pub registration_id: Option<String>,
+    pub claim: Option<String>,
+    pub token: Option<String>,
+    pub secret: Option<String>,
+}
+
+// Keep credentials out of debug logs
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("node_id", &self.node_id)
+            .field("registration_id", &self.registration_id)
+            .field("claim", &self.claim.as_ref().map(|_| "<redacted>"))
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl Credentials {
+    /// `ferrview-node.toml` -> `ferrview-node.credentials.toml`, in the same directory
+    pub fn path_for(config_file: &Path) -> PathBuf {
+        let stem = config_file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("ferrview-node");
+        config_file.with_file_name(format!("{}.credentials.toml", stem))
+    }
+
+    /// Credentials saved at `path`, empty if there is no such file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => basic_toml::from_str(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write to `path`, readable by the owner only. The file is replaced
+    /// through a rename, so a crash leaves either the old or the new one.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = basic_toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        let tmp_path = path.with_extension("toml.tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        io::Write::write_all(&mut options.open(&tmp_path)?, content.as_bytes())?;
+        fs::rename(&tmp_path, path)
+    }
+
+    pub fn is_approved(&self) -> bool {
+        self.token.is_some() || self.secret.is_some()
+    }
+}
+
+/// A registration waiting for an admin to approve or reject it
+pub struct Registration {
+    node_id: String,
+    path: PathBuf,
+    credentials: Credentials,
+    poll: Interval,
+}
+
+impl Registration {
+    pub fn new(node_id: &str, path: PathBuf, credentials: Credentials, poll: Duration) -> Self {
+        let mut poll = tokio::time::interval(poll);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            node_id: node_id.to_string(),
+            path,
+            credentials,
+            poll,
+        }
+    }
+
+    /// Wait until the collector is due to be asked again
+    pub async fn tick(&mut self) {
+        self.poll.tick().await;
+    }
+
+    /// Register, or ask the collector about the registration made before.
+    /// On approval the credentials are saved and handed to `client`.
+    pub async fn check(&mut self, client: &HttpClient) -> RegistrationStatus {
+        let (Some(id), Some(claim)) = (&self.credentials.registration_id, &self.credentials.claim)
+        else {
+            return self.register(client).await;
+        };
+
+        let response = match client.registration_status(id, claim).await {
+            Ok(response) => response,
+            Err(ClientError::InvalidResponse(StatusCode::NOT_FOUND)) => {
+                // The collector lost its credential store; start over
+                warn!("Collector no longer knows registration {}", id);
+                self.credentials = Credentials::default();
+                return self.register(client).await;
+            }
+            Err(e) => {
+                warn!("Failed to check registration {}: {}", id, e);
+                return RegistrationStatus::Pending;
+            }
+        };
+
+        match response.status {
+            RegistrationStatus::Pending => {
+                info!("Registration {} is awaiting approval", id);
+                RegistrationStatus::Pending
+            }
+            RegistrationStatus::Rejected => {
+                error!("Registration {} was rejected by the collector", id);
+                RegistrationStatus::Rejected
+            }
+            RegistrationStatus::Approved if response.token.is_none() => {
+                error!(
+                    "Registration {} was approved but its credential is no longer handed out; \
+                     remove {} to register again",
+                    id,
+                    self.path.display()
+                );
+                RegistrationStatus::Rejected
+            }
+            RegistrationStatus::Approved => {
+                self.credentials.token = response.token;
+                self.credentials.secret = response.secret;
+                if let Err(e) = self.credentials.save(&self.path) {
+                    // Still usable for this run; the node registers again
+                    // after a restart
+                    error!(
+                        "Failed to save credentials to {}: {}",
+                        self.path.display(),
+                        e
+                    );
+                }
+
+                if let Err(e) = client.set_credentials(
+                    self.credentials.token.as_deref(),
+                    self.credentials.secret.as_deref(),
+                ) {
+                    error!("Cannot use the issued credentials: {}", e);
+                    return RegistrationStatus::Rejected;
+                }
+
+                info!(
+                    "Registration {} approved, credentials saved to {}",
+                    id,
+                    self.path.display()
+                );
+                RegistrationStatus::Approved
+            }
+        }
+    }
+
+    async fn register(&mut self, client: &HttpClient) -> RegistrationStatus {
+        let request = RegistrationRequest {
+            node_id: self.node_id.clone(),
+            hostname: System::host_name(),
+            agent_version: AGENT_VERSION.to_string(),
+        };
+
+        let response = match client.register(&request).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to register with the collector: {}", e);
+                return RegistrationStatus::Pending;
+            }
+        };
+
+        info!(
+            "Registered as {}, awaiting approval on the collector",
+            response.registration_id
+        );
+        self.credentials.node_id = Some(self.node_id.clone());
+        self.credentials.registration_id = Some(response.registration_id);
+        self.credentials.claim = response.claim;
+        if let Err(e) = self.credentials.save(&self.path) {
+            error!(
+                "Failed to save registration to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+
+        RegistrationStatus::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+
+    #[test]
+    fn test_credentials_path() {
+        assert_eq!(
+            Credentials::path_for(Path::new("/etc/ferrview/ferrview-node.toml")),
+            Path::new("/etc/ferrview/ferrview-node.credentials.toml")
+        );
+        assert_eq!(
+            Credentials::path_for(Path::new("node.toml")),
+            Path::new("node.credentials.toml")
+        );
+    }
+
+    #[test]
+    fn test_credentials_round_trip() {
+        let dir = TestDir::new("credentials");
+        fs::create_dir_all(dir.path()).unwrap();
+        let path = dir.path().join("node.credentials.toml");
+
+        let missing = Credentials::load(&path).unwrap();
+        assert!(missing.registration_id.is_none());
+        assert!(!missing.is_approved());
+
+        let credentials = Credentials {
+            node_id: Some("node-1".to_string()),
+            registration_id: Some("0123abcd".to_string()),
+            claim: Some("c1a1m".to_string()),
+            token: Some("fvt_abc".to_string()),
+            secret: None,
+        };
+        credentials.save(&path).unwrap();
+
+        let loaded = Credentials::load(&path).unwrap();
+        assert_eq!(loaded.registration_id.as_deref(), Some("0123abcd"));
+        assert_eq!(loaded.token.as_deref(), Some("fvt_abc"));
+        assert!(loaded.is_approved());
+        assert!(!format!("{:?}", loaded).contains("fvt_abc"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
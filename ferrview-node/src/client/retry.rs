@@ -1,10 +1,15 @@
+use ring::rand::{SecureRandom, SystemRandom};
 use std::future::Future;
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{debug, warn};
 
+use super::breaker::MAX_COOLDOWN;
 use super::http::ClientError;
 
+/// Call `f` up to `max_retries` times while it fails with a retryable error.
+/// Waits between attempts are randomized so that nodes failing together do
+/// not retry together.
 pub async fn send_with_retry<F, Fut>(f: F, max_retries: u32) -> Result<(), ClientError>
 where
     F: Fn() -> Fut,
@@ -23,14 +28,30 @@ where
                 return Ok(());
             }
             Err(e) => {
+                if !e.is_retryable() {
+                    warn!("Not retrying: {}", e);
+                    return Err(e);
+                }
                 if attempt >= max_retries {
                     warn!("Failed to send after {} attempts: {}", max_retries, e);
                     return Err(e);
                 }
 
-                // Exponential backoff: 1s, 2s, 4s, 8s...
-                let backoff_secs = 2u64.pow(attempt - 1);
-                let backoff = Duration::from_secs(backoff_secs);
+                let backoff = match e.retry_after() {
+                    // Retry-After is capped at the breaker's longest pause;
+                    // a collector asking for that much is left to the breaker
+                    Some(wait) if wait >= MAX_COOLDOWN => {
+                        warn!("Not waiting {:?} to retry: {}", wait, e);
+                        return Err(e);
+                    }
+                    Some(wait) => wait + jitter(Duration::from_secs(1) + wait / 4),
+                    None => {
+                        // Exponential backoff: 1s, 2s, 4s, 8s..., each cut
+                        // short by up to half
+                        let backoff = Duration::from_secs(2u64.pow(attempt - 1));
+                        backoff / 2 + jitter(backoff / 2)
+                    }
+                };
 
                 warn!(
                     "Attempt {}/{} failed: {}. Retrying in {:?}",
@@ -43,19 +64,33 @@ where
     }
 }
 
+/// A random duration of at most `max`
+fn jitter(max: Duration) -> Duration {
+    let mut bytes = [0u8; 4];
+    // Without randomness the waits are just not spread out
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        return Duration::ZERO;
+    }
+    max.mul_f64(f64::from(u32::from_le_bytes(bytes)) / f64::from(u32::MAX))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use hyper::StatusCode;
     use std::sync::Arc;
     use std::sync::atomic::{AtomicU32, Ordering};
 
-    #[tokio::test]
+    // The clock is paused and jumps ahead whenever the runtime would idle,
+    // so backoffs take no real time
+
+    #[tokio::test(start_paused = true)]
     async fn test_succeeds_first_try() {
         let result = send_with_retry(|| async { Ok(()) }, 3).await;
         assert!(result.is_ok());
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn test_succeeds_after_retries() {
         let counter = Arc::new(AtomicU32::new(0));
         let counter_clone = Arc::clone(&counter);
@@ -80,7 +115,7 @@ mod tests {
         assert_eq!(counter.load(Ordering::SeqCst), 3);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn test_fails_after_max_retries() {
         let counter = Arc::new(AtomicU32::new(0));
         let counter_clone = Arc::clone(&counter);
@@ -101,7 +136,7 @@ mod tests {
         assert_eq!(counter.load(Ordering::SeqCst), 3);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn test_respects_max_retries() {
         let counter = Arc::new(AtomicU32::new(0));
         let counter_clone = Arc::clone(&counter);
@@ -121,4 +156,83 @@ mod tests {
         // Should attempt exactly max_retries times
         assert_eq!(counter.load(Ordering::SeqCst), 5);
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_permanent_error_not_retried() {
+        let counter = Arc::new(AtomicU32::new(0));
+        let counter_clone = Arc::clone(&counter);
+
+        let result = send_with_retry(
+            move || {
+                let c = Arc::clone(&counter_clone);
+                async move {
+                    c.fetch_add(1, Ordering::SeqCst);
+                    Err(ClientError::InvalidResponse(StatusCode::BAD_REQUEST))
+                }
+            },
+            3,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_honors_retry_after() {
+        let counter = Arc::new(AtomicU32::new(0));
+        let counter_clone = Arc::clone(&counter);
+        let started = tokio::time::Instant::now();
+
+        let result = send_with_retry(
+            move || {
+                let c = Arc::clone(&counter_clone);
+                async move {
+                    match c.fetch_add(1, Ordering::SeqCst) {
+                        0 => Err(ClientError::Throttled(
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            Duration::from_secs(2),
+                        )),
+                        _ => Ok(()),
+                    }
+                }
+            },
+            3,
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert!(started.elapsed() >= Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_long_retry_after_gives_up() {
+        let counter = Arc::new(AtomicU32::new(0));
+        let counter_clone = Arc::clone(&counter);
+
+        let result = send_with_retry(
+            move || {
+                let c = Arc::clone(&counter_clone);
+                async move {
+                    c.fetch_add(1, Ordering::SeqCst);
+                    Err(ClientError::Throttled(
+                        StatusCode::TOO_MANY_REQUESTS,
+                        MAX_COOLDOWN,
+                    ))
+                }
+            },
+            3,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let max = Duration::from_millis(500);
+        assert!((0..100).all(|_| jitter(max) <= max));
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+    }
 }
@@ -0,0 +1,317 @@
+use ferrview_common::ProbeDataPoint;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::utils::timestamp::get_utc_timestamp;
+
+const EXTENSION: &str = "json";
+const TMP_EXTENSION: &str = "tmp";
+
+/// On-disk queue of batches the collector has not accepted yet
+///
+/// Each batch is one JSON file named `<sequence>-<spooled at, epoch ms>.json`,
+/// so the directory listing alone gives replay order and age. Writes go
+/// through a temporary file and a rename, so a crash never leaves a
+/// half-written batch behind. When the spool outgrows `max_bytes`, or a batch
+/// outlives `max_age`, the oldest batches are dropped.
+pub struct Spool {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_age: Duration,
+    entries: VecDeque<Entry>,
+    total_bytes: u64,
+    next_sequence: u64,
+    /// The batch last returned by [`Spool::front`]
+    handed_out: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    spooled_at: i64,
+    size: u64,
+}
+
+impl Spool {
+    /// Open the spool in `dir`, creating it if needed and picking up
+    /// batches left by a previous run
+    pub fn open(dir: &Path, max_bytes: u64, max_age: Duration) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let mut found = Vec::new();
+        for dir_entry in fs::read_dir(dir)? {
+            let path = dir_entry?.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some(EXTENSION) => {}
+                Some(TMP_EXTENSION) => {
+                    debug!("Removing incomplete spool file {}", path.display());
+                    fs::remove_file(&path)?;
+                    continue;
+                }
+                _ => continue,
+            }
+
+            let Some((sequence, spooled_at)) = parse_file_name(&path) else {
+                warn!("Ignoring unexpected file in spool: {}", path.display());
+                continue;
+            };
+            let size = fs::metadata(&path)?.len();
+            found.push((
+                sequence,
+                Entry {
+                    path,
+                    spooled_at,
+                    size,
+                },
+            ));
+        }
+        found.sort_by_key(|(sequence, _)| *sequence);
+
+        let next_sequence = found.last().map_or(0, |(sequence, _)| sequence + 1);
+        let entries: VecDeque<Entry> = found.into_iter().map(|(_, entry)| entry).collect();
+        let total_bytes = entries.iter().map(|entry| entry.size).sum();
+
+        if !entries.is_empty() {
+            info!(
+                "Spool {} holds {} unsent batch(es), {} bytes",
+                dir.display(),
+                entries.len(),
+                total_bytes
+            );
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            max_age,
+            entries,
+            total_bytes,
+            next_sequence,
+            handed_out: None,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Append a batch, dropping the oldest ones if the spool is over its size limit
+    pub fn push(&mut self, data: &[ProbeDataPoint]) -> io::Result<()> {
+        self.push_at(data, get_utc_timestamp())
+    }
+
+    fn push_at(&mut self, data: &[ProbeDataPoint], now: i64) -> io::Result<()> {
+        let body = serde_json::to_vec(data)?;
+        let size = body.len() as u64;
+
+        let path = self
+            .dir
+            .join(format!("{:020}-{}.{}", self.next_sequence, now, EXTENSION));
+        let tmp_path = path.with_extension(TMP_EXTENSION);
+        fs::write(&tmp_path, &body)?;
+        fs::rename(&tmp_path, &path)?;
+        self.next_sequence += 1;
+
+        self.entries.push_back(Entry {
+            path,
+            spooled_at: now,
+            size,
+        });
+        self.total_bytes += size;
+        debug!("Spooled {} points ({} bytes)", data.len(), size);
+
+        while self.total_bytes > self.max_bytes && self.entries.len() > 1 {
+            warn!(
+                "Spool over {} bytes, dropping oldest unsent batch",
+                self.max_bytes
+            );
+            self.remove_front()?;
+        }
+
+        Ok(())
+    }
+
+    /// The oldest batch still within the age limit, `None` once the spool is
+    /// drained. Unreadable batches are discarded along the way.
+    pub fn front(&mut self) -> io::Result<Option<Vec<ProbeDataPoint>>> {
+        self.front_at(get_utc_timestamp())
+    }
+
+    fn front_at(&mut self, now: i64) -> io::Result<Option<Vec<ProbeDataPoint>>> {
+        let max_age_millis = i64::try_from(self.max_age.as_millis()).unwrap_or(i64::MAX);
+
+        while let Some(entry) = self.entries.front() {
+            if now.saturating_sub(entry.spooled_at) > max_age_millis {
+                warn!(
+                    "Dropping unsent batch older than {:?}: {}",
+                    self.max_age,
+                    entry.path.display()
+                );
+                self.remove_front()?;
+                continue;
+            }
+
+            match fs::read(&entry.path)
+                .map_err(|e| e.to_string())
+                .and_then(|body| serde_json::from_slice(&body).map_err(|e| e.to_string()))
+            {
+                Ok(data) => {
+                    self.handed_out = Some(entry.path.clone());
+                    return Ok(Some(data));
+                }
+                Err(e) => {
+                    warn!(
+                        "Dropping unreadable spooled batch {}: {}",
+                        entry.path.display(),
+                        e
+                    );
+                    self.remove_front()?;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Remove the batch last returned by [`Spool::front`] once it was
+    /// delivered, unless pushing more has dropped it already
+    pub fn pop(&mut self) -> io::Result<()> {
+        let handed_out = self.handed_out.take();
+        match self.entries.front() {
+            Some(entry) if Some(&entry.path) == handed_out.as_ref() => self.remove_front(),
+            _ => Ok(()),
+        }
+    }
+
+    fn remove_front(&mut self) -> io::Result<()> {
+        if let Some(entry) = self.entries.pop_front() {
+            self.total_bytes -= entry.size;
+            match fs::remove_file(&entry.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split `<sequence>-<spooled at>.json` into its two numbers
+fn parse_file_name(path: &Path) -> Option<(u64, i64)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (sequence, spooled_at) = stem.split_once('-')?;
+    Some((sequence.parse().ok()?, spooled_at.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use std::collections::BTreeMap;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+    const NOW: i64 = 1_704_110_400_000;
+
+    fn batch(value: u64) -> Vec<ProbeDataPoint> {
+        vec![ProbeDataPoint {
+            node_id: "node".to_string(),
+            timestamp: NOW,
+            probe_type: "sysinfo".to_string(),
+            probe_name: "cpu_count".to_string(),
+            probe_value: value.into(),
+            labels: BTreeMap::new(),
+        }]
+    }
+
+    fn value(data: &[ProbeDataPoint]) -> Option<i64> {
+        data[0].probe_value.as_i64()
+    }
+
+    #[test]
+    fn test_replays_in_order_across_reopen() {
+        let dir = TestDir::new("spool-reopen");
+
+        let mut spool = Spool::open(dir.path(), u64::MAX, HOUR).unwrap();
+        for i in 0..3 {
+            spool.push_at(&batch(i), NOW).unwrap();
+        }
+        drop(spool);
+
+        // A stray temporary file from an interrupted write is cleaned up
+        fs::write(dir.path().join("00000000000000000009-0.tmp"), b"{").unwrap();
+
+        let mut spool = Spool::open(dir.path(), u64::MAX, HOUR).unwrap();
+        assert_eq!(spool.len(), 3);
+        spool.push_at(&batch(3), NOW).unwrap();
+
+        let mut replayed = Vec::new();
+        while let Some(data) = spool.front_at(NOW).unwrap() {
+            replayed.push(value(&data).unwrap());
+            spool.pop().unwrap();
+        }
+        assert_eq!(replayed, vec![0, 1, 2, 3]);
+        assert!(spool.is_empty());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_size_limit_drops_oldest() {
+        let dir = TestDir::new("spool-size");
+        let size = serde_json::to_vec(&batch(0)).unwrap().len() as u64;
+
+        let mut spool = Spool::open(dir.path(), size * 2, HOUR).unwrap();
+        for i in 0..5 {
+            spool.push_at(&batch(i), NOW).unwrap();
+        }
+
+        assert_eq!(spool.len(), 2);
+        assert_eq!(value(&spool.front_at(NOW).unwrap().unwrap()), Some(3));
+
+        // The batch being sent is pushed out; popping it leaves the others
+        spool.push_at(&batch(5), NOW).unwrap();
+        spool.pop().unwrap();
+        assert_eq!(spool.len(), 2);
+        assert_eq!(value(&spool.front_at(NOW).unwrap().unwrap()), Some(4));
+    }
+
+    #[test]
+    fn test_age_limit_drops_expired() {
+        let dir = TestDir::new("spool-age");
+
+        let mut spool = Spool::open(dir.path(), u64::MAX, HOUR).unwrap();
+        spool.push_at(&batch(0), NOW).unwrap();
+        spool.push_at(&batch(1), NOW + 30 * 60 * 1000).unwrap();
+
+        let later = NOW + 61 * 60 * 1000;
+        assert_eq!(value(&spool.front_at(later).unwrap().unwrap()), Some(1));
+        assert_eq!(spool.len(), 1);
+
+        assert!(
+            spool
+                .front_at(later + HOUR.as_millis() as i64)
+                .unwrap()
+                .is_none()
+        );
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_batch_is_skipped() {
+        let dir = TestDir::new("spool-corrupt");
+
+        let mut spool = Spool::open(dir.path(), u64::MAX, HOUR).unwrap();
+        spool.push_at(&batch(0), NOW).unwrap();
+        spool.push_at(&batch(1), NOW).unwrap();
+        fs::write(&spool.entries[0].path, b"not json").unwrap();
+
+        assert_eq!(value(&spool.front_at(NOW).unwrap().unwrap()), Some(1));
+        assert_eq!(spool.len(), 1);
+    }
+}
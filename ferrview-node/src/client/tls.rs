@@ -0,0 +1,215 @@
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use super::http::ClientError;
+use crate::config::TlsConfig;
+
+/// Build the rustls configuration for `https://` collectors
+pub fn client_config(tls: &TlsConfig) -> Result<ClientConfig, ClientError> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| ClientError::Tls(e.to_string()))?;
+
+    let builder = if tls.insecure_skip_verify {
+        warn!("TLS certificate verification is disabled; the collector is not authenticated");
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+    } else {
+        builder.with_root_certificates(root_store(tls.ca_file.as_deref())?)
+    };
+
+    match (&tls.cert_file, &tls.key_file) {
+        (Some(cert_file), Some(key_file)) => {
+            let chain = load_certs(cert_file)?;
+            let key = PrivateKeyDer::from_pem_file(key_file)
+                .map_err(|e| ClientError::Tls(format!("{}: {}", key_file, e)))?;
+            debug!("Using client certificate {}", cert_file);
+            builder
+                .with_client_auth_cert(chain, key)
+                .map_err(|e| ClientError::Tls(e.to_string()))
+        }
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => Err(ClientError::Tls(
+            "cert_file and key_file must be set together".to_string(),
+        )),
+    }
+}
+
+/// Trust anchors from `ca_file`, or the bundled Mozilla roots
+fn root_store(ca_file: Option<&str>) -> Result<RootCertStore, ClientError> {
+    let Some(ca_file) = ca_file else {
+        return Ok(RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        });
+    };
+
+    let mut roots = RootCertStore::empty();
+    let (added, ignored) = roots.add_parsable_certificates(load_certs(ca_file)?);
+    if ignored > 0 {
+        warn!("Ignored {} unusable certificate(s) in {}", ignored, ca_file);
+    }
+    if added == 0 {
+        return Err(ClientError::Tls(format!(
+            "{}: no usable CA certificates",
+            ca_file
+        )));
+    }
+
+    debug!("Trusting {} CA certificate(s) from {}", added, ca_file);
+    Ok(roots)
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, ClientError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| ClientError::Tls(format!("{}: {}", path, e)))?;
+
+    if certs.is_empty() {
+        return Err(ClientError::Tls(format!("{}: no certificates found", path)));
+    }
+    Ok(certs)
+}
+
+/// Accepts any server certificate; handshake signatures are still checked
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use std::fs;
+
+    /// Write a CA and a client certificate signed by it, returning
+    /// `(ca, cert, key)` paths
+    fn write_pki(dir: &TestDir) -> (String, String, String) {
+        fs::create_dir_all(dir.path()).unwrap();
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec!["node-1".to_string()])
+            .unwrap()
+            .signed_by(&key, &ca, &ca_key)
+            .unwrap();
+
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        fs::write(path("ca.pem"), ca.pem()).unwrap();
+        fs::write(path("node.pem"), cert.pem()).unwrap();
+        fs::write(path("node.key"), key.serialize_pem()).unwrap();
+        (path("ca.pem"), path("node.pem"), path("node.key"))
+    }
+
+    #[test]
+    fn test_default_roots() {
+        let config = client_config(&TlsConfig::default()).unwrap();
+        assert!(!config.client_auth_cert_resolver.has_certs());
+    }
+
+    #[test]
+    fn test_ca_bundle_and_client_cert() {
+        let dir = TestDir::new("tls-mtls");
+        let (ca_file, cert_file, key_file) = write_pki(&dir);
+
+        let config = client_config(&TlsConfig {
+            ca_file: Some(ca_file),
+            cert_file: Some(cert_file),
+            key_file: Some(key_file),
+            insecure_skip_verify: false,
+        })
+        .unwrap();
+        assert!(config.client_auth_cert_resolver.has_certs());
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let dir = TestDir::new("tls-invalid");
+        let (ca_file, cert_file, key_file) = write_pki(&dir);
+
+        // A certificate without its key
+        let result = client_config(&TlsConfig {
+            cert_file: Some(cert_file),
+            ..TlsConfig::default()
+        });
+        assert!(matches!(result, Err(ClientError::Tls(_))));
+
+        // A CA bundle that holds no certificates
+        let result = client_config(&TlsConfig {
+            ca_file: Some(key_file),
+            ..TlsConfig::default()
+        });
+        assert!(matches!(result, Err(ClientError::Tls(_))));
+
+        // A missing file
+        let result = client_config(&TlsConfig {
+            ca_file: Some(format!("{}.missing", ca_file)),
+            ..TlsConfig::default()
+        });
+        assert!(matches!(result, Err(ClientError::Tls(_))));
+    }
+
+    #[test]
+    fn test_insecure_skip_verify() {
+        let config = client_config(&TlsConfig {
+            insecure_skip_verify: true,
+            ..TlsConfig::default()
+        });
+        assert!(config.is_ok());
+    }
+}
@@ -0,0 +1,250 @@
+use argh::FromArgs;
+use ferrview_common::ProbeDataPoint;
+use std::error::Error;
+use std::str::FromStr;
+use std::time::Instant as StdInstant;
+use tokio::time::Instant;
+
+use crate::client::http::HttpClient;
+use crate::config::Config;
+use crate::identity;
+use crate::probes::ProbeRegistry;
+
+/// Shown as the node_id of readings when the node has yet to pick one
+const UNKNOWN_NODE_ID: &str = "unknown";
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand)]
+pub enum Command {
+    Run(RunCommand),
+    Probe(ProbeCommand),
+    CheckConfig(CheckConfigCommand),
+    Ping(PingCommand),
+}
+
+/// collect and send metrics until stopped (the default)
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "run")]
+pub struct RunCommand {}
+
+/// collect the enabled probes and print the readings instead of sending them
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "probe")]
+pub struct ProbeCommand {
+    /// collect every enabled probe once and exit
+    #[argh(switch)]
+    once: bool,
+
+    /// output format: table (default) or json
+    #[argh(option, default = "OutputFormat::Table")]
+    format: OutputFormat,
+}
+
+/// validate the config file and report keys it does not use
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "check-config")]
+pub struct CheckConfigCommand {}
+
+/// ask the collector for its version and limits
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "ping")]
+pub struct PingCommand {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown format {}, expected table or json", other)),
+        }
+    }
+}
+
+/// Run a command other than `run`; what it reports goes to stdout
+pub async fn run(command: Command, config_file: &str) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Run(_) => unreachable!("`run` is handled by main"),
+        Command::Probe(probe) => run_probe(probe, config_file).await,
+        Command::CheckConfig(_) => check_config(config_file),
+        Command::Ping(_) => ping(config_file).await,
+    }
+}
+
+async fn run_probe(command: ProbeCommand, config_file: &str) -> Result<(), Box<dyn Error>> {
+    let config = Config::load(config_file)?;
+    let node_id = identity::peek(&config).unwrap_or_else(|| UNKNOWN_NODE_ID.to_string());
+
+    let mut registry = ProbeRegistry::from_config(&config);
+    registry.init();
+    // CPU usage is measured between two samples, the first taken by init;
+    // without a pause the first readings would leave it out
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    while let Some(deadline) = registry.next_due() {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => {}
+            _ = &mut shutdown => break,
+        }
+
+        let data = registry.collect_due(&node_id, Instant::now());
+        print_readings(&data, command.format)?;
+        if command.once {
+            break;
+        }
+    }
+
+    registry.teardown();
+    Ok(())
+}
+
+fn print_readings(data: &[ProbeDataPoint], format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(data)?),
+        OutputFormat::Table => {
+            let rows: Vec<[String; 4]> = data
+                .iter()
+                .map(|point| {
+                    let labels: Vec<String> = point
+                        .labels
+                        .iter()
+                        .map(|(key, value)| format!("{}={}", key, value))
+                        .collect();
+                    [
+                        point.probe_type.clone(),
+                        point.probe_name.clone(),
+                        point.probe_value.to_string(),
+                        labels.join(","),
+                    ]
+                })
+                .collect();
+            print_table(["TYPE", "NAME", "VALUE", "LABELS"], &rows);
+        }
+    }
+    Ok(())
+}
+
+fn print_table(header: [&str; 4], rows: &[[String; 4]]) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let header = header.map(str::to_string);
+    for row in std::iter::once(&header).chain(rows) {
+        let line = format!(
+            "{:<w0$}  {:<w1$}  {:>w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+fn check_config(config_file: &str) -> Result<(), Box<dyn Error>> {
+    let (config, unknown) = Config::check(config_file)?;
+
+    for key in &unknown {
+        println!("Unknown key: {}", key);
+    }
+
+    let registry = ProbeRegistry::from_config(&config);
+    for endpoint in config.endpoints() {
+        println!("Collector: {}", endpoint.addr);
+    }
+    println!("Enabled probes: {}", registry.names().join(", "));
+
+    if !unknown.is_empty() {
+        return Err(format!(
+            "{} has {} unknown key(s), which are ignored",
+            config_file,
+            unknown.len()
+        )
+        .into());
+    }
+    println!("{} is valid", config_file);
+    Ok(())
+}
+
+/// Ping every collector, failing if any of them cannot be reached
+async fn ping(config_file: &str) -> Result<(), Box<dyn Error>> {
+    let config = Config::load(config_file)?;
+    let node_id = identity::peek(&config).unwrap_or_else(|| UNKNOWN_NODE_ID.to_string());
+
+    let endpoints = config.endpoints();
+    if endpoints.is_empty() {
+        return Err("no collector configured, the node is only scraped".into());
+    }
+    let mut failed = 0;
+    for (index, endpoint) in endpoints.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let client = HttpClient::new(
+            endpoint.addr,
+            &node_id,
+            &config.tags,
+            config.compression,
+            endpoint.tls,
+            endpoint.auth,
+        )?;
+        if let Err(e) = ping_collector(&client).await {
+            println!("Collector:          {}", client.base_url());
+            println!("Error:              {}", e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} collector(s) unreachable", failed, endpoints.len()).into());
+    }
+    Ok(())
+}
+
+async fn ping_collector(client: &HttpClient) -> Result<(), Box<dyn Error>> {
+    let started = StdInstant::now();
+    let health = client.health().await?;
+    let elapsed = started.elapsed();
+
+    let versions: Vec<String> = health
+        .protocol_versions
+        .iter()
+        .map(u32::to_string)
+        .collect();
+    println!("Collector:          {}", client.base_url());
+    println!("Status:             {}", health.status);
+    println!("Version:            {}", health.version);
+    println!("Response time:      {} ms", elapsed.as_millis());
+    println!(
+        "Max request size:   {} bytes",
+        health.max_request_size_bytes
+    );
+    println!("Protocol versions:  {}", versions.join(", "));
+    println!("Content types:      {}", health.content_types.join(", "));
+    println!(
+        "Content encodings:  {}",
+        if health.content_encodings.is_empty() {
+            "none".to_string()
+        } else {
+            health.content_encodings.join(", ")
+        }
+    );
+    Ok(())
+}
@@ -1,43 +1,308 @@
 // ferrview-node/src/config.rs
-use serde::Deserialize;
+use ferrview_common::codec::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
+use std::net::SocketAddr;
+use std::time::Duration;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
-    pub node_id: String,
-    pub metrics_collector_addr: String,
+    /// Fixed node_id; when unset it comes from `node_id_source`
+    pub node_id: Option<String>,
+    #[serde(default)]
+    pub node_id_source: NodeIdSource,
+    /// Grouping such as environment, role or datacenter, sent with every batch
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// The collector, when there is just one; otherwise see `collectors`
+    pub metrics_collector_addr: Option<String>,
+    /// Several collectors, used as `collector_policy` says
+    #[serde(default)]
+    pub collectors: Vec<CollectorConfig>,
+    #[serde(default)]
+    pub collector_policy: CollectorPolicy,
     #[serde(default = "default_collection_interval")]
     pub collection_interval_secs: u64,
+    /// Request body compression, used only if the collector supports it
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub registration: RegistrationConfig,
+    /// Directory for state kept across restarts, such as the spool
+    #[serde(default = "default_state_dir")]
+    pub state_dir: String,
+    #[serde(default)]
+    pub spool: SpoolConfig,
+    #[serde(default)]
+    pub exporter: ExporterConfig,
     pub probes: ProbesConfig,
 }
 
+/// How a node without a configured `node_id` gets one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeIdSource {
+    /// A random UUID, generated on first start and kept in `state_dir`
+    #[default]
+    Generated,
+    /// A UUID derived from `/etc/machine-id`
+    MachineId,
+}
+
+/// A collector in `[[collectors]]`. Without `tls` or `auth` of its own it
+/// uses the top-level sections.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectorConfig {
+    /// Shown in logs; with `fan-out` also names the collector's spool
+    pub name: Option<String>,
+    pub addr: String,
+    pub tls: Option<TlsConfig>,
+    pub auth: Option<AuthConfig>,
+}
+
+/// How batches are spread over several collectors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CollectorPolicy {
+    /// Send each batch to the first collector that takes it, in list order
+    #[default]
+    Failover,
+    /// Send every batch to every collector, each with its own retries and spool
+    FanOut,
+}
+
+impl fmt::Display for CollectorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollectorPolicy::Failover => write!(f, "failover"),
+            CollectorPolicy::FanOut => write!(f, "fan-out"),
+        }
+    }
+}
+
+/// Reported by `restart_required` when collectors were added, removed,
+/// renamed or the policy changed
+pub const COLLECTOR_LIST: &str = "the collector list";
+
+/// A collector with the settings it is reached with
+pub struct Endpoint<'a> {
+    pub name: &'a str,
+    pub addr: &'a str,
+    pub tls: &'a TlsConfig,
+    pub auth: &'a AuthConfig,
+}
+
 fn default_collection_interval() -> u64 {
     60 // Default: collect every 60 seconds
 }
 
-#[derive(Debug, Deserialize)]
+fn default_state_dir() -> String {
+    String::from("/var/lib/ferrview-node")
+}
+
+/// TLS settings for an `https://` collector address
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM bundle of CAs trusted to sign the collector's certificate;
+    /// the bundled Mozilla roots when unset
+    pub ca_file: Option<String>,
+    /// PEM client certificate chain and key, for collectors requiring mutual TLS
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+    /// Accept any collector certificate. Only for test setups.
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+impl TlsConfig {
+    pub fn is_configured(&self) -> bool {
+        self.ca_file.is_some()
+            || self.cert_file.is_some()
+            || self.key_file.is_some()
+            || self.insecure_skip_verify
+    }
+}
+
+/// Credentials presented to the collector
+#[derive(Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Bearer token from `ferrview-collector token create`
+    pub token: Option<String>,
+    /// Batch signing secret from `ferrview-collector secret generate`
+    pub secret: Option<String>,
+}
+
+// Keep credentials out of debug logs
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// Registration with a collector that hands out credentials to nodes an
+/// admin approves. Only used when `[auth]` sets neither token nor secret.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegistrationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How often to ask the collector whether the node has been approved
+    #[serde(default = "default_registration_poll_secs")]
+    pub poll_interval_secs: u64,
+}
+
+impl Default for RegistrationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval_secs: default_registration_poll_secs(),
+        }
+    }
+}
+
+fn default_registration_poll_secs() -> u64 {
+    30
+}
+
+/// Batches the collector did not accept are kept in `<state_dir>/spool`
+/// and replayed in order once it is reachable again
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpoolConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_spool_max_size_mb")]
+    pub max_size_mb: u64,
+    #[serde(default = "default_spool_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            max_size_mb: default_spool_max_size_mb(),
+            max_age_secs: default_spool_max_age_secs(),
+        }
+    }
+}
+
+fn default_spool_max_size_mb() -> u64 {
+    64
+}
+
+fn default_spool_max_age_secs() -> u64 {
+    24 * 60 * 60 // A day
+}
+
+/// Local HTTP listener serving the latest readings at `/metrics`, in the
+/// Prometheus text format
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExporterConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_exporter_listen_addr")]
+    pub listen_addr: String,
+}
+
+impl Default for ExporterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_addr: default_exporter_listen_addr(),
+        }
+    }
+}
+
+fn default_exporter_listen_addr() -> String {
+    String::from("127.0.0.1:9101")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ProbesConfig {
     pub sysinfo: SysinfoProbes,
     #[serde(default)]
     pub procfs: ProcfsProbes,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SysinfoProbes {
-    pub cpu: bool,
-    pub memory: bool,
-    pub temperature: bool,
-    pub static_info: bool,
+    pub cpu: ProbeSection,
+    pub memory: ProbeSection,
+    pub temperature: ProbeSection,
+    pub static_info: ProbeSection,
     #[serde(default)]
-    pub disk: bool,
+    pub disk: ProbeSection,
     #[serde(default)]
-    pub network: bool,
+    pub network: ProbeSection,
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ProcfsProbes {
     #[serde(default)]
-    pub forks: bool,
+    pub forks: ProbeSection,
+}
+
+/// Settings for a single probe, written either as a plain toggle
+/// (`cpu = true`) or as its own table (`[probes.sysinfo.cpu]`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "ProbeSectionRepr")]
+pub struct ProbeSection {
+    pub enabled: bool,
+    /// How often this probe runs; `collection_interval_secs` when unset
+    pub interval_secs: Option<u64>,
+}
+
+impl ProbeSection {
+    pub fn interval(&self, default_secs: u64) -> Duration {
+        Duration::from_secs(self.interval_secs.unwrap_or(default_secs))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProbeSectionRepr {
+    Toggle(bool),
+    Table {
+        #[serde(default = "default_enabled")]
+        enabled: bool,
+        interval_secs: Option<u64>,
+    },
+}
+
+fn default_enabled() -> bool {
+    true // A section that is present is on unless it says otherwise
+}
+
+impl TryFrom<ProbeSectionRepr> for ProbeSection {
+    type Error = String;
+
+    fn try_from(repr: ProbeSectionRepr) -> Result<Self, Self::Error> {
+        match repr {
+            ProbeSectionRepr::Toggle(enabled) => Ok(Self {
+                enabled,
+                interval_secs: None,
+            }),
+            ProbeSectionRepr::Table {
+                interval_secs: Some(0),
+                ..
+            } => Err("interval_secs must be greater than zero".to_string()),
+            ProbeSectionRepr::Table {
+                enabled,
+                interval_secs,
+            } => Ok(Self {
+                enabled,
+                interval_secs,
+            }),
+        }
+    }
 }
 
 impl Config {
@@ -46,9 +311,188 @@ impl Config {
         Self::from_str(&content)
     }
 
-    // Helper for tests - parses TOML from string
+    /// Load and validate the config at `path`, also returning the keys in
+    /// it that no setting reads (typos, or settings from another version)
+    pub fn check(path: &str) -> Result<(Self, Vec<String>), Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let config = Self::from_str(&content)?;
+        let unknown = unknown_keys(&content, &config)?;
+        Ok((config, unknown))
+    }
+
+    /// Parse and validate the content of a config file
     fn from_str(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(basic_toml::from_str(content)?)
+        let config: Self = basic_toml::from_str(content)?;
+        if config.collection_interval_secs == 0 {
+            return Err("collection_interval_secs must be greater than zero".into());
+        }
+        if config.registration.poll_interval_secs == 0 {
+            return Err("registration.poll_interval_secs must be greater than zero".into());
+        }
+        if config.tags.keys().any(String::is_empty) {
+            return Err("tag keys must not be empty".into());
+        }
+        if config.exporter.enabled && config.exporter.listen_addr.parse::<SocketAddr>().is_err() {
+            return Err(format!(
+                "exporter.listen_addr {} is not an ip:port address",
+                config.exporter.listen_addr
+            )
+            .into());
+        }
+        config.validate_collectors()?;
+        Ok(config)
+    }
+
+    fn validate_collectors(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match (&self.metrics_collector_addr, self.collectors.is_empty()) {
+            // Nothing is pushed; the collector scrapes the exporter instead
+            (None, true) if self.exporter.enabled && self.registration.enabled => {
+                return Err("registration needs a collector to register with".into());
+            }
+            (None, true) if self.exporter.enabled => {}
+            (None, true) => {
+                return Err(
+                    "set metrics_collector_addr, list [[collectors]] or enable [exporter]".into(),
+                );
+            }
+            (Some(_), false) => {
+                return Err("set either metrics_collector_addr or [[collectors]], not both".into());
+            }
+            _ => {}
+        }
+
+        if self.collectors.len() > 1 && self.registration.enabled {
+            return Err("registration only works with a single collector".into());
+        }
+
+        if self.collector_policy == CollectorPolicy::FanOut && self.collectors.len() > 1 {
+            let mut names = std::collections::BTreeSet::new();
+            for collector in &self.collectors {
+                let Some(name) = &collector.name else {
+                    return Err(format!(
+                        "collector {} needs a name, fan-out keeps a spool per collector",
+                        collector.addr
+                    )
+                    .into());
+                };
+                if name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    return Err(format!(
+                        "collector name {:?} may only use letters, digits, - and _",
+                        name
+                    )
+                    .into());
+                }
+                if !names.insert(name) {
+                    return Err(format!("collector name {} is used twice", name).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every collector, in the order of the config file
+    pub fn endpoints(&self) -> Vec<Endpoint<'_>> {
+        match &self.metrics_collector_addr {
+            Some(addr) => vec![Endpoint {
+                name: addr,
+                addr,
+                tls: &self.tls,
+                auth: &self.auth,
+            }],
+            None => self
+                .collectors
+                .iter()
+                .map(|collector| Endpoint {
+                    name: collector.name.as_deref().unwrap_or(&collector.addr),
+                    addr: &collector.addr,
+                    tls: collector.tls.as_ref().unwrap_or(&self.tls),
+                    auth: collector.auth.as_ref().unwrap_or(&self.auth),
+                })
+                .collect(),
+        }
+    }
+
+    /// Settings that differ in `other` but are only read at startup, so a
+    /// reload cannot apply them
+    pub fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.node_id != other.node_id || self.node_id_source != other.node_id_source {
+            changed.push("node_id");
+        }
+        if self.state_dir != other.state_dir {
+            changed.push("state_dir");
+        }
+        if self.spool != other.spool {
+            changed.push("[spool]");
+        }
+        if self.registration != other.registration {
+            changed.push("[registration]");
+        }
+        if self.exporter != other.exporter {
+            changed.push("[exporter]");
+        }
+        let names = |config: &Config| -> Vec<String> {
+            config
+                .endpoints()
+                .iter()
+                .map(|endpoint| endpoint.name.to_string())
+                .collect()
+        };
+        if self.collector_policy != other.collector_policy
+            || (self.endpoints().is_empty() != other.endpoints().is_empty())
+            || (self.collectors.is_empty() != other.collectors.is_empty())
+            || (!self.collectors.is_empty() && names(self) != names(other))
+        {
+            changed.push(COLLECTOR_LIST);
+        }
+        changed
+    }
+}
+
+/// Keys in `content` missing from `config` serialized back, as dotted paths.
+/// Every setting serializes, so whatever is left was ignored when parsing.
+fn unknown_keys(content: &str, config: &Config) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let raw: serde_json::Value = basic_toml::from_str(content)?;
+    let known = serde_json::to_value(config)?;
+
+    let mut unknown = Vec::new();
+    collect_unknown_keys(&raw, &known, "", &mut unknown);
+    unknown.sort();
+    Ok(unknown)
+}
+
+fn collect_unknown_keys(
+    raw: &serde_json::Value,
+    known: &serde_json::Value,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    use serde_json::Value;
+
+    match (raw, known) {
+        (Value::Object(raw), Value::Object(known)) => {
+            for (key, value) in raw {
+                let key_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match known.get(key) {
+                    Some(known) => collect_unknown_keys(value, known, &key_path, unknown),
+                    None => unknown.push(key_path),
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(known)) => {
+            for (index, (raw, known)) in raw.iter().zip(known).enumerate() {
+                collect_unknown_keys(raw, known, &format!("{}[{}]", path, index), unknown);
+            }
+        }
+        _ => {}
     }
 }
 
@@ -72,13 +516,16 @@ mod tests {
 
         let config = Config::from_str(toml).unwrap();
 
-        assert_eq!(config.node_id, "test-node-123");
-        assert_eq!(config.metrics_collector_addr, "localhost:8080");
+        assert_eq!(config.node_id.as_deref(), Some("test-node-123"));
+        assert_eq!(
+            config.metrics_collector_addr.as_deref(),
+            Some("localhost:8080")
+        );
         assert_eq!(config.collection_interval_secs, 30);
-        assert!(config.probes.sysinfo.cpu);
-        assert!(config.probes.sysinfo.memory);
-        assert!(!config.probes.sysinfo.temperature);
-        assert!(config.probes.sysinfo.static_info);
+        assert!(config.probes.sysinfo.cpu.enabled);
+        assert!(config.probes.sysinfo.memory.enabled);
+        assert!(!config.probes.sysinfo.temperature.enabled);
+        assert!(config.probes.sysinfo.static_info.enabled);
     }
 
     #[test]
@@ -114,10 +561,10 @@ mod tests {
 
         let config = Config::from_str(toml).unwrap();
 
-        assert!(config.probes.sysinfo.cpu);
-        assert!(config.probes.sysinfo.memory);
-        assert!(config.probes.sysinfo.temperature);
-        assert!(config.probes.sysinfo.static_info);
+        assert!(config.probes.sysinfo.cpu.enabled);
+        assert!(config.probes.sysinfo.memory.enabled);
+        assert!(config.probes.sysinfo.temperature.enabled);
+        assert!(config.probes.sysinfo.static_info.enabled);
     }
 
     #[test]
@@ -135,15 +582,118 @@ mod tests {
 
         let config = Config::from_str(toml).unwrap();
 
-        assert!(!config.probes.sysinfo.cpu);
-        assert!(!config.probes.sysinfo.memory);
-        assert!(!config.probes.sysinfo.temperature);
-        assert!(!config.probes.sysinfo.static_info);
+        assert!(!config.probes.sysinfo.cpu.enabled);
+        assert!(!config.probes.sysinfo.memory.enabled);
+        assert!(!config.probes.sysinfo.temperature.enabled);
+        assert!(!config.probes.sysinfo.static_info.enabled);
     }
 
     #[test]
-    fn test_missing_required_field() {
+    fn test_compression() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+            compression = "zstd"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert_eq!(config.compression, Compression::Zstd);
+
+        let toml = toml.replace("\"zstd\"", "\"brotli\"");
+        assert!(Config::from_str(&toml).is_err());
+    }
+
+    #[test]
+    fn test_default_compression() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert_eq!(config.compression, Compression::None);
+    }
+
+    #[test]
+    fn test_probe_sections() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+            collection_interval_secs = 30
+
+            [probes.sysinfo]
+            memory = true
+            temperature = false
+
+            [probes.sysinfo.cpu]
+            interval_secs = 5
+
+            [probes.sysinfo.static_info]
+            interval_secs = 3600
+
+            [probes.procfs.forks]
+            enabled = false
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        let sysinfo = &config.probes.sysinfo;
+
+        assert!(sysinfo.cpu.enabled);
+        assert_eq!(sysinfo.cpu.interval(30), Duration::from_secs(5));
+        assert_eq!(sysinfo.static_info.interval(30), Duration::from_secs(3600));
+        assert!(sysinfo.memory.enabled);
+        assert_eq!(sysinfo.memory.interval(30), Duration::from_secs(30));
+        assert!(!sysinfo.temperature.enabled);
+        assert!(!sysinfo.disk.enabled);
+        assert!(!config.probes.procfs.forks.enabled);
+    }
+
+    #[test]
+    fn test_zero_interval_rejected() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+
+            [probes.sysinfo.static_info]
+            interval_secs = 0
+        "#;
+        assert!(Config::from_str(toml).is_err());
+
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+            collection_interval_secs = 0
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+        assert!(Config::from_str(toml).is_err());
+    }
+
+    #[test]
+    fn test_spool() {
         let toml = r#"
+            node_id = "test-node"
             metrics_collector_addr = "localhost:8080"
 
             [probes.sysinfo]
@@ -153,6 +703,309 @@ mod tests {
             static_info = true
         "#;
 
+        let config = Config::from_str(toml).unwrap();
+        assert_eq!(config.state_dir, "/var/lib/ferrview-node");
+        assert!(config.spool.enabled);
+        assert_eq!(config.spool.max_size_mb, 64);
+        assert_eq!(config.spool.max_age_secs, 86400);
+
+        let toml = format!(
+            "state_dir = \"/tmp/node\"\n{}\n[spool]\nmax_age_secs = 600\n",
+            toml
+        );
+        let config = Config::from_str(&toml).unwrap();
+        assert_eq!(config.state_dir, "/tmp/node");
+        assert!(config.spool.enabled);
+        assert_eq!(config.spool.max_age_secs, 600);
+    }
+
+    #[test]
+    fn test_exporter() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert!(!config.exporter.enabled);
+        assert_eq!(config.exporter.listen_addr, "127.0.0.1:9101");
+
+        let enabled = format!("{}\n[exporter]\nenabled = true\n", toml);
+        let config = Config::from_str(&enabled).unwrap();
+        assert!(config.exporter.enabled);
+
+        let invalid = format!("{}listen_addr = \"localhost\"\n", enabled);
+        assert!(Config::from_str(&invalid).is_err());
+        // Scraped only, without a collector to push to
+        let pull_only = enabled.replace("metrics_collector_addr = \"localhost:8080\"", "");
+        let config = Config::from_str(&pull_only).unwrap();
+        assert!(config.endpoints().is_empty());
+        let registering = format!("{}[registration]\nenabled = true\n", pull_only);
+        assert!(Config::from_str(&registering).is_err());
+        let silent = toml.replace("metrics_collector_addr = \"localhost:8080\"", "");
+        assert!(Config::from_str(&silent).is_err());
+    }
+
+    #[test]
+    fn test_tls() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "https://collector:8443"
+
+            [tls]
+            ca_file = "/etc/ferrview/ca.pem"
+            cert_file = "/etc/ferrview/node.pem"
+            key_file = "/etc/ferrview/node.key"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert!(config.tls.is_configured());
+        assert_eq!(config.tls.ca_file.as_deref(), Some("/etc/ferrview/ca.pem"));
+        assert_eq!(
+            config.tls.key_file.as_deref(),
+            Some("/etc/ferrview/node.key")
+        );
+        assert!(!config.tls.insecure_skip_verify);
+    }
+
+    #[test]
+    fn test_auth_token() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+
+            [auth]
+            token = "fvt_0123456789abcdef"
+            secret = "5ec7e7"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert_eq!(config.auth.token.as_deref(), Some("fvt_0123456789abcdef"));
+        assert_eq!(config.auth.secret.as_deref(), Some("5ec7e7"));
+
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("fvt_0123456789abcdef"));
+        assert!(!debug.contains("5ec7e7"));
+    }
+
+    #[test]
+    fn test_registration() {
+        let toml = r#"
+            node_id = "test-node"
+            metrics_collector_addr = "localhost:8080"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert!(!config.registration.enabled);
+        assert_eq!(config.registration.poll_interval_secs, 30);
+
+        let toml = format!("{}\n[registration]\nenabled = true\n", toml);
+        let config = Config::from_str(&toml).unwrap();
+        assert!(config.registration.enabled);
+
+        let toml = format!("{}poll_interval_secs = 0\n", toml);
+        assert!(Config::from_str(&toml).is_err());
+    }
+
+    #[test]
+    fn test_node_id_is_optional() {
+        let toml = r#"
+            metrics_collector_addr = "localhost:8080"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert!(config.node_id.is_none());
+        assert_eq!(config.node_id_source, NodeIdSource::Generated);
+
+        let toml = format!("node_id_source = \"machine-id\"\n{}", toml);
+        let config = Config::from_str(&toml).unwrap();
+        assert_eq!(config.node_id_source, NodeIdSource::MachineId);
+
+        let toml = toml.replace("machine-id", "hostname");
+        assert!(Config::from_str(&toml).is_err());
+    }
+
+    #[test]
+    fn test_restart_required() {
+        let toml = r#"
+            metrics_collector_addr = "localhost:8080"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+        let running = Config::from_str(toml).unwrap();
+
+        let reloaded = Config::from_str(&toml.replace("cpu = true", "cpu = false")).unwrap();
+        assert!(running.restart_required(&reloaded).is_empty());
+
+        let moved = format!(
+            "node_id = \"web-01\"\nstate_dir = \"/tmp/ferrview\"\n{}",
+            toml
+        );
+        let reloaded = Config::from_str(&moved).unwrap();
+        assert_eq!(
+            running.restart_required(&reloaded),
+            vec!["node_id", "state_dir"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let toml = r#"
+            metrics_collector_addr = "localhost:8080"
+            colection_interval_secs = 30
+
+            [tags]
+            anything = "goes"
+
+            [spool]
+            max_size = 10
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+
+            [probes.sysinfo.disk]
+            interval = 300
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert_eq!(
+            unknown_keys(toml, &config).unwrap(),
+            vec![
+                "colection_interval_secs",
+                "probes.sysinfo.disk.interval",
+                "spool.max_size"
+            ]
+        );
+
+        // The shipped example uses only known keys
+        let example = include_str!("../ferrview-node.toml");
+        let config = Config::from_str(example).unwrap();
+        assert!(unknown_keys(example, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_collectors() {
+        let toml = r#"
+            collector_policy = "fan-out"
+
+            [auth]
+            token = "fvt_prod"
+
+            [[collectors]]
+            name = "prod"
+            addr = "https://prod:8443"
+
+            [[collectors]]
+            name = "staging"
+            addr = "staging:8080"
+            [collectors.auth]
+            token = "fvt_staging"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert_eq!(config.collector_policy, CollectorPolicy::FanOut);
+        let endpoints = config.endpoints();
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(endpoints[0].name, "prod");
+        assert_eq!(endpoints[0].auth.token.as_deref(), Some("fvt_prod"));
+        assert_eq!(endpoints[1].addr, "staging:8080");
+        assert_eq!(endpoints[1].auth.token.as_deref(), Some("fvt_staging"));
+        assert!(unknown_keys(toml, &config).unwrap().is_empty());
+
+        // Fan-out spools per collector, so each needs a distinct name
+        let unnamed = toml.replace("name = \"staging\"", "");
+        assert!(Config::from_str(&unnamed).is_err());
+        let twice = toml.replace("staging\"", "prod\"");
+        assert!(Config::from_str(&twice).is_err());
+        // ...which failover does not
+        let failover = unnamed.replace("fan-out", "failover");
+        let config = Config::from_str(&failover).unwrap();
+        assert_eq!(config.endpoints()[1].name, "staging:8080");
+
+        let both = format!("metrics_collector_addr = \"localhost:8080\"\n{}", toml);
+        assert!(Config::from_str(&both).is_err());
+    }
+
+    #[test]
+    fn test_tags() {
+        let toml = r#"
+            metrics_collector_addr = "localhost:8080"
+
+            [tags]
+            env = "prod"
+            role = "web"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
+        let config = Config::from_str(toml).unwrap();
+        assert_eq!(config.tags.len(), 2);
+        assert_eq!(config.tags["env"], "prod");
+        assert_eq!(config.tags["role"], "web");
+
+        let toml = toml.replace("role = \"web\"", "\"\" = \"web\"");
+        assert!(Config::from_str(&toml).is_err());
+    }
+
+    #[test]
+    fn test_missing_required_field() {
+        let toml = r#"
+            node_id = "test-node"
+
+            [probes.sysinfo]
+            cpu = true
+            memory = true
+            temperature = true
+            static_info = true
+        "#;
+
         let result = Config::from_str(toml);
         assert!(result.is_err());
     }
@@ -0,0 +1,326 @@
+// ferrview-node/src/exporter.rs
+
+//! Serves the latest readings at `/metrics` in the Prometheus text format,
+//! so a node that pushes to its collectors can be scraped as well, and at
+//! `/api/v1/readings` as a batch envelope for a collector that pulls.
+
+use bytes::Bytes;
+use ferrview_common::metrics::{self, MetricKind};
+use ferrview_common::protocol::BatchEnvelope;
+use ferrview_common::{ProbeDataPoint, ProbeValue};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tracing::{debug, error};
+
+const AGENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The latest readings of every metric, shared with the listener
+#[derive(Clone)]
+pub struct Exporter {
+    node_id: Arc<str>,
+    state: Arc<Mutex<State>>,
+    /// Envelopes served, their `sequence`
+    snapshots: Arc<AtomicU64>,
+}
+
+struct State {
+    tags: BTreeMap<String, String>,
+    latest: BTreeMap<String, Vec<ProbeDataPoint>>,
+}
+
+impl Exporter {
+    pub fn new(node_id: &str, tags: &BTreeMap<String, String>) -> Self {
+        Self {
+            node_id: node_id.into(),
+            state: Arc::new(Mutex::new(State {
+                tags: tags.clone(),
+                latest: BTreeMap::new(),
+            })),
+            snapshots: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Take the readings of a collection. A metric's series are replaced
+    /// as a whole, so e.g. a disk that was unmounted disappears.
+    pub fn update(&self, data: &[ProbeDataPoint]) {
+        let mut by_name: BTreeMap<String, Vec<ProbeDataPoint>> = BTreeMap::new();
+        for point in data {
+            by_name
+                .entry(point.probe_name.clone())
+                .or_default()
+                .push(point.clone());
+        }
+        self.state.lock().unwrap().latest.extend(by_name);
+    }
+
+    /// Forget every reading, so metrics of probes a reload disabled go
+    /// away, and take the reloaded tags
+    pub fn reset(&self, tags: &BTreeMap<String, String>) {
+        let mut state = self.state.lock().unwrap();
+        state.latest.clear();
+        state.tags = tags.clone();
+    }
+
+    /// The latest readings as one envelope, as they would have been pushed
+    pub fn envelope(&self) -> BatchEnvelope {
+        let state = self.state.lock().unwrap();
+        let points = state.latest.values().flatten().cloned().collect();
+        let sequence = self.snapshots.fetch_add(1, Ordering::Relaxed);
+
+        BatchEnvelope::new(&self.node_id, AGENT_VERSION, sequence, points)
+            .with_tags(state.tags.clone())
+    }
+
+    /// The latest readings in the Prometheus text format
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        for (name, points) in state.latest.iter() {
+            let def = metrics::lookup(name);
+            let name = sanitize_name(name);
+            // Text values become an info-style series whose value is 1
+            let text = points
+                .iter()
+                .any(|p| matches!(p.probe_value, ProbeValue::Text(_)));
+            let kind = match def.map(|d| d.kind) {
+                Some(MetricKind::Counter) if !text => "counter",
+                Some(_) => "gauge",
+                None if text => "gauge",
+                None => "untyped",
+            };
+
+            if let Some(def) = def {
+                let _ = writeln!(out, "# HELP {} {}", name, escape_help(def.description));
+            }
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+            for point in points {
+                let mut labels: Vec<(String, &str)> = point
+                    .labels
+                    .iter()
+                    .map(|(key, value)| (sanitize_name(key), value.as_str()))
+                    .collect();
+                let value = match &point.probe_value {
+                    ProbeValue::Text(text) => {
+                        labels.push(("value".to_string(), text));
+                        "1".to_string()
+                    }
+                    ProbeValue::Integer(i) => i.to_string(),
+                    value => format_value(value.as_f64().unwrap_or(f64::NAN)),
+                };
+
+                out.push_str(&name);
+                if !labels.is_empty() {
+                    let labels: Vec<String> = labels
+                        .iter()
+                        .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                        .collect();
+                    let _ = write!(out, "{{{}}}", labels.join(","));
+                }
+                let _ = writeln!(out, " {}", value);
+            }
+        }
+
+        out
+    }
+}
+
+/// Answer scrapes on `listener` until the node stops
+pub async fn serve(listener: TcpListener, exporter: Exporter) {
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to accept scrape connection: {}", e);
+                continue;
+            }
+        };
+
+        let exporter = exporter.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(req, exporter.clone()));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Error serving scrape from {}: {}", remote_addr, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    req: Request<Incoming>,
+    exporter: Exporter,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let (status, content_type, body) = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => (StatusCode::OK, CONTENT_TYPE, exporter.render()),
+        (&Method::GET, "/api/v1/readings") => (
+            StatusCode::OK,
+            "application/json",
+            serde_json::to_string(&exporter.envelope()).unwrap_or_else(|_| "{}".to_string()),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            "text/plain; charset=utf-8",
+            "Not found\n".to_string(),
+        ),
+    };
+
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", content_type)
+        .body(Full::new(Bytes::from(body)))
+        .unwrap())
+}
+
+/// A metric or label name with the characters Prometheus does not allow
+/// replaced by `_`
+fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_help(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ferrview_common::metrics::names;
+
+    fn point(name: &str, value: ProbeValue, labels: &[(&str, &str)]) -> ProbeDataPoint {
+        ProbeDataPoint {
+            node_id: "node-1".to_string(),
+            timestamp: 1_704_110_400_000,
+            probe_type: "sysinfo".to_string(),
+            probe_name: name.to_string(),
+            probe_value: value,
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let exporter = Exporter::new("node-1", &BTreeMap::new());
+        exporter.update(&[
+            point(names::FORKS_TOTAL, ProbeValue::Integer(42), &[]),
+            point(
+                names::DISK_USAGE_PERCENT,
+                ProbeValue::Float(12.5),
+                &[("mount", "/var \"log\"")],
+            ),
+            point(
+                names::SYSTEM_HOSTNAME,
+                ProbeValue::Text("web-01".to_string()),
+                &[],
+            ),
+            point("custom.metric", ProbeValue::Boolean(true), &[]),
+        ]);
+
+        let text = exporter.render();
+        assert!(text.contains("# TYPE forks_total counter\nforks_total 42\n"));
+        assert!(text.contains("# TYPE disk_usage_percent gauge\n"));
+        assert!(text.contains("disk_usage_percent{mount=\"/var \\\"log\\\"\"} 12.5\n"));
+        assert!(text.contains("system_hostname{value=\"web-01\"} 1\n"));
+        assert!(text.contains("# TYPE custom_metric untyped\ncustom_metric 1\n"));
+        assert!(!text.contains("# HELP custom_metric"));
+    }
+
+    #[test]
+    fn test_update_replaces_series() {
+        let exporter = Exporter::new("node-1", &BTreeMap::new());
+        exporter.update(&[
+            point(
+                names::DISK_USAGE_PERCENT,
+                ProbeValue::Float(1.0),
+                &[("mount", "/")],
+            ),
+            point(
+                names::DISK_USAGE_PERCENT,
+                ProbeValue::Float(2.0),
+                &[("mount", "/mnt")],
+            ),
+            point(names::FORKS_TOTAL, ProbeValue::Integer(1), &[]),
+        ]);
+        exporter.update(&[point(
+            names::DISK_USAGE_PERCENT,
+            ProbeValue::Float(3.0),
+            &[("mount", "/")],
+        )]);
+
+        let text = exporter.render();
+        assert!(text.contains("disk_usage_percent{mount=\"/\"} 3\n"));
+        assert!(!text.contains("/mnt"));
+        assert!(text.contains("forks_total 1\n"));
+
+        exporter.reset(&BTreeMap::new());
+        assert!(exporter.render().is_empty());
+    }
+
+    #[test]
+    fn test_envelope() {
+        let tags = BTreeMap::from([("env".to_string(), "prod".to_string())]);
+        let exporter = Exporter::new("node-1", &tags);
+        let points = vec![
+            point(names::FORKS_TOTAL, ProbeValue::Integer(7), &[]),
+            point(names::MEMORY_USED_BYTES, ProbeValue::Integer(1024), &[]),
+        ];
+        exporter.update(&points);
+
+        let envelope = exporter.envelope();
+        assert_eq!(envelope.node_id, "node-1");
+        assert_eq!(envelope.tags, tags);
+        assert_eq!(envelope.into_points(), points);
+        assert_eq!(exporter.envelope().sequence, 1);
+    }
+
+    #[test]
+    fn test_sanitize_and_format() {
+        assert_eq!(sanitize_name("cpu.core-usage"), "cpu_core_usage");
+        assert_eq!(sanitize_name("9lives"), "_9lives");
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(0.25), "0.25");
+    }
+}
@@ -0,0 +1,384 @@
+//! The node_id this node reports as, when the config does not set one
+//!
+//! The id is either derived from `/etc/machine-id` or generated on first
+//! start, and is kept in `<state_dir>/node_identity.toml` together with a
+//! fingerprint of the machine-id it was first used on. A different
+//! machine-id on a later start means the state directory was copied along
+//! with a cloned VM image, so the clone picks a new id of its own.
+//!
+//! A node_id set in the config is recorded the same way, to warn about
+//! clones, but a state directory that cannot be written is no reason not
+//! to start.
+
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::config::{Config, NodeIdSource};
+
+const MACHINE_ID_PATH: &str = "/etc/machine-id";
+const IDENTITY_FILE: &str = "node_identity.toml";
+
+/// Mixed into the machine-id hash, so the id we report cannot be traced
+/// back to the machine-id itself
+const MACHINE_ID_CONTEXT: &str = "ferrview-node";
+
+/// Where the node_id came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    Config,
+    MachineId,
+    Generated,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Config => write!(f, "config file"),
+            Origin::MachineId => write!(f, "machine-id"),
+            Origin::Generated => write!(f, "generated"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeIdentity {
+    pub node_id: String,
+    pub origin: Origin,
+    /// The persisted identity belonged to a machine with another machine-id
+    pub cloned: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Persisted {
+    node_id: String,
+    /// Id derived from the machine-id at the time; absent where there is none
+    machine_fingerprint: Option<String>,
+}
+
+/// Work out the node_id for `config`, persisting it in its state directory
+pub fn resolve(config: &Config) -> io::Result<NodeIdentity> {
+    let machine_id = fs::read_to_string(MACHINE_ID_PATH)
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty());
+
+    resolve_in(
+        config.node_id.as_deref(),
+        config.node_id_source,
+        Path::new(&config.state_dir),
+        machine_id.as_deref(),
+    )
+}
+
+/// The node_id `resolve` would most likely settle on, without writing
+/// anything; `None` when one is still to be generated
+pub fn peek(config: &Config) -> Option<String> {
+    if let Some(node_id) = &config.node_id {
+        return Some(node_id.clone());
+    }
+    match config.node_id_source {
+        NodeIdSource::MachineId => fs::read_to_string(MACHINE_ID_PATH)
+            .ok()
+            .map(|id| machine_uuid(id.trim())),
+        NodeIdSource::Generated => load(&Path::new(&config.state_dir).join(IDENTITY_FILE))
+            .ok()
+            .flatten()
+            .map(|persisted| persisted.node_id),
+    }
+}
+
+fn resolve_in(
+    configured: Option<&str>,
+    source: NodeIdSource,
+    state_dir: &Path,
+    machine_id: Option<&str>,
+) -> io::Result<NodeIdentity> {
+    let path = state_dir.join(IDENTITY_FILE);
+    // A configured node_id does not depend on the state file, which then
+    // only serves to spot clones
+    let persisted = match load(&path) {
+        Ok(persisted) => persisted,
+        Err(e) if configured.is_some() => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    let fingerprint = machine_id.map(machine_uuid);
+
+    let cloned = match (&persisted, &fingerprint) {
+        (
+            Some(Persisted {
+                machine_fingerprint: Some(before),
+                ..
+            }),
+            Some(now),
+        ) => before != now,
+        _ => false,
+    };
+
+    let identity = match (configured, source) {
+        (Some(node_id), _) => {
+            let identity = NodeIdentity {
+                node_id: node_id.to_string(),
+                origin: Origin::Config,
+                cloned,
+            };
+            if cloned {
+                warn!(
+                    "This machine's machine-id changed since node_id {} was first used here; \
+                     if it is a clone, give it a node_id of its own",
+                    node_id
+                );
+                // Keep the old fingerprint so the warning repeats until fixed
+                return Ok(identity);
+            }
+            identity
+        }
+        (None, NodeIdSource::MachineId) => {
+            let Some(node_id) = fingerprint.clone() else {
+                return Err(io::Error::other(format!(
+                    "node_id_source is \"machine-id\" but {} is missing or empty",
+                    MACHINE_ID_PATH
+                )));
+            };
+            NodeIdentity {
+                node_id,
+                origin: Origin::MachineId,
+                cloned,
+            }
+        }
+        (None, NodeIdSource::Generated) => match &persisted {
+            Some(persisted) if !cloned => NodeIdentity {
+                node_id: persisted.node_id.clone(),
+                origin: Origin::Generated,
+                cloned,
+            },
+            _ => NodeIdentity {
+                node_id: random_uuid()?,
+                origin: Origin::Generated,
+                cloned,
+            },
+        },
+    };
+
+    if cloned && let Some(persisted) = &persisted {
+        warn!(
+            "{} was written on a machine with another machine-id; \
+             this looks like a clone of node {}, reporting as {} instead",
+            path.display(),
+            persisted.node_id,
+            identity.node_id
+        );
+    }
+
+    // Without a machine-id now, keep the one recorded before
+    let fingerprint = fingerprint.or_else(|| {
+        persisted
+            .as_ref()
+            .and_then(|p| p.machine_fingerprint.clone())
+    });
+    let unchanged = persisted
+        .as_ref()
+        .is_some_and(|p| p.node_id == identity.node_id && p.machine_fingerprint == fingerprint);
+    if !unchanged {
+        if persisted.is_none() && identity.origin == Origin::Generated {
+            info!("Generated node_id {}", identity.node_id);
+        }
+        let saved = save(
+            &path,
+            &Persisted {
+                node_id: identity.node_id.clone(),
+                machine_fingerprint: fingerprint,
+            },
+        );
+        match saved {
+            Err(e) if identity.origin == Origin::Config => {
+                warn!(
+                    "Failed to write {}, clones of this machine will not be noticed: {}",
+                    path.display(),
+                    e
+                );
+            }
+            saved => saved?,
+        }
+    }
+
+    Ok(identity)
+}
+
+fn load(path: &Path) -> io::Result<Option<Persisted>> {
+    match fs::read_to_string(path) {
+        Ok(content) => basic_toml::from_str(&content)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn save(path: &Path, persisted: &Persisted) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = basic_toml::to_string(persisted)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    let tmp_path = path.with_extension("toml.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// A UUID derived from the machine-id (SHA-256 based, so version 8)
+fn machine_uuid(machine_id: &str) -> String {
+    let hash = digest::digest(
+        &digest::SHA256,
+        format!("{}\n{}", MACHINE_ID_CONTEXT, machine_id).as_bytes(),
+    );
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash.as_ref()[..16]);
+    format_uuid(bytes, 8)
+}
+
+/// A random version 4 UUID
+fn random_uuid() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| io::Error::other("no system randomness available"))?;
+    Ok(format_uuid(bytes, 4))
+}
+
+fn format_uuid(mut bytes: [u8; 16], version: u8) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // RFC 9562 variant
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+
+    const MACHINE_A: &str = "3d1219c7c4c5404aaa1f6d2a48adfda4";
+    const MACHINE_B: &str = "9b5e0f4c1c0e4d8e8a4e0b6f7f3c2a11";
+
+    #[test]
+    fn test_generated_id_is_kept() {
+        let dir = TestDir::new("identity-generated");
+
+        let first = resolve_in(None, NodeIdSource::Generated, dir.path(), Some(MACHINE_A)).unwrap();
+        assert_eq!(first.origin, Origin::Generated);
+        assert_eq!(first.node_id.len(), 36);
+        assert_eq!(&first.node_id[14..15], "4");
+        assert!(!first.cloned);
+
+        let again = resolve_in(None, NodeIdSource::Generated, dir.path(), Some(MACHINE_A)).unwrap();
+        assert_eq!(again, first);
+
+        // Without a machine-id there is nothing to compare against
+        let unknown = resolve_in(None, NodeIdSource::Generated, dir.path(), None).unwrap();
+        assert_eq!(unknown.node_id, first.node_id);
+        let clone = resolve_in(None, NodeIdSource::Generated, dir.path(), Some(MACHINE_B)).unwrap();
+        assert!(clone.cloned);
+    }
+
+    #[test]
+    fn test_clone_gets_new_generated_id() {
+        let dir = TestDir::new("identity-clone");
+
+        let original =
+            resolve_in(None, NodeIdSource::Generated, dir.path(), Some(MACHINE_A)).unwrap();
+        let clone = resolve_in(None, NodeIdSource::Generated, dir.path(), Some(MACHINE_B)).unwrap();
+        assert!(clone.cloned);
+        assert_ne!(clone.node_id, original.node_id);
+
+        // The clone's new identity sticks
+        let again = resolve_in(None, NodeIdSource::Generated, dir.path(), Some(MACHINE_B)).unwrap();
+        assert!(!again.cloned);
+        assert_eq!(again.node_id, clone.node_id);
+    }
+
+    #[test]
+    fn test_machine_id_source() {
+        let dir = TestDir::new("identity-machine-id");
+
+        let a = resolve_in(None, NodeIdSource::MachineId, dir.path(), Some(MACHINE_A)).unwrap();
+        assert_eq!(a.origin, Origin::MachineId);
+        assert_eq!(&a.node_id[14..15], "8");
+        assert!(!a.node_id.contains(MACHINE_A));
+        assert_eq!(a.node_id, machine_uuid(MACHINE_A));
+
+        let b = resolve_in(None, NodeIdSource::MachineId, dir.path(), Some(MACHINE_B)).unwrap();
+        assert!(b.cloned);
+        assert_ne!(b.node_id, a.node_id);
+
+        assert!(resolve_in(None, NodeIdSource::MachineId, dir.path(), None).is_err());
+    }
+
+    #[test]
+    fn test_configured_id_wins() {
+        let dir = TestDir::new("identity-configured");
+
+        let first = resolve_in(
+            Some("web-01"),
+            NodeIdSource::Generated,
+            dir.path(),
+            Some(MACHINE_A),
+        )
+        .unwrap();
+        assert_eq!(first.node_id, "web-01");
+        assert_eq!(first.origin, Origin::Config);
+
+        // A clone keeps the configured id but is flagged, every time
+        for _ in 0..2 {
+            let clone = resolve_in(
+                Some("web-01"),
+                NodeIdSource::Generated,
+                dir.path(),
+                Some(MACHINE_B),
+            )
+            .unwrap();
+            assert_eq!(clone.node_id, "web-01");
+            assert!(clone.cloned);
+        }
+    }
+
+    #[test]
+    fn test_unwritable_state_dir() {
+        let dir = TestDir::new("identity-unwritable");
+        // A file where the state directory should be
+        fs::create_dir_all(dir.path()).unwrap();
+        let file = dir.path().join("not-a-dir");
+        fs::write(&file, "").unwrap();
+        let state_dir = file.join("state");
+
+        let configured = resolve_in(
+            Some("web-01"),
+            NodeIdSource::Generated,
+            &state_dir,
+            Some(MACHINE_A),
+        )
+        .unwrap();
+        assert_eq!(configured.node_id, "web-01");
+        assert_eq!(configured.origin, Origin::Config);
+
+        // A generated id that cannot be kept would change on every start
+        assert!(resolve_in(None, NodeIdSource::Generated, &state_dir, Some(MACHINE_A)).is_err());
+    }
+}
@@ -1,22 +1,34 @@
 use argh::FromArgs;
+use ferrview_common::logging::{
+    self, DEFAULT_LOG_MAX_SIZE_MB, LogFormat, LogOptions, parse_log_max_size_mb,
+};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use sysinfo::{Components, Disks, Networks, System};
-use tracing::{debug, error, info};
-use tracing_subscriber::{EnvFilter, fmt::time::UtcTime};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::fmt::time::UtcTime;
 
 use crate::{
-    client::http::HttpClient,
-    client::retry::send_with_retry,
-    config::Config,
-    probes::{
-        procfs,
-        sysinfo::{cpu, disk, mem, network, statik, temp},
-    },
+    client::collector::Collector,
+    client::delivery,
+    client::http::{ClientError, HttpClient},
+    client::registration::{Credentials, Registration},
+    client::spool::Spool,
+    commands::Command,
+    config::{COLLECTOR_LIST, CollectorPolicy, Config},
+    exporter::Exporter,
+    probes::ProbeRegistry,
     utils::timestamp::get_utc_formatter,
 };
 
 mod client;
+mod commands;
 mod config;
+mod exporter;
+mod identity;
 mod probes;
 mod utils;
 
@@ -31,120 +43,421 @@ pub struct Argz {
     /// config file location
     #[argh(option, default = "default_config_file()")]
     config_file: String,
+
+    /// log format: text (default) or json
+    #[argh(option, default = "LogFormat::Text")]
+    log_format: LogFormat,
+
+    /// write logs to this file instead of the console
+    #[argh(option)]
+    log_file: Option<PathBuf>,
+
+    /// size in MB at which the log file is rotated (default 10)
+    #[argh(
+        option,
+        default = "DEFAULT_LOG_MAX_SIZE_MB",
+        from_str_fn(parse_log_max_size_mb)
+    )]
+    log_max_size_mb: u64,
+
+    #[argh(subcommand)]
+    command: Option<Command>,
 }
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    let timer = UtcTime::new(get_utc_formatter());
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let argz: Argz = argh::from_env();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(env_filter)
-        .with_timer(timer)
-        .init();
+    // Initialize tracing. Commands print their results to stdout, so
+    // their logs go to stderr.
+    let options = LogOptions {
+        format: argz.log_format,
+        file: argz.log_file,
+        max_size_bytes: argz.log_max_size_mb.saturating_mul(1024 * 1024),
+    };
+    let stderr = !matches!(argz.command, None | Some(Command::Run(_)));
+    if let Err(e) = logging::init(&options, UtcTime::new(get_utc_formatter()), stderr) {
+        let path = options.file.unwrap_or_default();
+        eprintln!("Failed to open log file {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
 
-    info!("Starting ferrview-node");
+    match argz.command {
+        None | Some(Command::Run(_)) => run(&argz.config_file).await,
+        Some(command) => {
+            if let Err(e) = commands::run(command, &argz.config_file).await {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
-    let argz: Argz = argh::from_env();
-    debug!("Args: {:?}", argz);
-    info!("Config file: {}", argz.config_file);
+/// Collect and send metrics until interrupted
+async fn run(config_file: &str) {
+    info!("Starting ferrview-node");
+    info!("Config file: {}", config_file);
 
-    let config = Config::load(&argz.config_file).expect("Failed to load configuration");
+    let config = Config::load(config_file).expect("Failed to load configuration");
 
     debug!("Config: {:?}", config);
-    info!("Node ID: {}", config.node_id);
-    info!("Collector address: {}", config.metrics_collector_addr);
-    info!("Collection interval: {}s", config.collection_interval_secs);
 
-    // Initialize HTTP client
-    let client = HttpClient::new(&config.metrics_collector_addr);
+    let identity = identity::resolve(&config).expect("Failed to determine node_id");
+    let node_id = identity.node_id;
+    info!("Node ID: {} ({})", node_id, identity.origin);
+    log_collectors(&config);
+    info!(
+        "Default collection interval: {}s",
+        config.collection_interval_secs
+    );
+
+    // Initialize HTTP clients
+    let clients = build_clients(&config, &node_id).expect("Failed to initialize HTTP client");
+    let collectors = config
+        .endpoints()
+        .iter()
+        .zip(clients)
+        .map(|(endpoint, client)| Collector::new(endpoint.name, client))
+        .collect();
 
-    // Initialize system info
-    let mut sys = System::new_all();
-    let mut disks = Disks::new_with_refreshed_list();
-    let mut components = Components::new_with_refreshed_list();
-    let mut networks = Networks::new_with_refreshed_list();
+    let exporter = start_exporter(&config, &node_id).await;
 
-    // Perform initial CPU refresh with delay for accurate first reading
-    info!("Performing initial CPU refresh for accurate readings");
-    sys.refresh_cpu_usage();
-    tokio::time::sleep(Duration::from_millis(200)).await;
-    sys.refresh_cpu_usage();
+    let mut registry = ProbeRegistry::from_config(&config);
+    registry.init();
 
-    let interval = Duration::from_secs(config.collection_interval_secs);
+    // Sending runs on tasks of its own so a slow or unreachable collector
+    // never holds up the probe schedule. With fan-out every collector gets
+    // a task, and a spool, of its own.
+    let groups = group_by_route(config.collector_policy, collectors);
+    let fan_out = groups.len() > 1;
+    let routes: Vec<Route> = groups
+        .into_iter()
+        .map(|collectors| {
+            let spool = open_spool(&config, fan_out.then_some(collectors[0].name.as_str()))
+                .map(|spool| Arc::new(Mutex::new(spool)));
+            let registration =
+                start_registration(&config, &node_id, config_file, &collectors[0].client);
+            let (batches, queue) = delivery::Queue::new(spool.clone());
+            let (reloads, reloaded) = mpsc::unbounded_channel();
+            let task = tokio::spawn(delivery::deliver(
+                collectors,
+                spool,
+                registration,
+                queue,
+                reloaded,
+            ));
+            Route {
+                batches,
+                reloads,
+                task,
+            }
+        })
+        .collect();
 
     info!("Starting collection loop");
 
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+    let mut reloads = reload_requests();
+
     loop {
-        // Refresh system information
-        sys.refresh_all();
+        if registry.next_due().is_none() {
+            warn!("No probes enabled, nothing to collect");
+        }
 
-        let mut all_data = Vec::new();
+        tokio::select! {
+            _ = sleep_until(registry.next_due()) => {}
+            Some(()) = reloads.recv() => {
+                info!("Received SIGHUP, reloading {}", config_file);
+                reload(config_file, &config, &node_id, &mut registry, &routes, exporter.as_ref());
+                continue;
+            }
+            _ = &mut shutdown => {
+                info!("Received shutdown signal");
+                break;
+            }
+        }
 
-        // Collect all enabled probes
+        let data = registry.collect_due(&node_id, Instant::now());
+        debug!("Collected {} metrics", data.len());
+        if let Some(exporter) = &exporter {
+            exporter.update(&data);
+        }
 
-        if config.probes.sysinfo.static_info {
-            let static_info = statik::probe_static_info(&config.node_id);
-            debug!("Collected {} static info metrics", static_info.len());
-            all_data.extend(static_info);
+        if !data.is_empty() && routes.iter().any(|route| !route.batches.push(data.clone())) {
+            error!("Batch sender stopped unexpectedly");
+            break;
         }
+    }
 
-        if config.probes.sysinfo.cpu {
-            let cpu_data = cpu::probe_cpu(&sys, &config.node_id);
-            debug!("Collected {} CPU metrics", cpu_data.len());
-            all_data.extend(cpu_data);
+    // Let the senders flush whatever is still queued
+    for route in routes {
+        drop(route.batches);
+        drop(route.reloads);
+        if let Err(e) = route.task.await {
+            error!("Error waiting for batch sender: {}", e);
         }
+    }
+
+    registry.teardown();
+    info!("Shutdown complete");
+}
 
-        if config.probes.sysinfo.memory {
-            let mem_data = mem::probe_memory(&sys, &config.node_id);
-            debug!("Collected {} memory metrics", mem_data.len());
-            all_data.extend(mem_data);
+/// Collectors sharing a queue, a spool and a delivery task: all of them
+/// for failover, each on its own for fan-out
+struct Route {
+    batches: delivery::Queue,
+    reloads: mpsc::UnboundedSender<Vec<HttpClient>>,
+    task: JoinHandle<()>,
+}
+
+/// Split collectors, or their clients, into the routes `policy` asks for.
+/// Without collectors there is nothing to route.
+fn group_by_route<T>(policy: CollectorPolicy, collectors: Vec<T>) -> Vec<Vec<T>> {
+    match policy {
+        _ if collectors.is_empty() => Vec::new(),
+        CollectorPolicy::Failover => vec![collectors],
+        CollectorPolicy::FanOut => collectors.into_iter().map(|c| vec![c]).collect(),
+    }
+}
+
+/// A client for every collector, in config order
+fn build_clients(config: &Config, node_id: &str) -> Result<Vec<HttpClient>, ClientError> {
+    config
+        .endpoints()
+        .iter()
+        .map(|endpoint| {
+            HttpClient::new(
+                endpoint.addr,
+                node_id,
+                &config.tags,
+                config.compression,
+                endpoint.tls,
+                endpoint.auth,
+            )
+        })
+        .collect()
+}
+
+fn log_collectors(config: &Config) {
+    let endpoints = config.endpoints();
+    match endpoints.as_slice() {
+        [] => {
+            info!("No collector, readings are only served by the exporter");
+            return;
+        }
+        [endpoint] => {
+            info!("Collector address: {}", endpoint.addr);
+            return;
         }
+        _ => {}
+    }
+    for endpoint in &endpoints {
+        info!("Collector {}: {}", endpoint.name, endpoint.addr);
+    }
+    info!("Collector policy: {}", config.collector_policy);
+}
 
-        if config.probes.sysinfo.disk {
-            let disk_data = disk::probe_disks(&mut disks, &config.node_id);
-            debug!("Collected {} disk metrics", disk_data.len());
-            all_data.extend(disk_data);
+/// Re-read the config file and switch to its probes and collector
+/// settings. A config that fails to load or validate is logged and the
+/// running one kept. `running` is the config the node started with.
+fn reload(
+    config_file: &str,
+    running: &Config,
+    node_id: &str,
+    registry: &mut ProbeRegistry,
+    routes: &[Route],
+    exporter: Option<&Exporter>,
+) {
+    let config = match Config::load(config_file) {
+        Ok(config) => config,
+        Err(e) => {
+            error!(
+                "Keeping the current config, {} is invalid: {}",
+                config_file, e
+            );
+            return;
         }
+    };
 
-        if config.probes.sysinfo.temperature {
-            let temp_data = temp::probe_temperature(&mut components, &config.node_id);
-            debug!("Collected {} temperature metrics", temp_data.len());
-            all_data.extend(temp_data);
+    let clients = match build_clients(&config, node_id) {
+        Ok(clients) => clients,
+        Err(e) => {
+            error!(
+                "Keeping the current config, {} is invalid: {}",
+                config_file, e
+            );
+            return;
         }
+    };
+    // Only picks up credentials from an approved registration; a pending
+    // one is still followed by the sender
+    if let [client] = clients.as_slice() {
+        start_registration(&config, node_id, config_file, client);
+    }
 
-        if config.probes.sysinfo.network {
-            let network_data = network::probe_networks(&mut networks, &config.node_id);
-            debug!("Collected {} network metrics", network_data.len());
-            all_data.extend(network_data);
+    let ignored = running.restart_required(&config);
+    if !ignored.is_empty() {
+        warn!(
+            "Changes to {} take effect after a restart",
+            ignored.join(", ")
+        );
+    }
+
+    // The senders swap clients by position, which only works for the
+    // collectors they were started with
+    if ignored.contains(&COLLECTOR_LIST) {
+        warn!("Keeping the current collector settings");
+    } else {
+        log_collectors(&config);
+        let groups = group_by_route(config.collector_policy, clients);
+        for (route, clients) in routes.iter().zip(groups) {
+            if route.reloads.send(clients).is_err() {
+                error!("Batch sender stopped unexpectedly");
+            }
         }
+    }
+    registry.replace(ProbeRegistry::from_config(&config));
+    info!("Enabled probes: {}", registry.names().join(", "));
+    // Probes that stay enabled fill it again when they are next due
+    if let Some(exporter) = exporter {
+        exporter.reset(&config.tags);
+    }
+}
+
+/// Listen for scrapes if `[exporter]` is enabled
+async fn start_exporter(config: &Config, node_id: &str) -> Option<Exporter> {
+    if !config.exporter.enabled {
+        return None;
+    }
+
+    let listener = tokio::net::TcpListener::bind(&config.exporter.listen_addr)
+        .await
+        .expect("Failed to start the exporter");
+    info!(
+        "Serving metrics on http://{}/metrics",
+        config.exporter.listen_addr
+    );
+
+    let exporter = Exporter::new(node_id, &config.tags);
+    tokio::spawn(exporter::serve(listener, exporter.clone()));
+    Some(exporter)
+}
+
+/// A message for every SIGHUP. Where there is no SIGHUP the channel is
+/// closed from the start.
+fn reload_requests() -> mpsc::UnboundedReceiver<()> {
+    let (requests, receiver) = mpsc::unbounded_channel();
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
 
-        if config.probes.procfs.forks {
-            match procfs::forks::probe_forks(&config.node_id) {
-                Ok(forks_data) => {
-                    debug!("Collected {} fork metrics", forks_data.len());
-                    all_data.extend(forks_data);
-                }
-                Err(e) => error!("Failed to collect fork metrics: {}", e),
+        match signal(SignalKind::hangup()) {
+            Ok(mut hangup) => {
+                tokio::spawn(async move {
+                    while hangup.recv().await.is_some() && requests.send(()).is_ok() {}
+                });
             }
+            Err(e) => error!("Failed to listen for SIGHUP, config reload disabled: {}", e),
         }
+    }
+    #[cfg(not(unix))]
+    drop(requests);
+
+    receiver
+}
 
-        info!("Collected {} total metrics", all_data.len());
+/// Sleep until `deadline`, forever without one
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Credentials saved by an earlier registration, or a registration still
+/// to be approved. Credentials in `[auth]` take precedence over both.
+fn start_registration(
+    config: &Config,
+    node_id: &str,
+    config_file: &str,
+    client: &HttpClient,
+) -> Option<Registration> {
+    if !config.registration.enabled || client.has_credentials() {
+        return None;
+    }
+
+    let path = Credentials::path_for(Path::new(config_file));
+    let mut credentials = Credentials::load(&path).unwrap_or_else(|e| {
+        warn!(
+            "Ignoring unreadable credentials file {}: {}",
+            path.display(),
+            e
+        );
+        Credentials::default()
+    });
+    if let Some(issued_to) = &credentials.node_id
+        && issued_to != node_id
+    {
+        // E.g. the config directory came along with a cloned image
+        warn!(
+            "Ignoring credentials in {}, issued to node {}; registering again",
+            path.display(),
+            issued_to
+        );
+        credentials = Credentials::default();
+    }
 
-        // Send batch with retry
-        if !all_data.is_empty() {
-            let data_clone = all_data.clone();
-            match send_with_retry(|| client.send_batch(data_clone.clone()), 3).await {
-                Ok(_) => info!("Batch sent successfully"),
-                Err(e) => error!("Failed to send batch after retries: {}", e),
+    if credentials.is_approved() {
+        match client.set_credentials(credentials.token.as_deref(), credentials.secret.as_deref()) {
+            Ok(()) => {
+                info!("Using credentials from {}", path.display());
+                return None;
+            }
+            Err(e) => {
+                warn!(
+                    "Ignoring invalid credentials in {}, registering again: {}",
+                    path.display(),
+                    e
+                );
+                credentials = Credentials::default();
             }
-        } else {
-            info!("No metrics collected, skipping send");
         }
+    }
+
+    Some(Registration::new(
+        node_id,
+        path,
+        credentials,
+        Duration::from_secs(config.registration.poll_interval_secs),
+    ))
+}
 
-        // Wait for next collection interval
-        debug!("Sleeping for {:?}", interval);
-        tokio::time::sleep(interval).await;
+/// The spool in `<state_dir>/spool`, or in a directory of its own below
+/// that for one of several fanned-out collectors
+fn open_spool(config: &Config, collector: Option<&str>) -> Option<Spool> {
+    if !config.spool.enabled {
+        return None;
+    }
+
+    let mut dir = Path::new(&config.state_dir).join("spool");
+    if let Some(name) = collector {
+        dir.push(name);
+    }
+    match Spool::open(
+        &dir,
+        config.spool.max_size_mb * 1024 * 1024,
+        Duration::from_secs(config.spool.max_age_secs),
+    ) {
+        Ok(spool) => Some(spool),
+        Err(e) => {
+            warn!(
+                "Cannot open spool at {}, unsent batches will be lost: {}",
+                dir.display(),
+                e
+            );
+            None
+        }
     }
 }
@@ -1,2 +1,32 @@
+use ferrview_common::ProbeDataPoint;
+
 pub mod procfs;
+mod registry;
 pub mod sysinfo;
+
+pub use registry::ProbeRegistry;
+
+/// Error returned by a probe; any error type will do
+pub type ProbeError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A source of metrics, driven by the collection loop through a
+/// [`ProbeRegistry`]
+///
+/// Probes own whatever state they need between collections (sysinfo
+/// handles, previous counter values, open files).
+pub trait Probe: Send {
+    /// Short name, matching the probe's key in the config file
+    fn name(&self) -> &'static str;
+
+    /// One-time setup before the first collection. A probe that fails to
+    /// initialize is left out of the registry.
+    fn init(&mut self) -> Result<(), ProbeError> {
+        Ok(())
+    }
+
+    /// Take one set of readings
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError>;
+
+    /// Release resources on shutdown
+    fn teardown(&mut self) {}
+}
@@ -2,6 +2,8 @@ use ferrview_common::ProbeDataPoint;
 use std::io;
 use tracing::info;
 
+use crate::probes::{Probe, ProbeError};
+
 /// Probe the fork rate by reading /proc/stat
 ///
 /// This probe reads the cumulative number of forks (processes created) since boot
@@ -11,6 +13,8 @@ use tracing::info;
 #[cfg(target_os = "linux")]
 pub fn probe_forks(node_id: &str) -> Result<Vec<ProbeDataPoint>, io::Error> {
     use crate::utils::timestamp::get_utc_timestamp;
+    use ferrview_common::metrics::FORKS_TOTAL;
+    use std::collections::BTreeMap;
     use std::fs;
     use tracing::warn;
 
@@ -26,16 +30,18 @@ pub fn probe_forks(node_id: &str) -> Result<Vec<ProbeDataPoint>, io::Error> {
     for line in stat_content.lines() {
         if line.starts_with("processes ") {
             let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let fork_count = parts[1];
+            if parts.len() >= 2
+                && let Ok(fork_count) = parts[1].parse::<u64>()
+            {
                 info!("Fork count: {}", fork_count);
 
                 data_points.push(ProbeDataPoint {
                     node_id: node_id.to_string(),
-                    timestamp: timestamp.clone(),
-                    probe_type: "procfs".to_string(),
-                    probe_name: "forks_total".to_string(),
-                    probe_value: fork_count.to_string(),
+                    timestamp,
+                    probe_type: FORKS_TOTAL.probe_type.to_string(),
+                    probe_name: FORKS_TOTAL.name.to_string(),
+                    probe_value: fork_count.into(),
+                    labels: BTreeMap::new(),
                 });
 
                 info!("Collected {} fork metrics", data_points.len());
@@ -55,6 +61,18 @@ pub fn probe_forks(_node_id: &str) -> Result<Vec<ProbeDataPoint>, io::Error> {
     Ok(Vec::new())
 }
 
+pub struct ForksProbe;
+
+impl Probe for ForksProbe {
+    fn name(&self) -> &'static str {
+        "forks"
+    }
+
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+        Ok(probe_forks(node_id)?)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,7 +93,10 @@ mod tests {
         assert_eq!(dp.probe_name, "forks_total");
 
         // Value should be a valid positive number
-        let value: u64 = dp.probe_value.parse().expect("fork count should be a number");
+        let value = dp
+            .probe_value
+            .as_i64()
+            .expect("fork count should be a number");
         assert!(value > 0, "fork count should be positive");
     }
 
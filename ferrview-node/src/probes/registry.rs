@@ -0,0 +1,383 @@
+use ferrview_common::ProbeDataPoint;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, error, info, warn};
+
+use crate::config::Config;
+use crate::probes::Probe;
+use crate::probes::procfs::forks::ForksProbe;
+use crate::probes::sysinfo::{
+    cpu::CpuProbe, disk::DiskProbe, mem::MemoryProbe, network::NetworkProbe,
+    statik::StaticInfoProbe, temp::TemperatureProbe,
+};
+
+/// The set of probes the collection loop runs, each on its own interval
+#[derive(Default)]
+pub struct ProbeRegistry {
+    probes: Vec<Scheduled>,
+}
+
+struct Scheduled {
+    probe: Box<dyn Probe>,
+    interval: Duration,
+    next_due: Instant,
+}
+
+impl ProbeRegistry {
+    /// Build a registry holding every built-in probe enabled in `config`
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = Self::default();
+        let default_secs = config.collection_interval_secs;
+        let sysinfo = &config.probes.sysinfo;
+        let procfs = &config.probes.procfs;
+
+        if sysinfo.static_info.enabled {
+            registry.register(
+                Box::new(StaticInfoProbe),
+                sysinfo.static_info.interval(default_secs),
+            );
+        }
+        if sysinfo.cpu.enabled {
+            registry.register(
+                Box::new(CpuProbe::new()),
+                sysinfo.cpu.interval(default_secs),
+            );
+        }
+        if sysinfo.memory.enabled {
+            registry.register(
+                Box::new(MemoryProbe::new()),
+                sysinfo.memory.interval(default_secs),
+            );
+        }
+        if sysinfo.disk.enabled {
+            registry.register(
+                Box::new(DiskProbe::new()),
+                sysinfo.disk.interval(default_secs),
+            );
+        }
+        if sysinfo.temperature.enabled {
+            registry.register(
+                Box::new(TemperatureProbe::new()),
+                sysinfo.temperature.interval(default_secs),
+            );
+        }
+        if sysinfo.network.enabled {
+            registry.register(
+                Box::new(NetworkProbe::new()),
+                sysinfo.network.interval(default_secs),
+            );
+        }
+        if procfs.forks.enabled {
+            registry.register(Box::new(ForksProbe), procfs.forks.interval(default_secs));
+        }
+
+        registry
+    }
+
+    /// Add a probe that runs every `interval`, starting at the first tick
+    pub fn register(&mut self, probe: Box<dyn Probe>, interval: Duration) {
+        debug!("Registered probe {} every {:?}", probe.name(), interval);
+        self.probes.push(Scheduled {
+            probe,
+            interval,
+            next_due: Instant::now(),
+        });
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.probes.iter().map(|entry| entry.probe.name()).collect()
+    }
+
+    /// Initialize every probe, dropping those that fail. All remaining
+    /// probes become due immediately.
+    pub fn init(&mut self) {
+        self.probes.retain_mut(|entry| match entry.probe.init() {
+            Ok(()) => true,
+            Err(e) => {
+                error!(
+                    "Failed to initialize {} probe, disabling it: {}",
+                    entry.probe.name(),
+                    e
+                );
+                false
+            }
+        });
+
+        let now = Instant::now();
+        for entry in &mut self.probes {
+            entry.next_due = now;
+        }
+        info!("Enabled probes: {}", self.names().join(", "));
+    }
+
+    /// When the next probe is due, `None` if no probe is registered
+    pub fn next_due(&self) -> Option<Instant> {
+        self.probes.iter().map(|entry| entry.next_due).min()
+    }
+
+    /// Run every probe due at `now` and merge their readings. A failing
+    /// probe is logged and skipped so the others still report.
+    ///
+    /// Each probe's schedule advances by whole intervals from its previous
+    /// due time rather than from `now`, so time spent collecting and
+    /// sending never stretches the period. Ticks missed entirely (a stalled
+    /// process, a suspended host) are skipped rather than run in a burst.
+    pub fn collect_due(&mut self, node_id: &str, now: Instant) -> Vec<ProbeDataPoint> {
+        let mut all_data = Vec::new();
+
+        for entry in self.probes.iter_mut().filter(|entry| entry.next_due <= now) {
+            match entry.probe.collect(node_id) {
+                Ok(data) => {
+                    debug!("Collected {} {} metrics", data.len(), entry.probe.name());
+                    all_data.extend(data);
+                }
+                Err(e) => error!("Failed to collect {} metrics: {}", entry.probe.name(), e),
+            }
+
+            entry.next_due += entry.interval;
+            if entry.next_due <= now {
+                let missed = (now - entry.next_due).as_nanos() / entry.interval.as_nanos() + 1;
+                warn!(
+                    "Probe {} fell behind, skipping {} tick(s)",
+                    entry.probe.name(),
+                    missed
+                );
+                entry.next_due += entry.interval * missed as u32;
+            }
+        }
+
+        all_data
+    }
+
+    /// Switch to the probes in `other`, as built from a reloaded config.
+    /// Probes in both keep their state and schedule, only taking the new
+    /// interval; probes no longer enabled are torn down and new ones are
+    /// initialized and due immediately.
+    pub fn replace(&mut self, other: ProbeRegistry) {
+        let now = Instant::now();
+        let mut current = std::mem::take(&mut self.probes);
+
+        for mut entry in other.probes {
+            let name = entry.probe.name();
+            match current.iter().position(|old| old.probe.name() == name) {
+                Some(index) => {
+                    let old = current.swap_remove(index);
+                    if old.interval != entry.interval {
+                        info!(
+                            "Probe {} now runs every {:?}, was {:?}",
+                            name, entry.interval, old.interval
+                        );
+                    }
+                    self.probes.push(Scheduled {
+                        probe: old.probe,
+                        next_due: old.next_due.min(now + entry.interval),
+                        interval: entry.interval,
+                    });
+                }
+                None => match entry.probe.init() {
+                    Ok(()) => {
+                        info!("Enabled probe {}", name);
+                        entry.next_due = now;
+                        self.probes.push(entry);
+                    }
+                    Err(e) => error!("Failed to initialize {} probe, disabling it: {}", name, e),
+                },
+            }
+        }
+
+        for mut entry in current {
+            info!("Disabled probe {}", entry.probe.name());
+            entry.probe.teardown();
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        for entry in &mut self.probes {
+            debug!("Tearing down probe {}", entry.probe.name());
+            entry.probe.teardown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probes::ProbeError;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct FakeProbe {
+        name: &'static str,
+        fail_init: bool,
+        fail_collect: bool,
+        teardowns: Arc<AtomicUsize>,
+    }
+
+    impl FakeProbe {
+        fn new(name: &'static str, teardowns: &Arc<AtomicUsize>) -> Self {
+            Self {
+                name,
+                fail_init: false,
+                fail_collect: false,
+                teardowns: Arc::clone(teardowns),
+            }
+        }
+    }
+
+    impl Probe for FakeProbe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn init(&mut self) -> Result<(), ProbeError> {
+            if self.fail_init {
+                return Err("no such device".into());
+            }
+            Ok(())
+        }
+
+        fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+            if self.fail_collect {
+                return Err("read failed".into());
+            }
+            Ok(vec![ProbeDataPoint {
+                node_id: node_id.to_string(),
+                timestamp: 0,
+                probe_type: "fake".to_string(),
+                probe_name: self.name.to_string(),
+                probe_value: 1u64.into(),
+                labels: BTreeMap::new(),
+            }])
+        }
+
+        fn teardown(&mut self) {
+            self.teardowns.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn names(data: &[ProbeDataPoint]) -> Vec<&str> {
+        data.iter().map(|p| p.probe_name.as_str()).collect()
+    }
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_collect_skips_failing_probes() {
+        let teardowns = Arc::new(AtomicUsize::new(0));
+        let mut registry = ProbeRegistry::default();
+
+        registry.register(Box::new(FakeProbe::new("a", &teardowns)), SECOND);
+        registry.register(
+            Box::new(FakeProbe {
+                fail_init: true,
+                ..FakeProbe::new("b", &teardowns)
+            }),
+            SECOND,
+        );
+        registry.register(
+            Box::new(FakeProbe {
+                fail_collect: true,
+                ..FakeProbe::new("c", &teardowns)
+            }),
+            SECOND,
+        );
+        registry.register(Box::new(FakeProbe::new("d", &teardowns)), SECOND);
+
+        registry.init();
+        assert_eq!(registry.names(), vec!["a", "c", "d"]);
+
+        let data = registry.collect_due("node", Instant::now());
+        assert_eq!(names(&data), vec!["a", "d"]);
+        assert!(data.iter().all(|p| p.node_id == "node"));
+
+        registry.teardown();
+        assert_eq!(teardowns.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_probes_run_on_their_own_interval() {
+        let teardowns = Arc::new(AtomicUsize::new(0));
+        let mut registry = ProbeRegistry::default();
+        registry.register(Box::new(FakeProbe::new("fast", &teardowns)), 5 * SECOND);
+        registry.register(Box::new(FakeProbe::new("slow", &teardowns)), 60 * SECOND);
+        registry.init();
+
+        let start = registry.next_due().unwrap();
+        assert_eq!(
+            names(&registry.collect_due("n", start)),
+            vec!["fast", "slow"]
+        );
+        assert_eq!(registry.next_due(), Some(start + 5 * SECOND));
+
+        // Collection running late does not push the schedule back
+        let late = start + 5 * SECOND + Duration::from_millis(800);
+        assert_eq!(names(&registry.collect_due("n", late)), vec!["fast"]);
+        assert_eq!(registry.next_due(), Some(start + 10 * SECOND));
+
+        // Nothing is due before the next tick
+        assert!(registry.collect_due("n", start + 9 * SECOND).is_empty());
+
+        let mut runs = 0;
+        let mut now = start + 10 * SECOND;
+        while now < start + 60 * SECOND {
+            runs += registry.collect_due("n", now).len();
+            now += 5 * SECOND;
+        }
+        assert_eq!(runs, 10);
+        assert_eq!(names(&registry.collect_due("n", now)), vec!["fast", "slow"]);
+    }
+
+    #[test]
+    fn test_replace_keeps_running_probes() {
+        let teardowns = Arc::new(AtomicUsize::new(0));
+        let mut registry = ProbeRegistry::default();
+        registry.register(Box::new(FakeProbe::new("a", &teardowns)), 5 * SECOND);
+        registry.register(Box::new(FakeProbe::new("b", &teardowns)), 60 * SECOND);
+        registry.init();
+
+        let start = registry.next_due().unwrap();
+        registry.collect_due("n", start);
+
+        let mut reloaded = ProbeRegistry::default();
+        reloaded.register(Box::new(FakeProbe::new("a", &teardowns)), 10 * SECOND);
+        reloaded.register(
+            Box::new(FakeProbe {
+                fail_init: true,
+                ..FakeProbe::new("c", &teardowns)
+            }),
+            SECOND,
+        );
+        reloaded.register(Box::new(FakeProbe::new("d", &teardowns)), 60 * SECOND);
+        registry.replace(reloaded);
+
+        assert_eq!(registry.names(), vec!["a", "d"]);
+        // "b" is torn down; the "a" built for the reload is never used
+        assert_eq!(teardowns.load(Ordering::SeqCst), 1);
+
+        // "a" stays on its schedule, "d" starts right away
+        let now = Instant::now();
+        assert_eq!(names(&registry.collect_due("n", now)), vec!["d"]);
+        assert_eq!(
+            names(&registry.collect_due("n", start + 5 * SECOND)),
+            vec!["a"]
+        );
+        assert!(registry.next_due().unwrap() > start + 5 * SECOND);
+    }
+
+    #[test]
+    fn test_missed_ticks_are_skipped() {
+        let teardowns = Arc::new(AtomicUsize::new(0));
+        let mut registry = ProbeRegistry::default();
+        registry.register(Box::new(FakeProbe::new("a", &teardowns)), 5 * SECOND);
+        registry.init();
+
+        let start = registry.next_due().unwrap();
+        registry.collect_due("n", start);
+
+        // Stalled for three and a bit periods: one collection, then back on the grid
+        let resumed = start + 17 * SECOND;
+        assert_eq!(registry.collect_due("n", resumed).len(), 1);
+        assert_eq!(registry.next_due(), Some(start + 20 * SECOND));
+    }
+}
@@ -1,10 +1,16 @@
 use ferrview_common::ProbeDataPoint;
-use sysinfo::System;
+use ferrview_common::metrics::{CPU_CORE_COUNT, CPU_CORE_FREQUENCY_MHZ, CPU_CORE_USAGE_PERCENT};
+use std::collections::BTreeMap;
+use std::time::Instant;
+use sysinfo::{MINIMUM_CPU_UPDATE_INTERVAL, System};
 use tracing::info;
 
+use crate::probes::{Probe, ProbeError};
 use crate::utils::timestamp::get_utc_timestamp;
 
-pub fn probe_cpu(sys: &System, node_id: &str) -> Vec<ProbeDataPoint> {
+/// Readings of every core; usage only with `usage`, when `sys` was
+/// refreshed long enough apart to measure it
+pub fn probe_cpu(sys: &System, node_id: &str, usage: bool) -> Vec<ProbeDataPoint> {
     info!("Starting CPU probe");
 
     let core_count = sys.cpus().len();
@@ -16,31 +22,79 @@ pub fn probe_cpu(sys: &System, node_id: &str) -> Vec<ProbeDataPoint> {
     // Adding core count
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "cpu_core_count".to_string(),
-        probe_value: core_count.to_string(),
+        timestamp,
+        probe_type: CPU_CORE_COUNT.probe_type.to_string(),
+        probe_name: CPU_CORE_COUNT.name.to_string(),
+        probe_value: core_count.into(),
+        labels: BTreeMap::new(),
     });
 
     // Add per-core metrics
     for (idx, cpu) in sys.cpus().iter().enumerate() {
+        let labels = BTreeMap::from([("core".to_string(), idx.to_string())]);
+
         // Frequency
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("cpu_core_{}_frequency_mhz", idx),
-            probe_value: cpu.frequency().to_string(),
+            timestamp,
+            probe_type: CPU_CORE_FREQUENCY_MHZ.probe_type.to_string(),
+            probe_name: CPU_CORE_FREQUENCY_MHZ.name.to_string(),
+            probe_value: cpu.frequency().into(),
+            labels: labels.clone(),
         });
 
-        data_points.push(ProbeDataPoint {
-            node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("cpu_core_{}_usage_percent", idx),
-            probe_value: cpu.cpu_usage().to_string(),
-        });
+        if usage {
+            data_points.push(ProbeDataPoint {
+                node_id: node_id.to_string(),
+                timestamp,
+                probe_type: CPU_CORE_USAGE_PERCENT.probe_type.to_string(),
+                probe_name: CPU_CORE_USAGE_PERCENT.name.to_string(),
+                probe_value: cpu.cpu_usage().into(),
+                labels,
+            });
+        }
     }
 
     data_points
 }
+
+/// Per-core usage and frequency; usage is measured since the previous collection
+pub struct CpuProbe {
+    sys: System,
+    /// When usage was last sampled
+    refreshed_at: Option<Instant>,
+}
+
+impl CpuProbe {
+    pub fn new() -> Self {
+        Self {
+            sys: System::new(),
+            refreshed_at: None,
+        }
+    }
+}
+
+impl Probe for CpuProbe {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn init(&mut self) -> Result<(), ProbeError> {
+        // Usage needs two samples; take the first now so that it can be
+        // measured on the next collection
+        self.sys.refresh_cpu_usage();
+        self.refreshed_at = Some(Instant::now());
+        Ok(())
+    }
+
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+        // Samples taken too close together give no usage; it is left out
+        // until the next collection rather than waited for
+        let usage = self
+            .refreshed_at
+            .is_some_and(|at| at.elapsed() >= MINIMUM_CPU_UPDATE_INTERVAL);
+        self.sys.refresh_cpu_all();
+        self.refreshed_at = Some(Instant::now());
+        Ok(probe_cpu(&self.sys, node_id, usage))
+    }
+}
@@ -1,7 +1,13 @@
 use ferrview_common::ProbeDataPoint;
+use ferrview_common::metrics::{
+    DISK_AVAILABLE_BYTES, DISK_COUNT, DISK_FILESYSTEM_TYPE, DISK_NAME, DISK_TOTAL_BYTES,
+    DISK_USAGE_PERCENT,
+};
+use std::collections::BTreeMap;
 use sysinfo::Disks;
 use tracing::info;
 
+use crate::probes::{Probe, ProbeError};
 use crate::utils::timestamp::get_utc_timestamp;
 
 pub fn probe_disks(disks: &mut Disks, node_id: &str) -> Vec<ProbeDataPoint> {
@@ -18,55 +24,64 @@ pub fn probe_disks(disks: &mut Disks, node_id: &str) -> Vec<ProbeDataPoint> {
     // Add disk count metric
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "disk_count".to_string(),
-        probe_value: disk_count.to_string(),
+        timestamp,
+        probe_type: DISK_COUNT.probe_type.to_string(),
+        probe_name: DISK_COUNT.name.to_string(),
+        probe_value: disk_count.into(),
+        labels: BTreeMap::new(),
     });
 
-    // Collect metrics for each disk
-    for (idx, disk) in disks.iter().enumerate() {
+    // Collect metrics for each disk, identified by mount point
+    for disk in disks.iter() {
         let disk_name = disk.name().to_string_lossy().into_owned();
-        info!("Processing disk {}: {}", idx, disk_name);
+        let mount_point = disk.mount_point().to_string_lossy().into_owned();
+        info!("Processing disk {} mounted at {}", disk_name, mount_point);
+
+        let labels = BTreeMap::from([("mount".to_string(), mount_point)]);
 
-        // Disk name
+        // Disk (device) name
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("disk_{}_name", idx),
-            probe_value: disk_name,
+            timestamp,
+            probe_type: DISK_NAME.probe_type.to_string(),
+            probe_name: DISK_NAME.name.to_string(),
+            probe_value: disk_name.into(),
+            labels: labels.clone(),
         });
 
         // Total space
         let total_space = disk.total_space();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("disk_{}_total_bytes", idx),
-            probe_value: total_space.to_string(),
+            timestamp,
+            probe_type: DISK_TOTAL_BYTES.probe_type.to_string(),
+            probe_name: DISK_TOTAL_BYTES.name.to_string(),
+            probe_value: total_space.into(),
+            labels: labels.clone(),
         });
 
         // Available space
         let available_space = disk.available_space();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("disk_{}_available_bytes", idx),
-            probe_value: available_space.to_string(),
+            timestamp,
+            probe_type: DISK_AVAILABLE_BYTES.probe_type.to_string(),
+            probe_name: DISK_AVAILABLE_BYTES.name.to_string(),
+            probe_value: available_space.into(),
+            labels: labels.clone(),
         });
 
         // Calculate and add usage percentage
         if total_space > 0 {
-            let usage_percent = ((total_space - available_space) as f64 / total_space as f64) * 100.0;
+            let usage_percent =
+                ((total_space - available_space) as f64 / total_space as f64) * 100.0;
             data_points.push(ProbeDataPoint {
                 node_id: node_id.to_string(),
-                timestamp: timestamp.clone(),
-                probe_type: "sysinfo".to_string(),
-                probe_name: format!("disk_{}_usage_percent", idx),
-                probe_value: format!("{:.2}", usage_percent),
+                timestamp,
+                probe_type: DISK_USAGE_PERCENT.probe_type.to_string(),
+                probe_name: DISK_USAGE_PERCENT.name.to_string(),
+                probe_value: ((usage_percent * 100.0).round() / 100.0).into(),
+                labels: labels.clone(),
             });
         }
 
@@ -74,23 +89,36 @@ pub fn probe_disks(disks: &mut Disks, node_id: &str) -> Vec<ProbeDataPoint> {
         let fs_type = disk.file_system().to_string_lossy().into_owned();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("disk_{}_filesystem_type", idx),
-            probe_value: fs_type,
-        });
-
-        // Mount point
-        let mount_point = disk.mount_point().to_string_lossy().into_owned();
-        data_points.push(ProbeDataPoint {
-            node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("disk_{}_mount_point", idx),
-            probe_value: mount_point,
+            timestamp,
+            probe_type: DISK_FILESYSTEM_TYPE.probe_type.to_string(),
+            probe_name: DISK_FILESYSTEM_TYPE.name.to_string(),
+            probe_value: fs_type.into(),
+            labels,
         });
     }
 
     info!("Collected {} disk metrics", data_points.len());
     data_points
-}
\ No newline at end of file
+}
+
+pub struct DiskProbe {
+    disks: Disks,
+}
+
+impl DiskProbe {
+    pub fn new() -> Self {
+        Self {
+            disks: Disks::new_with_refreshed_list(),
+        }
+    }
+}
+
+impl Probe for DiskProbe {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+        Ok(probe_disks(&mut self.disks, node_id))
+    }
+}
@@ -1,7 +1,13 @@
 use ferrview_common::ProbeDataPoint;
+use ferrview_common::metrics::{
+    MEMORY_AVAILABLE_BYTES, MEMORY_TOTAL_BYTES, MEMORY_USED_BYTES, SWAP_TOTAL_BYTES,
+    SWAP_USED_BYTES,
+};
+use std::collections::BTreeMap;
 use sysinfo::System;
 use tracing::info;
 
+use crate::probes::{Probe, ProbeError};
 use crate::utils::timestamp::get_utc_timestamp;
 
 pub fn probe_memory(sys: &System, node_id: &str) -> Vec<ProbeDataPoint> {
@@ -19,47 +25,73 @@ pub fn probe_memory(sys: &System, node_id: &str) -> Vec<ProbeDataPoint> {
     // Adding total memory
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "memory_total_bytes".to_string(),
-        probe_value: total_memory.to_string(),
+        timestamp,
+        probe_type: MEMORY_TOTAL_BYTES.probe_type.to_string(),
+        probe_name: MEMORY_TOTAL_BYTES.name.to_string(),
+        probe_value: total_memory.into(),
+        labels: BTreeMap::new(),
     });
 
     // Adding used memory
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "memory_used_bytes".to_string(),
-        probe_value: used_memory.to_string(),
+        timestamp,
+        probe_type: MEMORY_USED_BYTES.probe_type.to_string(),
+        probe_name: MEMORY_USED_BYTES.name.to_string(),
+        probe_value: used_memory.into(),
+        labels: BTreeMap::new(),
     });
 
     // Adding available memory
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "memory_available_bytes".to_string(),
-        probe_value: available_memory.to_string(),
+        timestamp,
+        probe_type: MEMORY_AVAILABLE_BYTES.probe_type.to_string(),
+        probe_name: MEMORY_AVAILABLE_BYTES.name.to_string(),
+        probe_value: available_memory.into(),
+        labels: BTreeMap::new(),
     });
 
     // Adding total swap
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "swap_total_bytes".to_string(),
-        probe_value: total_swap.to_string(),
+        timestamp,
+        probe_type: SWAP_TOTAL_BYTES.probe_type.to_string(),
+        probe_name: SWAP_TOTAL_BYTES.name.to_string(),
+        probe_value: total_swap.into(),
+        labels: BTreeMap::new(),
     });
 
     // Adding used swap
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "swap_used_bytes".to_string(),
-        probe_value: used_swap.to_string(),
+        timestamp,
+        probe_type: SWAP_USED_BYTES.probe_type.to_string(),
+        probe_name: SWAP_USED_BYTES.name.to_string(),
+        probe_value: used_swap.into(),
+        labels: BTreeMap::new(),
     });
 
     data_points
 }
+
+pub struct MemoryProbe {
+    sys: System,
+}
+
+impl MemoryProbe {
+    pub fn new() -> Self {
+        Self { sys: System::new() }
+    }
+}
+
+impl Probe for MemoryProbe {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+        self.sys.refresh_memory();
+        Ok(probe_memory(&self.sys, node_id))
+    }
+}
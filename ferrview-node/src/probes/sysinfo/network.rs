@@ -1,7 +1,14 @@
 use ferrview_common::ProbeDataPoint;
+use ferrview_common::metrics::{
+    NETWORK_ERRORS_ON_RECEIVED, NETWORK_ERRORS_ON_TRANSMITTED, NETWORK_INTERFACE_COUNT,
+    NETWORK_PACKETS_RECEIVED, NETWORK_PACKETS_TRANSMITTED, NETWORK_TOTAL_RECEIVED_BYTES,
+    NETWORK_TOTAL_TRANSMITTED_BYTES,
+};
+use std::collections::BTreeMap;
 use sysinfo::Networks;
 use tracing::info;
 
+use crate::probes::{Probe, ProbeError};
 use crate::utils::timestamp::get_utc_timestamp;
 
 pub fn probe_networks(networks: &mut Networks, node_id: &str) -> Vec<ProbeDataPoint> {
@@ -18,87 +25,108 @@ pub fn probe_networks(networks: &mut Networks, node_id: &str) -> Vec<ProbeDataPo
     // Add network interface count metric
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "network_interface_count".to_string(),
-        probe_value: network_count.to_string(),
+        timestamp,
+        probe_type: NETWORK_INTERFACE_COUNT.probe_type.to_string(),
+        probe_name: NETWORK_INTERFACE_COUNT.name.to_string(),
+        probe_value: network_count.into(),
+        labels: BTreeMap::new(),
     });
 
-    // Collect metrics for each network interface
-    for (idx, (interface_name, data)) in networks.iter().enumerate() {
-        let name = interface_name.to_string();
-        info!("Processing network interface {}: {}", idx, name);
+    // Collect metrics for each network interface, identified by name
+    for (interface_name, data) in networks.iter() {
+        info!("Processing network interface {}", interface_name);
 
-        // Interface name
-        data_points.push(ProbeDataPoint {
-            node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("network_interface_{}_name", idx),
-            probe_value: name,
-        });
+        let labels = BTreeMap::from([("iface".to_string(), interface_name.to_string())]);
 
         // Total received bytes
         let received = data.total_received();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("network_interface_{}_total_received_bytes", idx),
-            probe_value: received.to_string(),
+            timestamp,
+            probe_type: NETWORK_TOTAL_RECEIVED_BYTES.probe_type.to_string(),
+            probe_name: NETWORK_TOTAL_RECEIVED_BYTES.name.to_string(),
+            probe_value: received.into(),
+            labels: labels.clone(),
         });
 
         // Total transmitted bytes
         let transmitted = data.total_transmitted();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("network_interface_{}_total_transmitted_bytes", idx),
-            probe_value: transmitted.to_string(),
+            timestamp,
+            probe_type: NETWORK_TOTAL_TRANSMITTED_BYTES.probe_type.to_string(),
+            probe_name: NETWORK_TOTAL_TRANSMITTED_BYTES.name.to_string(),
+            probe_value: transmitted.into(),
+            labels: labels.clone(),
         });
 
         // Received packets
         let packets_received = data.packets_received();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("network_interface_{}_packets_received", idx),
-            probe_value: packets_received.to_string(),
+            timestamp,
+            probe_type: NETWORK_PACKETS_RECEIVED.probe_type.to_string(),
+            probe_name: NETWORK_PACKETS_RECEIVED.name.to_string(),
+            probe_value: packets_received.into(),
+            labels: labels.clone(),
         });
 
         // Transmitted packets
         let packets_transmitted = data.packets_transmitted();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("network_interface_{}_packets_transmitted", idx),
-            probe_value: packets_transmitted.to_string(),
+            timestamp,
+            probe_type: NETWORK_PACKETS_TRANSMITTED.probe_type.to_string(),
+            probe_name: NETWORK_PACKETS_TRANSMITTED.name.to_string(),
+            probe_value: packets_transmitted.into(),
+            labels: labels.clone(),
         });
 
         // Errors on receive
         let errors_on_received = data.errors_on_received();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("network_interface_{}_errors_on_received", idx),
-            probe_value: errors_on_received.to_string(),
+            timestamp,
+            probe_type: NETWORK_ERRORS_ON_RECEIVED.probe_type.to_string(),
+            probe_name: NETWORK_ERRORS_ON_RECEIVED.name.to_string(),
+            probe_value: errors_on_received.into(),
+            labels: labels.clone(),
         });
 
         // Errors on transmit
         let errors_on_transmitted = data.errors_on_transmitted();
         data_points.push(ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("network_interface_{}_errors_on_transmitted", idx),
-            probe_value: errors_on_transmitted.to_string(),
+            timestamp,
+            probe_type: NETWORK_ERRORS_ON_TRANSMITTED.probe_type.to_string(),
+            probe_name: NETWORK_ERRORS_ON_TRANSMITTED.name.to_string(),
+            probe_value: errors_on_transmitted.into(),
+            labels,
         });
     }
 
     info!("Collected {} network metrics", data_points.len());
     data_points
 }
+
+pub struct NetworkProbe {
+    networks: Networks,
+}
+
+impl NetworkProbe {
+    pub fn new() -> Self {
+        Self {
+            networks: Networks::new_with_refreshed_list(),
+        }
+    }
+}
+
+impl Probe for NetworkProbe {
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+        Ok(probe_networks(&mut self.networks, node_id))
+    }
+}
@@ -1,7 +1,12 @@
 use ferrview_common::ProbeDataPoint;
+use ferrview_common::metrics::{
+    SYSTEM_CPU_ARCH, SYSTEM_HOSTNAME, SYSTEM_KERNEL_VERSION, SYSTEM_OS_NAME, SYSTEM_OS_VERSION,
+};
+use std::collections::BTreeMap;
 use sysinfo::System;
 use tracing::info;
 
+use crate::probes::{Probe, ProbeError};
 use crate::utils::timestamp::get_utc_timestamp;
 
 pub fn probe_static_info(node_id: &str) -> Vec<ProbeDataPoint> {
@@ -12,41 +17,59 @@ pub fn probe_static_info(node_id: &str) -> Vec<ProbeDataPoint> {
     let data_points = vec![
         ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: "system_cpu_arch".to_string(),
-            probe_value: System::cpu_arch(),
+            timestamp,
+            probe_type: SYSTEM_CPU_ARCH.probe_type.to_string(),
+            probe_name: SYSTEM_CPU_ARCH.name.to_string(),
+            probe_value: System::cpu_arch().into(),
+            labels: BTreeMap::new(),
         },
         ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: "system_os_name".to_string(),
-            probe_value: System::name().unwrap_or_default(),
+            timestamp,
+            probe_type: SYSTEM_OS_NAME.probe_type.to_string(),
+            probe_name: SYSTEM_OS_NAME.name.to_string(),
+            probe_value: System::name().unwrap_or_default().into(),
+            labels: BTreeMap::new(),
         },
         ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: "system_kernel_version".to_string(),
-            probe_value: System::kernel_version().unwrap_or_default(),
+            timestamp,
+            probe_type: SYSTEM_KERNEL_VERSION.probe_type.to_string(),
+            probe_name: SYSTEM_KERNEL_VERSION.name.to_string(),
+            probe_value: System::kernel_version().unwrap_or_default().into(),
+            labels: BTreeMap::new(),
         },
         ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: "system_os_version".to_string(),
-            probe_value: System::os_version().unwrap_or_default(),
+            timestamp,
+            probe_type: SYSTEM_OS_VERSION.probe_type.to_string(),
+            probe_name: SYSTEM_OS_VERSION.name.to_string(),
+            probe_value: System::os_version().unwrap_or_default().into(),
+            labels: BTreeMap::new(),
         },
         ProbeDataPoint {
             node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: "system_hostname".to_string(),
-            probe_value: System::host_name().unwrap_or_default(),
+            timestamp,
+            probe_type: SYSTEM_HOSTNAME.probe_type.to_string(),
+            probe_name: SYSTEM_HOSTNAME.name.to_string(),
+            probe_value: System::host_name().unwrap_or_default().into(),
+            labels: BTreeMap::new(),
         },
     ];
 
     info!("Collected {} static system metrics", data_points.len());
     data_points
 }
+
+/// Architecture, OS, kernel and hostname
+pub struct StaticInfoProbe;
+
+impl Probe for StaticInfoProbe {
+    fn name(&self) -> &'static str {
+        "static_info"
+    }
+
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+        Ok(probe_static_info(node_id))
+    }
+}
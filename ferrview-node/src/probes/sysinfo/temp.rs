@@ -1,7 +1,13 @@
 use ferrview_common::ProbeDataPoint;
+use ferrview_common::metrics::{
+    TEMPERATURE_CELSIUS, TEMPERATURE_CRITICAL_CELSIUS, TEMPERATURE_MAX_CELSIUS,
+    TEMPERATURE_SENSOR_COUNT,
+};
+use std::collections::BTreeMap;
 use sysinfo::Components;
 use tracing::info;
 
+use crate::probes::{Probe, ProbeError};
 use crate::utils::timestamp::get_utc_timestamp;
 
 pub fn probe_temperature(components: &mut Components, node_id: &str) -> Vec<ProbeDataPoint> {
@@ -18,10 +24,11 @@ pub fn probe_temperature(components: &mut Components, node_id: &str) -> Vec<Prob
     // Add sensor count metric
     data_points.push(ProbeDataPoint {
         node_id: node_id.to_string(),
-        timestamp: timestamp.clone(),
-        probe_type: "sysinfo".to_string(),
-        probe_name: "temperature_sensor_count".to_string(),
-        probe_value: component_count.to_string(),
+        timestamp,
+        probe_type: TEMPERATURE_SENSOR_COUNT.probe_type.to_string(),
+        probe_name: TEMPERATURE_SENSOR_COUNT.name.to_string(),
+        probe_value: component_count.into(),
+        labels: BTreeMap::new(),
     });
 
     if component_count == 0 {
@@ -29,8 +36,8 @@ pub fn probe_temperature(components: &mut Components, node_id: &str) -> Vec<Prob
         return data_points;
     }
 
-    for (idx, component) in components.iter().enumerate() {
-        let label = component.label();
+    for component in components.iter() {
+        let labels = BTreeMap::from([("sensor".to_string(), component.label().to_string())]);
         let temperature = component.temperature();
         let max_temp = component.max();
         let critical_temp = component.critical();
@@ -39,30 +46,23 @@ pub fn probe_temperature(components: &mut Components, node_id: &str) -> Vec<Prob
         if let Some(temp) = temperature {
             data_points.push(ProbeDataPoint {
                 node_id: node_id.to_string(),
-                timestamp: timestamp.clone(),
-                probe_type: "sysinfo".to_string(),
-                probe_name: format!("temperature_sensor_{}_celsius", idx),
-                probe_value: temp.to_string(),
+                timestamp,
+                probe_type: TEMPERATURE_CELSIUS.probe_type.to_string(),
+                probe_name: TEMPERATURE_CELSIUS.name.to_string(),
+                probe_value: temp.into(),
+                labels: labels.clone(),
             });
         }
 
-        // Add sensor label as a separate metric for identification
-        data_points.push(ProbeDataPoint {
-            node_id: node_id.to_string(),
-            timestamp: timestamp.clone(),
-            probe_type: "sysinfo".to_string(),
-            probe_name: format!("temperature_sensor_{}_label", idx),
-            probe_value: label.to_string(),
-        });
-
         // Add max temperature if available
         if let Some(max) = max_temp {
             data_points.push(ProbeDataPoint {
                 node_id: node_id.to_string(),
-                timestamp: timestamp.clone(),
-                probe_type: "sysinfo".to_string(),
-                probe_name: format!("temperature_sensor_{}_max_celsius", idx),
-                probe_value: max.to_string(),
+                timestamp,
+                probe_type: TEMPERATURE_MAX_CELSIUS.probe_type.to_string(),
+                probe_name: TEMPERATURE_MAX_CELSIUS.name.to_string(),
+                probe_value: max.into(),
+                labels: labels.clone(),
             });
         }
 
@@ -70,13 +70,36 @@ pub fn probe_temperature(components: &mut Components, node_id: &str) -> Vec<Prob
         if let Some(critical) = critical_temp {
             data_points.push(ProbeDataPoint {
                 node_id: node_id.to_string(),
-                timestamp: timestamp.clone(),
-                probe_type: "sysinfo".to_string(),
-                probe_name: format!("temperature_sensor_{}_critical_celsius", idx),
-                probe_value: critical.to_string(),
+                timestamp,
+                probe_type: TEMPERATURE_CRITICAL_CELSIUS.probe_type.to_string(),
+                probe_name: TEMPERATURE_CRITICAL_CELSIUS.name.to_string(),
+                probe_value: critical.into(),
+                labels: labels.clone(),
             });
         }
     }
 
     data_points
 }
+
+pub struct TemperatureProbe {
+    components: Components,
+}
+
+impl TemperatureProbe {
+    pub fn new() -> Self {
+        Self {
+            components: Components::new_with_refreshed_list(),
+        }
+    }
+}
+
+impl Probe for TemperatureProbe {
+    fn name(&self) -> &'static str {
+        "temperature"
+    }
+
+    fn collect(&mut self, node_id: &str) -> Result<Vec<ProbeDataPoint>, ProbeError> {
+        Ok(probe_temperature(&mut self.components, node_id))
+    }
+}
@@ -1 +1,3 @@
+#[cfg(test)]
+pub mod test_dir;
 pub mod timestamp;
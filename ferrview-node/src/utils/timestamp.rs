@@ -1,4 +1,4 @@
-use time::{OffsetDateTime, format_description::FormatItem, macros::format_description};
+use time::{format_description::FormatItem, macros::format_description};
 
 pub fn get_utc_formatter() -> &'static [FormatItem<'static>] {
     format_description!(
@@ -6,10 +6,9 @@ pub fn get_utc_formatter() -> &'static [FormatItem<'static>] {
     )
 }
 
-pub fn get_utc_timestamp() -> String {
-    OffsetDateTime::now_utc()
-        .format(get_utc_formatter())
-        .unwrap()
+/// Current time as Unix epoch milliseconds, the timestamp of every data point
+pub fn get_utc_timestamp() -> i64 {
+    ferrview_common::timestamp::now_millis()
 }
 
 #[cfg(test)]
@@ -17,41 +16,19 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_timestamp_format_length() {
+    fn test_timestamp_is_epoch_millis() {
         let ts = get_utc_timestamp();
 
-        // Should be exactly 20 chars: YYYY-MM-DDTHH:MM:SSZ
-        assert_eq!(ts.len(), 20);
+        // Later than 2024-01-01 and earlier than 2100-01-01, in milliseconds
+        assert!(ts > 1_704_067_200_000);
+        assert!(ts < 4_102_444_800_000);
     }
 
     #[test]
-    fn test_timestamp_has_required_chars() {
-        let ts = get_utc_timestamp();
-
-        assert!(ts.ends_with('Z'), "Must end with Z (UTC)");
-        assert!(ts.contains('T'), "Must contain T separator");
-        assert_eq!(ts.chars().nth(4), Some('-'), "Year separator");
-        assert_eq!(ts.chars().nth(7), Some('-'), "Month separator");
-        assert_eq!(ts.chars().nth(10), Some('T'), "Date/time separator");
-        assert_eq!(ts.chars().nth(13), Some(':'), "Hour separator");
-        assert_eq!(ts.chars().nth(16), Some(':'), "Minute separator");
-    }
-
-    #[test]
-    fn test_timestamp_components_are_digits() {
-        let ts = get_utc_timestamp();
+    fn test_timestamp_is_monotonic_enough() {
+        let first = get_utc_timestamp();
+        let second = get_utc_timestamp();
 
-        // Year
-        assert!(ts[0..4].chars().all(|c| c.is_ascii_digit()));
-        // Month
-        assert!(ts[5..7].chars().all(|c| c.is_ascii_digit()));
-        // Day
-        assert!(ts[8..10].chars().all(|c| c.is_ascii_digit()));
-        // Hour
-        assert!(ts[11..13].chars().all(|c| c.is_ascii_digit()));
-        // Minute
-        assert!(ts[14..16].chars().all(|c| c.is_ascii_digit()));
-        // Second
-        assert!(ts[17..19].chars().all(|c| c.is_ascii_digit()));
+        assert!(second >= first);
     }
 }